@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use crate::managed_push::{self, ApprovalLimits, ApprovalScope, PushTarget};
+
+// History events shown by `approvals list`.
+const RECENT_HISTORY: usize = 10;
+
+pub fn parse_scope(value: &str) -> Result<ApprovalScope, String> {
+    match value {
+        "once" => Ok(ApprovalScope::Once),
+        "persistent" => Ok(ApprovalScope::Persistent),
+        "push" => Ok(ApprovalScope::Push),
+        other => Err(format!("invalid approval scope '{other}'")),
+    }
+}
+
+/// Pending requests and approved repositories, as shown by
+/// `claude-sandbox approvals list`.
+pub fn list(state: &Path, workspace_root: &Path) -> Result<String, String> {
+    let candidates = managed_push::list_candidates(state)?;
+    let approvals = managed_push::list_approvals(state)?;
+    let mut report = String::from("Pending requests:\n");
+    if candidates.is_empty() {
+        report.push_str("  none\n");
+    }
+    for (id, candidate) in candidates {
+        let repository = &candidate.repository;
+        report.push_str(&format!(
+            "  {id}  {}  {} (branch {})\n",
+            repository.relative_path,
+            repository.origin,
+            repository.branch.as_deref().unwrap_or("detached HEAD")
+        ));
+        if let Some(previous) = &candidate.previously_approved_origin {
+            report.push_str(&format!("    previously approved: {previous}\n"));
+        }
+        if let Some(push) = &candidate.push {
+            report.push_str(&format!(
+                "    push {} at {}\n",
+                push.target.reference, push.target.oid
+            ));
+            for line in push.commits.iter().chain(&push.diffstat) {
+                report.push_str(&format!("      {line}\n"));
+            }
+        }
+    }
+    report.push_str("\nApproved repositories:\n");
+    if approvals.is_empty() {
+        report.push_str("  none\n");
+    }
+    for (_, approval) in approvals {
+        let status = match managed_push::suspension(workspace_root, &approval) {
+            None => managed_push::scope_label(&approval),
+            Some(reason) => format!("suspended — {reason}"),
+        };
+        report.push_str(&format!(
+            "  {}  {}  {status}\n",
+            approval.repository.relative_path, approval.repository.origin
+        ));
+    }
+    let history = managed_push::list_history(state)?;
+    if !history.is_empty() {
+        report.push_str("\nRecent history:\n");
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for event in history.into_iter().take(RECENT_HISTORY) {
+        report.push_str(&format!(
+            "  {} ago  {}  {}  {}\n",
+            managed_push::format_duration(now.saturating_sub(event.at)),
+            event.event.as_str(),
+            event.relative_path,
+            event.detail
+        ));
+    }
+    Ok(report)
+}
+
+pub fn approve(
+    state: &Path,
+    workspace_root: &Path,
+    id: &str,
+    scope: ApprovalScope,
+    reviewed: Option<&PushTarget>,
+    limits: &ApprovalLimits,
+) -> Result<String, String> {
+    let repository =
+        managed_push::approve_candidate(state, workspace_root, id, scope, reviewed, limits)?;
+    Ok(managed_push::approved_message(
+        scope,
+        &repository.relative_path,
+    ))
+}
+
+pub fn dismiss(state: &Path, id: &str) -> Result<String, String> {
+    let candidate = managed_push::read_candidate(state, id)?;
+    managed_push::remove_candidate(state, id)?;
+    Ok(format!(
+        "Dismissed the pending request from {}.",
+        candidate.repository.relative_path
+    ))
+}
+
+pub fn revoke(state: &Path, relative_path: &str) -> Result<String, String> {
+    if managed_push::read_approval(state, relative_path)?.is_none() {
+        return Err(format!("{relative_path} is not approved"));
+    }
+    managed_push::revoke(state, relative_path)?;
+    Ok(format!("Revoked pushes from {relative_path}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn lists_approves_and_revokes_from_the_terminal() {
+        let root = std::env::temp_dir().join(format!(
+            "claude-sandbox-approvals-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let workspace = root.join("workspace");
+        let state = root.join("state");
+        fs::create_dir_all(workspace.join("project")).unwrap();
+        for args in [
+            &["init", "-q", "--initial-branch=main"][..],
+            &[
+                "remote",
+                "add",
+                "origin",
+                "git@example.test:org/project.git",
+            ],
+        ] {
+            assert!(
+                Command::new("git")
+                    .arg("-C")
+                    .arg(workspace.join("project"))
+                    .args(args)
+                    .status()
+                    .unwrap()
+                    .success()
+            );
+        }
+        let (_, repository) =
+            managed_push::resolve_relative_repository(&workspace, "project").unwrap();
+        let id = managed_push::record_candidate(&state, &repository, None, None).unwrap();
+
+        let listed = list(&state, &workspace).unwrap();
+        assert!(listed.contains(&format!(
+            "  {id}  project  git@example.test:org/project.git (branch main)"
+        )));
+        let limits = ApprovalLimits::default();
+        assert!(approve(&state, &workspace, &id, ApprovalScope::Push, None, &limits).is_err());
+        assert_eq!(
+            approve(
+                &state,
+                &workspace,
+                &id,
+                ApprovalScope::Persistent,
+                None,
+                &limits
+            )
+            .unwrap(),
+            "Approved persistent pushes from project."
+        );
+        let listed = list(&state, &workspace).unwrap();
+        assert!(listed.contains("Pending requests:\n  none\n"));
+        assert!(listed.contains("  project  git@example.test:org/project.git  persistent\n"));
+        assert!(listed.contains("  <1m ago  approve  project  persistent\n"));
+
+        assert!(dismiss(&state, &id).is_err());
+        revoke(&state, "project").unwrap();
+        assert!(revoke(&state, "project").is_err());
+        let events: Vec<_> = managed_push::list_history(&state)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.event)
+            .collect();
+        assert!(events.contains(&managed_push::HistoryKind::Revoke));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
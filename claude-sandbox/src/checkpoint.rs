@@ -0,0 +1,754 @@
+//! Host-side checkpoints of the workspace repository: every ref, `HEAD`, the
+//! index and the uncommitted working tree, kept in a bare mirror under
+//! `~/.claude-sandbox/projects/<instance>/checkpoints/` that the container
+//! cannot reach.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::git_proxy::{self, PinnedRepository, RepositoryConfigSnapshot, TemporaryDirectory};
+use crate::managed_push;
+
+/// Checkpoints kept per project; the oldest are pruned first.
+const MAX_CHECKPOINTS: usize = 50;
+
+/// The mirror's whole config, written when it is created. A mirror whose
+/// config differs is refused, so nothing planted there runs on the host.
+const MIRROR_CONFIG: &str = "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = true\n[receive]\n\tshallowUpdate = true\n";
+
+/// Receive-pack for pushes into the mirror. Local transport drops `-c`
+/// settings, so hooks are turned off in the command itself.
+const MIRROR_RECEIVE_PACK: &str = "git -c core.hooksPath=/dev/null receive-pack";
+
+/// Files are read and written byte for byte: no filter, end-of-line or
+/// encoding conversion named by the repository's attributes runs on the host.
+const NEUTRAL_ATTRIBUTES: &str = "* -filter -text -ident !eol !working-tree-encoding\n";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Checkpoint {
+    pub id: u64,
+    pub created_at: u64,
+    /// Why it was taken: `launch`, `manual` or `before restore`.
+    pub reason: String,
+    /// The repository's worktree root on the host.
+    pub repository: PathBuf,
+    /// `HEAD` as stored: `ref: <name>` or a detached object ID.
+    pub head: String,
+    /// Every direct ref and the object it names.
+    pub refs: BTreeMap<String, String>,
+    /// Commit recording the index.
+    pub index: String,
+    /// Commit recording the working tree, including untracked files that
+    /// are not ignored.
+    pub worktree: String,
+    /// Whether the index or working tree differed from `HEAD`.
+    pub dirty: bool,
+}
+
+pub fn state_dir(home: &Path, instance: &str) -> PathBuf {
+    home.join(".claude-sandbox/projects")
+        .join(instance)
+        .join("checkpoints")
+}
+
+fn mirror_dir(state: &Path) -> PathBuf {
+    state.join("mirror.git")
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Run `command` with `input` on stdin, returning its trimmed stdout.
+fn run(mut command: Command, input: &[u8], what: &str) -> Result<String, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("could not {what}: {error}"))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let input = input.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("could not {what}: {error}"))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!(
+            "could not {what}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Git against the mirror, with hooks off, once its config checks out.
+fn mirror_git(state: &Path) -> Result<Command, String> {
+    check_mirror(state)?;
+    let mut command = Command::new("git");
+    git_proxy::clear_repository_environment(&mut command);
+    command
+        .args(["-c", "core.hooksPath=/dev/null"])
+        .arg("--git-dir")
+        .arg(mirror_dir(state));
+    Ok(command)
+}
+
+fn check_mirror(state: &Path) -> Result<(), String> {
+    let path = mirror_dir(state).join("config");
+    let config =
+        fs::read(&path).map_err(|error| format!("could not read {}: {error}", path.display()))?;
+    if config != MIRROR_CONFIG.as_bytes() {
+        return Err(format!(
+            "{} was changed outside claude-sandbox; refusing to use the checkpoint mirror",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+fn ensure_mirror(state: &Path) -> Result<(), String> {
+    let mirror = mirror_dir(state);
+    if mirror.join("HEAD").exists() {
+        return check_mirror(state);
+    }
+    let mut command = Command::new("git");
+    git_proxy::clear_repository_environment(&mut command);
+    command
+        .args(["init", "--bare", "--quiet", "--template="])
+        .arg(&mirror);
+    run(command, b"", "create the checkpoint mirror")?;
+    fs::write(mirror.join("config"), MIRROR_CONFIG)
+        .map_err(|error| format!("could not configure the checkpoint mirror: {error}"))
+}
+
+/// The repository state at one moment, with the index and working tree
+/// recorded as commits in a scratch object directory.
+struct Capture {
+    head: String,
+    refs: BTreeMap<String, String>,
+    index: String,
+    worktree: String,
+    dirty: bool,
+}
+
+/// Git commands against a config snapshot of the pinned repository. New
+/// objects go to a private scratch directory; existing ones are read from
+/// it, the workspace object store and any `alternates`. Nothing the
+/// repository configures runs: its local config is left out of the
+/// snapshot, hooks are off, and attributes are neutralized.
+struct Session<'a> {
+    repository: &'a PinnedRepository,
+    snapshot: RepositoryConfigSnapshot,
+    scratch: TemporaryDirectory,
+    alternates: Vec<PathBuf>,
+}
+
+impl<'a> Session<'a> {
+    fn open(repository: &'a PinnedRepository, alternates: Vec<PathBuf>) -> Result<Self, String> {
+        let snapshot = RepositoryConfigSnapshot::create(repository, &[])?;
+        let info = snapshot.path().join("info");
+        fs::create_dir_all(&info)
+            .and_then(|()| fs::write(info.join("attributes"), NEUTRAL_ATTRIBUTES))
+            .map_err(|error| format!("could not prepare checkpoint attributes: {error}"))?;
+        // Keep the repository's own ignore rules for untracked files
+        if let Ok(exclude) = fs::read(repository.common_dir_command_path.join("info/exclude")) {
+            fs::write(info.join("exclude"), exclude)
+                .map_err(|error| format!("could not prepare checkpoint excludes: {error}"))?;
+        }
+        let scratch = TemporaryDirectory::create()?;
+        fs::create_dir(scratch.path.join("objects"))
+            .map_err(|error| format!("could not create checkpoint objects: {error}"))?;
+        Ok(Self {
+            repository,
+            snapshot,
+            scratch,
+            alternates,
+        })
+    }
+
+    /// Git with the snapshot's object store, for writing into the workspace.
+    fn workspace_git(&self) -> Command {
+        git_proxy::hardened_command(self.repository, &self.snapshot)
+    }
+
+    fn git(&self) -> Command {
+        let mut alternates = vec![self.repository.objects.command_path.clone()];
+        alternates.extend(self.alternates.iter().cloned());
+        let alternates: Vec<String> = alternates
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        let mut command = self.workspace_git();
+        command
+            .env(
+                "GIT_OBJECT_DIRECTORY",
+                self.scratch.command_path.join("objects"),
+            )
+            .env("GIT_ALTERNATE_OBJECT_DIRECTORIES", alternates.join(":"))
+            .env("GIT_INDEX_FILE", self.scratch.command_path.join("index"))
+            .env("GIT_NO_REPLACE_OBJECTS", "1");
+        command
+    }
+
+    fn run(&self, args: &[&str], what: &str) -> Result<String, String> {
+        let mut command = self.git();
+        command.args(args);
+        run(command, b"", what)
+    }
+
+    fn commit(&self, tree: &str, parents: &[String], message: &str) -> Result<String, String> {
+        let mut command = self.git();
+        for (key, value) in [
+            ("GIT_AUTHOR_NAME", "claude-sandbox"),
+            ("GIT_AUTHOR_EMAIL", "checkpoint@claude-sandbox.invalid"),
+            ("GIT_COMMITTER_NAME", "claude-sandbox"),
+            ("GIT_COMMITTER_EMAIL", "checkpoint@claude-sandbox.invalid"),
+        ] {
+            command.env(key, value);
+        }
+        command.args(["commit-tree", "--no-gpg-sign", tree, "-m", message]);
+        for parent in parents {
+            command.arg("-p").arg(parent);
+        }
+        run(command, b"", "record the checkpoint")
+    }
+
+    fn capture(&self) -> Result<Capture, String> {
+        let mut command = self.snapshot.command(self.repository);
+        let refs: BTreeMap<String, String> = git_proxy::reference_entries(&mut command)?
+            .into_iter()
+            .filter(|(_, _, symref)| symref.is_none())
+            .map(|(reference, oid, _)| (reference, oid))
+            .collect();
+        let head = fs::read_to_string(self.snapshot.path().join("git/HEAD"))
+            .map_err(|error| format!("could not read HEAD: {error}"))?
+            .trim()
+            .to_string();
+        let head_commit = match head.strip_prefix("ref: ") {
+            Some(target) => refs.get(target).cloned(),
+            None => Some(head.clone()),
+        };
+        match fs::copy(
+            self.repository.git_dir_command_path.join("index"),
+            self.scratch.path.join("index"),
+        ) {
+            Ok(_) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(format!("could not copy the index: {error}")),
+        }
+
+        let parents: Vec<String> = head_commit.iter().cloned().collect();
+        let index_tree = self.run(&["write-tree"], "record the index")?;
+        let index = self.commit(&index_tree, &parents, "checkpoint index")?;
+        self.run(&["add", "--all"], "record the working tree")?;
+        let worktree_tree = self.run(&["write-tree"], "record the working tree")?;
+        let mut worktree_parents = parents;
+        worktree_parents.push(index.clone());
+        let worktree = self.commit(&worktree_tree, &worktree_parents, "checkpoint working tree")?;
+        let head_tree = match &head_commit {
+            Some(commit) => self.run(&["rev-parse", &format!("{commit}^{{tree}}")], "read HEAD")?,
+            None => self.run(&["mktree"], "read HEAD")?,
+        };
+        Ok(Capture {
+            head,
+            refs,
+            index,
+            worktree,
+            dirty: index_tree != head_tree || worktree_tree != head_tree,
+        })
+    }
+
+    /// Push a capture and everything it references into the mirror under
+    /// `refs/checkpoints/<id>/`.
+    fn save(&self, state: &Path, capture: Capture, reason: &str) -> Result<Checkpoint, String> {
+        ensure_mirror(state)?;
+        let existing = load_all(state)?;
+        let id = existing.last().map_or(1, |checkpoint| checkpoint.id + 1);
+        let prefix = format!("refs/checkpoints/{id}");
+        let mut command = self.git();
+        command
+            .args([
+                "-c",
+                "push.gpgSign=false",
+                "-c",
+                "push.recurseSubmodules=no",
+                "push",
+                "--quiet",
+                "--no-verify",
+            ])
+            .arg(format!("--receive-pack={MIRROR_RECEIVE_PACK}"))
+            .arg(mirror_dir(state))
+            .arg(format!("refs/*:{prefix}/refs/*"))
+            .arg(format!("{}:{prefix}/index", capture.index))
+            .arg(format!("{}:{prefix}/worktree", capture.worktree));
+        run(command, b"", "save the checkpoint")?;
+
+        let checkpoint = Checkpoint {
+            id,
+            created_at: unix_time(),
+            reason: reason.to_string(),
+            repository: self.repository.worktree_path().to_path_buf(),
+            head: capture.head,
+            refs: capture.refs,
+            index: capture.index,
+            worktree: capture.worktree,
+            dirty: capture.dirty,
+        };
+        managed_push::atomic_write_json(&state.join(format!("{id}.json")), &checkpoint)?;
+        prune(state, &existing)?;
+        Ok(checkpoint)
+    }
+}
+
+fn load_all(state: &Path) -> Result<Vec<Checkpoint>, String> {
+    let entries = match fs::read_dir(state) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(format!("could not list {}: {error}", state.display())),
+    };
+    let mut checkpoints = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|error| format!("could not read checkpoint entry: {error}"))?
+            .path();
+        if path.extension().and_then(|value| value.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+        checkpoints.push(
+            serde_json::from_str::<Checkpoint>(&contents)
+                .map_err(|error| format!("invalid {}: {error}", path.display()))?,
+        );
+    }
+    checkpoints.sort_by_key(|checkpoint| checkpoint.id);
+    Ok(checkpoints)
+}
+
+fn read(state: &Path, id: u64) -> Result<Checkpoint, String> {
+    load_all(state)?
+        .into_iter()
+        .find(|checkpoint| checkpoint.id == id)
+        .ok_or_else(|| format!("no checkpoint {id}; run 'claude-sandbox checkpoint list'"))
+}
+
+/// Drop the oldest checkpoints beyond `MAX_CHECKPOINTS`, given the ones that
+/// existed before the newest was saved.
+fn prune(state: &Path, existing: &[Checkpoint]) -> Result<(), String> {
+    let excess = (existing.len() + 1).saturating_sub(MAX_CHECKPOINTS);
+    if excess == 0 {
+        return Ok(());
+    }
+    let mut deletions = String::new();
+    for checkpoint in &existing[..excess] {
+        let mut command = mirror_git(state)?;
+        command
+            .args(["for-each-ref", "--format=%(refname)"])
+            .arg(format!("refs/checkpoints/{}/", checkpoint.id));
+        for reference in run(command, b"", "prune checkpoints")?.lines() {
+            deletions.push_str(&format!("delete {reference}\n"));
+        }
+    }
+    let mut command = mirror_git(state)?;
+    command.args(["update-ref", "--stdin"]);
+    run(command, deletions.as_bytes(), "prune checkpoints")?;
+    for checkpoint in &existing[..excess] {
+        let path = state.join(format!("{}.json", checkpoint.id));
+        fs::remove_file(&path)
+            .map_err(|error| format!("could not remove {}: {error}", path.display()))?;
+    }
+    let mut command = mirror_git(state)?;
+    command.args(["gc", "--auto", "--quiet"]);
+    run(command, b"", "prune checkpoints").map(|_| ())
+}
+
+fn pin(root: &Path) -> Result<PinnedRepository, String> {
+    git_proxy::pin_repository(root, root)
+}
+
+fn check_repository(checkpoint: &Checkpoint, repository: &PinnedRepository) -> Result<(), String> {
+    if checkpoint.repository != repository.worktree_path() {
+        return Err(format!(
+            "checkpoint {} belongs to {}, not {}",
+            checkpoint.id,
+            checkpoint.repository.display(),
+            repository.worktree_path().display()
+        ));
+    }
+    Ok(())
+}
+
+fn short(oid: &str) -> &str {
+    &oid[..oid.len().min(12)]
+}
+
+fn head_label(head: &str) -> String {
+    match head.strip_prefix("ref: ") {
+        Some(target) => target
+            .strip_prefix("refs/heads/")
+            .unwrap_or(target)
+            .to_string(),
+        None => format!("detached at {}", short(head)),
+    }
+}
+
+/// Save a checkpoint of the repository at `root`.
+pub fn create(state: &Path, root: &Path, reason: &str) -> Result<Checkpoint, String> {
+    let repository = pin(root)?;
+    let session = Session::open(&repository, Vec::new())?;
+    let capture = session.capture()?;
+    session.save(state, capture, reason)
+}
+
+/// Checkpoints newest first, as shown by `claude-sandbox checkpoint list`.
+pub fn list(state: &Path) -> Result<String, String> {
+    let checkpoints = load_all(state)?;
+    if checkpoints.is_empty() {
+        return Ok(
+            "No checkpoints yet. Run 'claude-sandbox checkpoint create' to save one.".to_string(),
+        );
+    }
+    let now = unix_time();
+    let mut report = String::from("Checkpoints:\n");
+    for checkpoint in checkpoints.iter().rev() {
+        report.push_str(&format!(
+            "  {}  {} ago  {}  {}  {} ref(s){}\n",
+            checkpoint.id,
+            managed_push::format_duration(now.saturating_sub(checkpoint.created_at)),
+            checkpoint.reason,
+            head_label(&checkpoint.head),
+            checkpoint.refs.len(),
+            if checkpoint.dirty {
+                ", uncommitted changes"
+            } else {
+                ""
+            }
+        ));
+    }
+    Ok(report)
+}
+
+/// How refs, `HEAD` and the working tree changed since checkpoint `id`.
+pub fn diff(state: &Path, root: &Path, id: u64, patch: bool) -> Result<String, String> {
+    let checkpoint = read(state, id)?;
+    let repository = pin(root)?;
+    check_repository(&checkpoint, &repository)?;
+    check_mirror(state)?;
+    let session = Session::open(&repository, vec![mirror_dir(state).join("objects")])?;
+    let current = session.capture()?;
+
+    let mut report = format!(
+        "Since checkpoint {id} ({}, {} ago):\n",
+        checkpoint.reason,
+        managed_push::format_duration(unix_time().saturating_sub(checkpoint.created_at))
+    );
+    let mut changed = false;
+    if checkpoint.head != current.head {
+        changed = true;
+        report.push_str(&format!(
+            "  HEAD: {} -> {}\n",
+            head_label(&checkpoint.head),
+            head_label(&current.head)
+        ));
+    }
+    let names: BTreeSet<&String> = checkpoint.refs.keys().chain(current.refs.keys()).collect();
+    for name in names {
+        let line = match (checkpoint.refs.get(name), current.refs.get(name)) {
+            (Some(old), Some(new)) if old != new => {
+                format!("  {name}: {} -> {}\n", short(old), short(new))
+            }
+            (Some(old), None) => format!("  {name}: deleted (was {})\n", short(old)),
+            (None, Some(new)) => format!("  {name}: created at {}\n", short(new)),
+            _ => continue,
+        };
+        changed = true;
+        report.push_str(&line);
+    }
+    let tree_diff = session.run(
+        &[
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--no-textconv",
+            if patch { "--patch-with-stat" } else { "--stat" },
+            &checkpoint.worktree,
+            &current.worktree,
+        ],
+        "compare the working tree",
+    )?;
+    if !tree_diff.is_empty() {
+        changed = true;
+        report.push_str("\nWorking tree:\n");
+        report.push_str(&tree_diff);
+        report.push('\n');
+    }
+    if !changed {
+        report.push_str("  no changes\n");
+    }
+    Ok(report)
+}
+
+/// Put refs, `HEAD`, the index and the working tree back as they were at
+/// checkpoint `id`. The current state is saved as a new checkpoint first, so
+/// a restore can itself be undone. Untracked files created since the
+/// checkpoint are left in place.
+pub fn restore(state: &Path, root: &Path, id: u64) -> Result<String, String> {
+    let checkpoint = read(state, id)?;
+    let repository = pin(root)?;
+    check_repository(&checkpoint, &repository)?;
+    let session = Session::open(&repository, Vec::new())?;
+    let current = session.capture()?;
+    let saved = session.save(state, current, "before restore")?;
+
+    // Copy back whatever the workspace no longer has
+    let mut revisions = String::new();
+    for oid in checkpoint
+        .refs
+        .values()
+        .chain([&checkpoint.index, &checkpoint.worktree])
+    {
+        revisions.push_str(&format!("{oid}\n"));
+    }
+    for oid in saved.refs.values().chain([&saved.index, &saved.worktree]) {
+        revisions.push_str(&format!("^{oid}\n"));
+    }
+    let mut pack = mirror_git(state)?
+        .args(["pack-objects", "--revs", "--stdout", "--quiet"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("could not read the checkpoint: {error}"))?;
+    let mut stdin = pack.stdin.take().expect("piped stdin");
+    let writer = thread::spawn(move || stdin.write_all(revisions.as_bytes()));
+    let unpacked = session
+        .workspace_git()
+        .args(["index-pack", "--stdin"])
+        .stdin(pack.stdout.take().expect("piped stdout"))
+        .output()
+        .map_err(|error| {
+            format!("could not copy checkpoint objects into the workspace: {error}")
+        })?;
+    let _ = writer.join();
+    let packed = pack
+        .wait()
+        .map_err(|error| format!("could not read the checkpoint: {error}"))?;
+    if !packed.success() {
+        return Err("could not read the checkpoint objects from the mirror".to_string());
+    }
+    if !unpacked.status.success() {
+        return Err(format!(
+            "could not copy checkpoint objects into the workspace: {}",
+            String::from_utf8_lossy(&unpacked.stderr).trim()
+        ));
+    }
+
+    // The working tree and index, through the snapshot so no filter runs
+    for args in [
+        ["read-tree", "--reset", "-u", checkpoint.worktree.as_str()],
+        ["read-tree", "--reset", "--", checkpoint.index.as_str()],
+    ] {
+        let mut command = session.workspace_git();
+        command
+            .env(
+                "GIT_INDEX_FILE",
+                repository.git_dir_command_path.join("index"),
+            )
+            .args(args);
+        run(command, b"", "restore the working tree")?;
+    }
+
+    // Refs and HEAD, in the repository itself with hooks off
+    let mut updates = String::new();
+    for (reference, oid) in &checkpoint.refs {
+        updates.push_str(&format!("update {reference} {oid}\n"));
+    }
+    for reference in saved.refs.keys() {
+        if !checkpoint.refs.contains_key(reference) {
+            updates.push_str(&format!("delete {reference}\n"));
+        }
+    }
+    let repository_git = || {
+        let mut command = repository.command();
+        command.args([
+            "-c",
+            "core.hooksPath=/dev/null",
+            "-c",
+            "core.fsmonitor=false",
+        ]);
+        command
+    };
+    let mut command = repository_git();
+    command.args(["update-ref", "--stdin"]);
+    run(command, updates.as_bytes(), "restore refs")?;
+    let mut command = repository_git();
+    match checkpoint.head.strip_prefix("ref: ") {
+        Some(target) => command.args(["symbolic-ref", "HEAD", target]),
+        None => command.args(["update-ref", "--no-deref", "HEAD", &checkpoint.head]),
+    };
+    run(command, b"", "restore HEAD")?;
+
+    Ok(format!(
+        "Restored checkpoint {id}. The previous state was saved as checkpoint {}; \
+         run 'claude-sandbox checkpoint restore {}' to go back.",
+        saved.id, saved.id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(directory: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn restores_refs_index_and_working_tree() {
+        let root = std::env::temp_dir().join(format!(
+            "claude-sandbox-checkpoint-{}-{}",
+            std::process::id(),
+            unix_time()
+        ));
+        let repository = root.join("project");
+        let state = root.join("state");
+        fs::create_dir_all(&repository).unwrap();
+        git(&repository, &["init", "-q", "--initial-branch=main"]);
+        git(&repository, &["config", "user.name", "Test"]);
+        git(
+            &repository,
+            &["config", "user.email", "test@example.invalid"],
+        );
+        fs::write(repository.join("kept.txt"), "first\n").unwrap();
+        git(&repository, &["add", "kept.txt"]);
+        git(&repository, &["commit", "-q", "-m", "first"]);
+        git(&repository, &["branch", "feature"]);
+        fs::write(repository.join("kept.txt"), "staged\n").unwrap();
+        git(&repository, &["add", "kept.txt"]);
+        fs::write(repository.join("kept.txt"), "unstaged\n").unwrap();
+        fs::write(repository.join("notes.txt"), "untracked\n").unwrap();
+        // A filter named by the repository never runs on the host
+        fs::write(repository.join(".gitattributes"), "*.txt filter=evil\n").unwrap();
+        let filter = |enabled: bool| {
+            for key in ["filter.evil.clean", "filter.evil.smudge"] {
+                if enabled {
+                    git(&repository, &["config", key, "touch pwned; cat"]);
+                } else {
+                    git(&repository, &["config", "--unset", key]);
+                }
+            }
+        };
+        filter(true);
+        let canonical = fs::canonicalize(&repository).unwrap();
+
+        let saved = create(&state, &canonical, "manual").unwrap();
+        assert_eq!((saved.id, saved.dirty), (1, true));
+        assert!(!repository.join("pwned").exists());
+        let first = git(&repository, &["rev-parse", "HEAD"]);
+
+        // The agent rewrites history and discards everything
+        filter(false);
+        git(&repository, &["add", "-A"]);
+        git(&repository, &["commit", "-q", "-m", "rewrite"]);
+        git(&repository, &["branch", "-D", "feature"]);
+        git(&repository, &["checkout", "-q", "--detach"]);
+        git(&repository, &["reflog", "expire", "--expire=now", "--all"]);
+        git(&repository, &["gc", "-q", "--prune=now"]);
+        fs::remove_file(repository.join("notes.txt")).unwrap();
+
+        let listed = list(&state).unwrap();
+        assert!(listed.contains("  1  <1m ago  manual  main  2 ref(s), uncommitted changes\n"));
+        let changes = diff(&state, &canonical, 1, false).unwrap();
+        assert!(
+            changes.contains("  HEAD: main -> detached at "),
+            "{changes}"
+        );
+        assert!(
+            changes.contains("  refs/heads/feature: deleted"),
+            "{changes}"
+        );
+        assert!(changes.contains("notes.txt"), "{changes}");
+
+        filter(true);
+        let restored = restore(&state, &canonical, 1).unwrap();
+        assert!(restored.contains("saved as checkpoint 2"), "{restored}");
+        assert_eq!(
+            git(&repository, &["symbolic-ref", "HEAD"]),
+            "refs/heads/main"
+        );
+        assert_eq!(git(&repository, &["rev-parse", "main"]), first);
+        assert_eq!(git(&repository, &["rev-parse", "feature"]), first);
+        assert_eq!(git(&repository, &["show", ":kept.txt"]), "staged");
+        assert_eq!(
+            fs::read_to_string(repository.join("kept.txt")).unwrap(),
+            "unstaged\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repository.join("notes.txt")).unwrap(),
+            "untracked\n"
+        );
+        assert!(git(&repository, &["ls-files", "notes.txt"]).is_empty());
+        assert!(!repository.join("pwned").exists());
+        assert!(
+            diff(&state, &canonical, 1, false)
+                .unwrap()
+                .ends_with("  no changes\n")
+        );
+
+        // The restore itself can be undone
+        restore(&state, &canonical, 2).unwrap();
+        assert_ne!(git(&repository, &["rev-parse", "HEAD"]), first);
+        assert!(git(&repository, &["branch", "--list", "feature"]).is_empty());
+
+        // Hooks planted in the mirror never run, and a changed config is refused
+        let hooks = mirror_dir(&state).join("hooks");
+        fs::create_dir_all(&hooks).unwrap();
+        let hook = hooks.join("pre-receive");
+        fs::write(
+            &hook,
+            format!("#!/bin/sh\ntouch {}\n", root.join("hooked").display()),
+        )
+        .unwrap();
+        fs::set_permissions(&hook, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        create(&state, &canonical, "manual").unwrap();
+        assert!(!root.join("hooked").exists());
+        let config = mirror_dir(&state).join("config");
+        fs::write(
+            &config,
+            format!("{MIRROR_CONFIG}[core]\n\thooksPath = hooks\n"),
+        )
+        .unwrap();
+        let error = create(&state, &canonical, "manual").unwrap_err();
+        assert!(
+            error.contains("refusing to use the checkpoint mirror"),
+            "{error}"
+        );
+        assert!(diff(&state, &canonical, 1, false).is_err());
+        assert!(restore(&state, &canonical, 1).is_err());
+        assert!(!root.join("hooked").exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
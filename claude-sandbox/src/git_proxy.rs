@@ -1,20 +1,108 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs::{File, OpenOptions, Permissions};
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{Read, Write};
 use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt, symlink};
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use std::{fs, process, thread};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::{fs, thread};
 
-use crate::{proxy_log, proxy_socket};
+use crate::ssh_proxy::glob_match;
+use crate::{proxy_log, proxy_socket, redact};
 
 use crate::logging::log_line;
 use crate::managed_push;
 
+/// Per-project push policy. Patterns are globs against the short branch or
+/// tag name, where `*` matches any run of characters including `/`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Config {
+    /// Branches a bridged push may update, e.g. `agent/*`. Empty allows any
+    /// branch that is not protected.
+    #[serde(default)]
+    pub allowed_branches: Vec<String>,
+    /// Branches that are always refused, even when also allowed.
+    #[serde(default)]
+    pub protected_branches: Vec<String>,
+    /// Tags `git push --tags` may create or move. Unset allows any tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_patterns: Option<Vec<String>>,
+    /// Agent-owned branches `git push --force-with-lease` may rewrite. Empty
+    /// refuses every force push.
+    #[serde(default)]
+    pub agent_owned_branches: Vec<String>,
+    /// Reject pushed blobs containing known key formats or high-entropy
+    /// values assigned to secret-like names.
+    #[serde(default = "default_scan_secrets")]
+    pub scan_secrets: bool,
+    /// Largest blob a push may add, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// Author email globs every pushed commit must match. Empty allows any.
+    #[serde(default)]
+    pub allowed_author_emails: Vec<String>,
+    /// Upload the Git LFS objects pushed commits reference from the host,
+    /// against the pinned origin, before the push itself.
+    #[serde(default)]
+    pub lfs: bool,
+}
+
+fn default_scan_secrets() -> bool {
+    true
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            allowed_branches: Vec::new(),
+            protected_branches: Vec::new(),
+            tag_patterns: None,
+            agent_owned_branches: Vec::new(),
+            scan_secrets: default_scan_secrets(),
+            max_file_size: None,
+            allowed_author_emails: Vec::new(),
+            lfs: false,
+        }
+    }
+}
+
+impl Config {
+    fn inspects_content(&self) -> bool {
+        self.scan_secrets || self.max_file_size.is_some() || !self.allowed_author_emails.is_empty()
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Tracking ref values this proxy has itself seen on the remote, keyed by
+/// repository. Force-push leases are taken from here rather than from the
+/// container's agent-writable tracking refs.
+static OBSERVED_TRACKING: Mutex<BTreeMap<PathBuf, BTreeMap<String, String>>> =
+    Mutex::new(BTreeMap::new());
+
+fn record_observed_tracking(repository: &Path, refs: BTreeMap<String, String>) {
+    let mut observed = OBSERVED_TRACKING.lock().unwrap();
+    observed
+        .entry(repository.to_path_buf())
+        .or_default()
+        .extend(refs);
+}
+
+fn observed_tracking(repository: &Path) -> BTreeMap<String, String> {
+    OBSERVED_TRACKING
+        .lock()
+        .unwrap()
+        .get(repository)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
 #[derive(Deserialize)]
 struct Request {
     args: Vec<String>,
@@ -42,6 +130,22 @@ struct TrackingUpdate {
 enum Push {
     Branch,
     Tags,
+    ForceWithLease,
+}
+
+#[derive(Debug, PartialEq)]
+enum Operation {
+    Push(Push),
+    Fetch,
+}
+
+impl Operation {
+    fn verb(&self) -> &'static str {
+        match self {
+            Operation::Push(_) => "push",
+            Operation::Fetch => "fetch",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -56,25 +160,30 @@ pub enum Mode {
     },
 }
 
-struct PinnedDirectory {
+pub(crate) struct PinnedDirectory {
     _handle: File,
-    command_path: PathBuf,
+    pub(crate) command_path: PathBuf,
     resolved_path: PathBuf,
 }
 
-struct PinnedRepository {
+pub(crate) struct PinnedRepository {
     worktree: PinnedDirectory,
     _git_dir: PinnedDirectory,
     _common_dir: PinnedDirectory,
-    objects: PinnedDirectory,
+    pub(crate) objects: PinnedDirectory,
     refs: PinnedDirectory,
     allowed_root: PathBuf,
-    git_dir_command_path: PathBuf,
-    common_dir_command_path: PathBuf,
+    pub(crate) git_dir_command_path: PathBuf,
+    pub(crate) common_dir_command_path: PathBuf,
 }
 
 impl PinnedRepository {
-    fn command(&self) -> Command {
+    /// The worktree root as resolved when it was pinned.
+    pub(crate) fn worktree_path(&self) -> &Path {
+        &self.worktree.resolved_path
+    }
+
+    pub(crate) fn command(&self) -> Command {
         let mut command = Command::new("git");
         clear_repository_environment(&mut command);
         command
@@ -140,7 +249,7 @@ fn bool_config(
     }
 }
 
-fn clear_repository_environment(command: &mut Command) {
+pub(crate) fn clear_repository_environment(command: &mut Command) {
     for key in [
         "GIT_DIR",
         "GIT_WORK_TREE",
@@ -153,7 +262,7 @@ fn clear_repository_environment(command: &mut Command) {
     }
 }
 
-fn fd_path(file: &File) -> PathBuf {
+pub(crate) fn fd_path(file: &File) -> PathBuf {
     PathBuf::from(format!(
         "/proc/{}/fd/{}",
         std::process::id(),
@@ -227,7 +336,10 @@ fn discover_repository_layout(start: &PinnedDirectory) -> Result<[PathBuf; 3], S
     })
 }
 
-fn pin_repository(repository: &Path, allowed_root: &Path) -> Result<PinnedRepository, String> {
+pub(crate) fn pin_repository(
+    repository: &Path,
+    allowed_root: &Path,
+) -> Result<PinnedRepository, String> {
     let allowed_root = fs::canonicalize(allowed_root)
         .map_err(|error| format!("could not resolve approved workspace: {error}"))?;
     let start = pin_directory(repository, &allowed_root, "repository directory")?;
@@ -270,12 +382,101 @@ fn parse_push_args(args: &[String]) -> Option<Push> {
     match args {
         [p] if p == "push" => Some(Push::Branch),
         [p, t] if p == "push" && t == "--tags" => Some(Push::Tags),
+        [p, f] if p == "push" && f == "--force-with-lease" => Some(Push::ForceWithLease),
         _ => None,
     }
 }
 
+fn parse_fetch_args(args: &[String]) -> bool {
+    match args {
+        [f] => f == "fetch",
+        [f, r] => f == "fetch" && r == "origin",
+        _ => false,
+    }
+}
+
+fn parse_args(args: &[String]) -> Option<Operation> {
+    if let Some(push) = parse_push_args(args) {
+        return Some(Operation::Push(push));
+    }
+    parse_fetch_args(args).then_some(Operation::Fetch)
+}
+
+/// Check every destination ref of a push against the policy, naming the
+/// first offending ref.
+fn check_push_policy(config: &Config, refs: &[String]) -> Result<(), String> {
+    let matches =
+        |patterns: &[String], name: &str| patterns.iter().any(|pattern| glob_match(pattern, name));
+    for reference in refs {
+        if let Some(branch) = reference.strip_prefix("refs/heads/") {
+            if matches(&config.protected_branches, branch) {
+                return Err(format!("branch '{branch}' is protected"));
+            }
+            if !config.allowed_branches.is_empty() && !matches(&config.allowed_branches, branch) {
+                return Err(format!(
+                    "branch '{branch}' is not in allowed_branches ({})",
+                    config.allowed_branches.join(", ")
+                ));
+            }
+        } else if let Some(tag) = reference.strip_prefix("refs/tags/") {
+            if let Some(patterns) = &config.tag_patterns
+                && !matches(patterns, tag)
+            {
+                return Err(format!(
+                    "tag '{tag}' is not in tag_patterns ({})",
+                    patterns.join(", ")
+                ));
+            }
+        } else {
+            return Err(format!("ref '{reference}' is not a branch or tag"));
+        }
+    }
+    Ok(())
+}
+
+/// Force pushes are additionally limited to agent-owned branches.
+fn check_force_policy(config: &Config, refs: &[String]) -> Result<(), String> {
+    for reference in refs {
+        let branch = reference
+            .strip_prefix("refs/heads/")
+            .ok_or_else(|| format!("ref '{reference}' is not a branch"))?;
+        if !config
+            .agent_owned_branches
+            .iter()
+            .any(|pattern| glob_match(pattern, branch))
+        {
+            return Err(if config.agent_owned_branches.is_empty() {
+                "force pushes are disabled; set agent_owned_branches to enable them".to_string()
+            } else {
+                format!(
+                    "branch '{branch}' is not in agent_owned_branches ({})",
+                    config.agent_owned_branches.join(", ")
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Map a remote branch to its `refs/remotes/origin/*` tracking ref through the
+/// validated origin fetch refspecs.
+fn tracking_ref_for(refspecs: &[String], reference: &str) -> Option<String> {
+    refspecs.iter().find_map(|refspec| {
+        let (source, destination) = refspec.trim_start_matches('+').split_once(':')?;
+        match (source.split_once('*'), destination.split_once('*')) {
+            (Some((prefix, suffix)), Some((target_prefix, target_suffix))) => {
+                let middle = reference.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                Some(format!("{target_prefix}{middle}{target_suffix}"))
+            }
+            (None, None) if source == reference => Some(destination.to_string()),
+            _ => None,
+        }
+    })
+}
+
 // Repo-local config keys that could make the host-side `git push` execute
-// agent-controlled code, redirect the push, or persist temporary routing.
+// agent-controlled code, redirect the push or fetch, or persist temporary
+// routing.
 // The workspace is agent-writable, so its .git/config is untrusted.
 const DENIED_KEYS: &[&str] = &[
     "core.sshcommand",
@@ -311,9 +512,20 @@ const DENIED_REMOTE_SUFFIXES: &[&str] = &[
     ".push",
 ];
 
+/// Keys that would point Git LFS at an endpoint other than the one derived
+/// from the pinned origin.
+fn is_lfs_url_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key == "lfs.url"
+        || key == "lfs.pushurl"
+        || (key.starts_with("remote.")
+            && (key.ends_with(".lfsurl") || key.ends_with(".lfspushurl")))
+}
+
 fn is_denied_key(key: &str) -> bool {
     let key = key.to_ascii_lowercase();
-    DENIED_KEYS.contains(&key.as_str())
+    is_lfs_url_key(&key)
+        || DENIED_KEYS.contains(&key.as_str())
         || DENIED_PREFIXES.iter().any(|p| key.starts_with(p))
         || (key.starts_with("remote.") && DENIED_REMOTE_SUFFIXES.iter().any(|s| key.ends_with(s)))
         || (key.starts_with("branch.") && key.ends_with(".pushremote"))
@@ -435,14 +647,14 @@ fn random_hex() -> Result<String, String> {
     Ok(name)
 }
 
-struct TemporaryDirectory {
-    path: PathBuf,
+pub(crate) struct TemporaryDirectory {
+    pub(crate) path: PathBuf,
     _handle: File,
-    command_path: PathBuf,
+    pub(crate) command_path: PathBuf,
 }
 
 impl TemporaryDirectory {
-    fn create() -> Result<Self, String> {
+    pub(crate) fn create() -> Result<Self, String> {
         for _ in 0..10 {
             let path =
                 PathBuf::from("/tmp").join(format!("claude-sandbox-git-config-{}", random_hex()?));
@@ -479,7 +691,7 @@ impl Drop for TemporaryDirectory {
     }
 }
 
-struct RepositoryConfigSnapshot {
+pub(crate) struct RepositoryConfigSnapshot {
     directory: TemporaryDirectory,
     credentials: Vec<(String, String)>,
     tracking_before: BTreeMap<String, String>,
@@ -496,7 +708,7 @@ fn host_config_entries(repository: &PinnedRepository, scope: &str) -> Vec<(Strin
         .unwrap_or_default()
 }
 
-fn reference_entries(
+pub(crate) fn reference_entries(
     command: &mut Command,
 ) -> Result<Vec<(String, String, Option<String>)>, String> {
     let output = command
@@ -674,7 +886,7 @@ fn reject_escaping_ref_symlinks(directory: &Path, allowed_root: &Path) -> Result
 }
 
 impl RepositoryConfigSnapshot {
-    fn create(
+    pub(crate) fn create(
         repository: &PinnedRepository,
         local_entries: &[(String, String)],
     ) -> Result<Self, String> {
@@ -741,7 +953,12 @@ impl RepositoryConfigSnapshot {
         })
     }
 
-    fn command(&self, repository: &PinnedRepository) -> Command {
+    /// The private common directory the snapshot's commands run against.
+    pub(crate) fn path(&self) -> &Path {
+        &self.directory.path
+    }
+
+    pub(crate) fn command(&self, repository: &PinnedRepository) -> Command {
         let mut command = repository.command();
         command
             .env("GIT_DIR", self.directory.command_path.join("git"))
@@ -757,20 +974,27 @@ impl RepositoryConfigSnapshot {
         command
     }
 
+    /// Direct `refs/remotes/origin/*` refs in the private refs.
+    fn tracking_refs(
+        &self,
+        repository: &PinnedRepository,
+    ) -> Result<BTreeMap<String, String>, String> {
+        let mut command = self.command(repository);
+        Ok(reference_entries(&mut command)?
+            .into_iter()
+            .filter(|(reference, _, symref)| {
+                reference.starts_with("refs/remotes/origin/") && symref.is_none()
+            })
+            .map(|(reference, oid, _)| (reference, oid))
+            .collect())
+    }
+
     fn tracking_updates(
         &self,
         repository: &PinnedRepository,
     ) -> Result<Vec<TrackingUpdate>, String> {
-        let mut command = self.command(repository);
-        let after = reference_entries(&mut command)?;
         let mut updates = Vec::new();
-        for (reference, oid, symref) in after {
-            if !reference.starts_with("refs/remotes/origin/") {
-                continue;
-            }
-            if symref.is_some() {
-                continue;
-            }
+        for (reference, oid) in self.tracking_refs(repository)? {
             let old_oid = self.tracking_before.get(&reference).cloned();
             if old_oid.as_deref() != Some(oid.as_str()) {
                 updates.push(TrackingUpdate {
@@ -784,12 +1008,27 @@ impl RepositoryConfigSnapshot {
     }
 }
 
-struct PreparedPush {
+struct PreparedCommand {
     _config_snapshot: RepositoryConfigSnapshot,
     command: Command,
+    /// Remote refs the command will update; empty for fetches.
+    refs: Vec<String>,
+    /// Content-check violations in what a push would send.
+    findings: Vec<String>,
+    /// The single branch update and commit a branch push sends.
+    target: Option<managed_push::PushTarget>,
+    /// LFS objects to upload before the push runs.
+    lfs_upload: Option<LfsUpload>,
 }
 
-impl PreparedPush {
+impl PreparedCommand {
+    fn tracking_refs(
+        &self,
+        repository: &PinnedRepository,
+    ) -> Result<BTreeMap<String, String>, String> {
+        self._config_snapshot.tracking_refs(repository)
+    }
+
     fn tracking_updates(
         &self,
         repository: &PinnedRepository,
@@ -798,15 +1037,17 @@ impl PreparedPush {
     }
 }
 
-fn prepare_push(
+/// Start a Git command against the config snapshot with everything that can
+/// run host-side code, prompt, or reach a secondary remote switched off.
+/// Credentials come from the host's system/global config only.
+pub(crate) fn hardened_command(
     repository: &PinnedRepository,
-    audit: &LocalConfigAudit,
-    expected_origin: &str,
-    push: &Push,
-) -> Result<PreparedPush, String> {
-    let config_snapshot = RepositoryConfigSnapshot::create(repository, &audit.snapshot_entries)?;
-    let remote_name = temporary_remote_name()?;
-    let mut command = config_snapshot.command(repository);
+    config_snapshot: &RepositoryConfigSnapshot,
+) -> Command {
+    harden(config_snapshot.command(repository), config_snapshot)
+}
+
+fn harden(mut command: Command, config_snapshot: &RepositoryConfigSnapshot) -> Command {
     command
         .env("GIT_TERMINAL_PROMPT", "0")
         .env("GIT_NO_LAZY_FETCH", "1");
@@ -823,173 +1064,1183 @@ fn prepare_push(
         "protocol.ext.allow=never",
         "-c",
         "credential.helper=",
-        "-c",
-        "push.gpgSign=false",
-        "-c",
-        "push.recurseSubmodules=no",
     ]);
     for (key, value) in &config_snapshot.credentials {
         command.arg("-c").arg(format!("{key}={value}"));
     }
     command
-        .arg("-c")
-        .arg(format!("remote.{remote_name}.pushurl={expected_origin}"));
-    for refspec in &audit.origin_fetch_refspecs {
-        command
-            .arg("-c")
-            .arg(format!("remote.{remote_name}.fetch={refspec}"));
-    }
-    command.args(["-c", "push.autoSetupRemote=false"]);
-    command.args(["push", "--no-verify"]);
-    if push == &Push::Tags {
-        command.arg("--tags");
-    }
-    command.arg(&remote_name);
+}
 
-    Ok(PreparedPush {
-        _config_snapshot: config_snapshot,
-        command,
-    })
+fn snapshot_git(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    args: &[&str],
+) -> Option<String> {
+    config_snapshot
+        .command(repository)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|value| value.trim().to_string())
 }
 
-fn origin_url_at(repository: &PinnedRepository) -> Option<String> {
-    let output = repository
-        .command()
-        .args(["remote", "get-url", "origin"])
+/// Resolve what a plain `git push` would update into one explicit refspec,
+/// following `push.default` for the snapshot's current branch.
+fn resolve_branch_refspec(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+) -> Result<(String, String), String> {
+    let head = snapshot_git(repository, config_snapshot, &["symbolic-ref", "-q", "HEAD"])
+        .filter(|head| head.starts_with("refs/heads/"))
+        .ok_or("HEAD is detached; check out a branch to push")?;
+    let branch = &head["refs/heads/".len()..];
+    let push_default = snapshot_git(repository, config_snapshot, &["config", "push.default"])
+        .unwrap_or_else(|| "simple".to_string());
+    let destination = match push_default.as_str() {
+        "simple" | "current" => head.clone(),
+        "upstream" | "tracking" => {
+            let remote = snapshot_git(
+                repository,
+                config_snapshot,
+                &["config", &format!("branch.{branch}.remote")],
+            );
+            let merge = snapshot_git(
+                repository,
+                config_snapshot,
+                &["config", &format!("branch.{branch}.merge")],
+            );
+            match (remote.as_deref(), merge) {
+                (Some("origin"), Some(merge)) if merge.starts_with("refs/heads/") => merge,
+                _ => {
+                    return Err(format!(
+                        "branch '{branch}' has no upstream branch on origin"
+                    ));
+                }
+            }
+        }
+        other => {
+            return Err(format!(
+                "push.default={other} is not supported by the push bridge; use simple, current or upstream"
+            ));
+        }
+    };
+    Ok((head, destination))
+}
+
+/// Current `refs/*` values on the pinned origin, by ref name.
+fn origin_refs(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    expected_origin: &str,
+) -> Result<BTreeMap<String, String>, String> {
+    let output = hardened_command(repository, config_snapshot)
+        .args(["ls-remote", "--refs", expected_origin])
         .output()
-        .ok()?;
+        .map_err(|error| format!("could not list origin refs: {error}"))?;
     if !output.status.success() {
-        return None;
+        return Err(format!(
+            "could not list origin refs: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
-    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
-    if value.is_empty() { None } else { Some(value) }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (oid, reference) = line.split_once('\t')?;
+            Some((reference.to_string(), oid.to_string()))
+        })
+        .collect())
 }
 
-pub fn origin_url() -> Option<String> {
-    let cwd = std::env::current_dir().ok()?;
-    managed_push::origin_url_at(&cwd)
+fn local_tags(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+) -> Result<BTreeMap<String, String>, String> {
+    let local = snapshot_git(
+        repository,
+        config_snapshot,
+        &[
+            "for-each-ref",
+            "--format=%(refname) %(objectname)",
+            "refs/tags/",
+        ],
+    )
+    .ok_or("could not list local tags")?;
+    Ok(local
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(reference, oid)| (reference.to_string(), oid.to_string()))
+        .collect())
 }
 
-fn repository_root_at(directory: &Path) -> Option<PathBuf> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(directory)
-        .args(["rev-parse", "--show-toplevel"])
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
+/// Tags `git push --tags` would create or move: local tags whose value
+/// differs from the origin's.
+fn resolve_tag_updates(
+    local: &BTreeMap<String, String>,
+    remote: &BTreeMap<String, String>,
+) -> Vec<String> {
+    local
+        .iter()
+        .filter(|(reference, oid)| remote.get(*reference) != Some(*oid))
+        .map(|(reference, _)| reference.clone())
+        .collect()
+}
+
+// Blobs larger than this are size-checked but not scanned for secrets.
+const MAX_SCANNED_BLOB: u64 = 1024 * 1024;
+// Findings beyond this are summarized rather than listed.
+const MAX_REPORTED_FINDINGS: usize = 20;
+// Commits and files listed in a held push's preview.
+const MAX_PREVIEW_LINES: usize = 50;
+
+const SECRET_KEYWORDS: &[&str] = &[
+    "secret",
+    "token",
+    "password",
+    "passwd",
+    "api_key",
+    "apikey",
+    "private_key",
+    "access_key",
+    "auth",
+];
+
+fn shannon_entropy(value: &str) -> f64 {
+    let mut counts = BTreeMap::new();
+    for byte in value.bytes() {
+        *counts.entry(byte).or_insert(0usize) += 1;
     }
-    let path = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
-    fs::canonicalize(path).ok()
+    let length = value.len() as f64;
+    counts
+        .values()
+        .map(|count| {
+            let probability = *count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
 }
 
-pub fn repository_root() -> Option<PathBuf> {
-    repository_root_at(&std::env::current_dir().ok()?)
+/// A line that assigns a long, random-looking value to a secret-like name,
+/// e.g. `API_TOKEN = "x8Fq..."`. Requiring the name keeps lockfile hashes and
+/// similar high-entropy noise from tripping the check.
+fn high_entropy_assignment(line: &str) -> bool {
+    let lower = line.to_ascii_lowercase();
+    if !SECRET_KEYWORDS
+        .iter()
+        .any(|keyword| lower.contains(keyword))
+    {
+        return false;
+    }
+    let Some(index) = line.find(['=', ':']) else {
+        return false;
+    };
+    line[index + 1..]
+        .split(|c: char| !(c.is_ascii_alphanumeric() || "+/=_-".contains(c)))
+        .any(|value| {
+            value.len() >= 20
+                && value.bytes().any(|b| b.is_ascii_digit())
+                && value.bytes().any(|b| b.is_ascii_alphabetic())
+                && shannon_entropy(value) >= 3.5
+        })
 }
 
-fn deny(stderr: String) -> Response {
-    Response {
-        exit_code: 1,
-        stdout: String::new(),
-        stderr,
-        tracking_updates: Vec::new(),
+fn scan_blob(path: &str, content: &[u8], findings: &mut Vec<String>) {
+    // Binary files still get the known formats; the entropy heuristic would
+    // only flag noise in them
+    let binary = content.contains(&0);
+    let text = String::from_utf8_lossy(content);
+    for (index, line) in text.lines().enumerate() {
+        let kind = if redact::global().contains_secret(line.as_bytes()) {
+            "known secret format"
+        } else if !binary && high_entropy_assignment(line) {
+            "high-entropy value assigned to a secret-like name"
+        } else {
+            continue;
+        };
+        findings.push(format!("secret: {path}:{} ({kind})", index + 1));
     }
 }
 
-fn resolve_request_repository(
-    req: &Request,
-    mode: &Mode,
-    log: &Arc<Mutex<File>>,
-) -> Result<(PathBuf, String, PathBuf), Response> {
-    match mode {
-        Mode::Single { repository, origin } => {
-            Ok((repository.clone(), origin.clone(), repository.clone()))
-        }
-        Mode::Managed {
-            workspace_root,
-            state_dir,
-        } => {
-            let cwd = req.cwd.as_deref().ok_or_else(|| {
-                deny(
-                    "git-proxy: managed push request did not include a working directory"
-                        .to_string(),
-                )
-            })?;
-            let (repository_path, repository) =
-                managed_push::resolve_repository(workspace_root, cwd)
-                    .map_err(|error| deny(format!("git-proxy: push refused: {error}")))?;
-            let approval = managed_push::read_approval(state_dir, &repository.relative_path)
-                .map_err(|error| deny(format!("git-proxy: push refused: {error}")))?;
+/// Run a read-only Git plumbing command against the snapshot with `input` on
+/// stdin. Replace refs live in the agent-writable ref store, so they are
+/// ignored: the inspection must see the objects that are actually sent.
+fn inspection_git(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    args: &[&str],
+    input: &[u8],
+) -> Result<Vec<u8>, String> {
+    let mut child = config_snapshot
+        .command(repository)
+        .env("GIT_NO_REPLACE_OBJECTS", "1")
+        .env("GIT_NO_LAZY_FETCH", "1")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("could not inspect push: {error}"))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let input = input.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("could not inspect push: {error}"))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!(
+            "could not inspect push: git {}: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
 
-            match approval {
-                Some(approval) if approval.repository.origin == repository.origin => {
-                    match managed_push::consume_once(state_dir, &approval) {
-                        Ok(true) => Ok((
-                            repository_path,
-                            repository.origin,
-                            workspace_root.clone(),
-                        )),
-                        Ok(false) => Err(deny(
-                            "git-proxy: the one-time approval was already consumed; approve this repository again"
-                                .to_string(),
-                        )),
-                        Err(error) => Err(deny(format!("git-proxy: push refused: {error}"))),
-                    }
-                }
-                approval => {
-                    let previous = approval.map(|value| value.repository.origin);
-                    let id = managed_push::record_candidate(state_dir, &repository, previous.clone())
-                        .map_err(|error| deny(format!("git-proxy: push refused: {error}")))?;
-                    let reason = if let Some(previous) = previous {
-                        format!("origin changed from {previous} to {}", repository.origin)
-                    } else {
-                        "repository is not approved".to_string()
-                    };
-                    log_line(
-                        log,
-                        &format!(
-                            "PENDING {} ({}; candidate {})",
-                            repository.relative_path, reason, id
-                        ),
-                    );
-                    Err(deny(format!(
-                        "git-proxy: push pending approval for '{}' ({})\nOpen the T3 admin portal, approve the repository, and retry.",
-                        repository.relative_path, repository.origin
-                    )))
-                }
-            }
+/// `sources` followed by `^oid` for each of `excludes` present locally, one
+/// revision per line for `--stdin`.
+fn exclusion_revisions(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    sources: &[String],
+    excludes: &[String],
+) -> Result<String, String> {
+    let present = inspection_git(
+        repository,
+        config_snapshot,
+        &["cat-file", "--batch-check=%(objectname) %(objecttype)"],
+        excludes.join("\n").as_bytes(),
+    )?;
+    let mut revisions = sources.join("\n");
+    for line in String::from_utf8_lossy(&present).lines() {
+        if let Some((oid, kind)) = line.split_once(' ')
+            && kind != "missing"
+        {
+            revisions.push_str(&format!("\n^{oid}"));
         }
     }
+    revisions.push('\n');
+    Ok(revisions)
 }
 
-fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response {
-    let cmd_str = req.args.join(" ");
+/// Resolve a local branch to the commit it names, ignoring replace refs.
+fn resolve_source_oid(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    source: &str,
+) -> Result<String, String> {
+    let output = inspection_git(
+        repository,
+        config_snapshot,
+        &["rev-parse", "--verify", &format!("{source}^{{commit}}")],
+        b"",
+    )?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
 
-    let push = match parse_push_args(&req.args) {
-        Some(p) => p,
-        None => {
-            log_line(log, &format!("DENIED  git {} (not allowed)", cmd_str));
-            return deny(format!(
-                "git-proxy: command not allowed: git {}\n\
-                 Only 'git push' and 'git push --tags' are bridged to the host.",
-                cmd_str
-            ));
-        }
-    };
+/// Cap a preview listing, noting how many entries were left out.
+fn capped_preview(mut lines: Vec<String>, noun: &str) -> Vec<String> {
+    if lines.len() > MAX_PREVIEW_LINES {
+        let omitted = lines.len() - MAX_PREVIEW_LINES;
+        lines.truncate(MAX_PREVIEW_LINES);
+        lines.push(format!("... and {omitted} more {noun}"));
+    }
+    lines
+}
 
-    let (repository_path, expected_origin, allowed_root) =
-        match resolve_request_repository(&req, mode, log) {
-            Ok(repository) => repository,
-            Err(response) => return response,
+/// Sum `git log --numstat` output per path into a diffstat, totals first.
+fn summarize_numstat(numstat: &str) -> Vec<String> {
+    let mut files: BTreeMap<&str, Option<(u64, u64)>> = BTreeMap::new();
+    for line in numstat.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
         };
-    let repository_label = repository_path.display();
-    let repository = match pin_repository(&repository_path, &allowed_root) {
-        Ok(repository) => repository,
-        Err(error) => return deny(format!("git-proxy: push refused: {error}")),
-    };
+        let counts = added.parse::<u64>().ok().zip(deleted.parse::<u64>().ok());
+        let entry = files.entry(path).or_insert(Some((0, 0)));
+        *entry = match (*entry, counts) {
+            (Some((a, d)), Some((added, deleted))) => Some((a + added, d + deleted)),
+            _ => None,
+        };
+    }
+    let (added, deleted) = files
+        .values()
+        .flatten()
+        .fold((0, 0), |(a, d), (added, deleted)| (a + added, d + deleted));
+    let mut lines = vec![format!(
+        "{} file(s) changed, +{added} -{deleted}",
+        files.len()
+    )];
+    lines.extend(capped_preview(
+        files
+            .into_iter()
+            .map(|(path, counts)| match counts {
+                Some((added, deleted)) => format!("{path} | +{added} -{deleted}"),
+                None => format!("{path} | binary"),
+            })
+            .collect(),
+        "files",
+    ));
+    lines
+}
 
-    let audit = match local_config_audit(&repository) {
+/// Resolve a branch push locally and describe what it would send: the
+/// commits and diffstat not reachable from tracking values the host has
+/// observed on origin. Nothing here contacts origin, so it is safe to run
+/// before the repository is approved. Returns the refs the push would update
+/// and, for branch pushes, the preview.
+fn preview_push(
+    repository: &PinnedRepository,
+    audit: &LocalConfigAudit,
+    push: &Push,
+    observed: &BTreeMap<String, String>,
+) -> Result<(Vec<String>, Option<managed_push::PushPreview>), String> {
+    if push == &Push::Tags {
+        return Ok((Vec::new(), None));
+    }
+    let config_snapshot = RepositoryConfigSnapshot::create(repository, &audit.snapshot_entries)?;
+    let (source, destination) = resolve_branch_refspec(repository, &config_snapshot)?;
+    let oid = resolve_source_oid(repository, &config_snapshot, &source)?;
+    let excludes: Vec<String> = observed.values().cloned().collect();
+    let revisions = exclusion_revisions(
+        repository,
+        &config_snapshot,
+        std::slice::from_ref(&oid),
+        &excludes,
+    )?;
+    let commits = inspection_git(
+        repository,
+        &config_snapshot,
+        &["log", "--stdin", "--format=%h %s"],
+        revisions.as_bytes(),
+    )?;
+    let numstat = inspection_git(
+        repository,
+        &config_snapshot,
+        &[
+            "log",
+            "--stdin",
+            "--format=",
+            "--numstat",
+            "--no-renames",
+            "--no-textconv",
+            "--no-ext-diff",
+        ],
+        revisions.as_bytes(),
+    )?;
+    let preview = managed_push::PushPreview {
+        target: managed_push::PushTarget {
+            reference: destination.clone(),
+            oid,
+        },
+        commits: capped_preview(
+            String::from_utf8_lossy(&commits)
+                .lines()
+                .map(str::to_string)
+                .collect(),
+            "commits",
+        ),
+        diffstat: summarize_numstat(&String::from_utf8_lossy(&numstat)),
+    };
+    Ok((vec![destination], Some(preview)))
+}
+
+/// Enumerate the commits and blobs reachable from `sources` but not from
+/// `excludes` (values the origin already has) and check them against the
+/// content policy. Only plumbing runs here, through the pinned handles, so no
+/// repository hook or filter can execute.
+fn inspect_push(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    sources: &[String],
+    excludes: &[String],
+    config: &Config,
+) -> Result<Vec<String>, String> {
+    let mut findings = Vec::new();
+    if sources.is_empty() {
+        return Ok(findings);
+    }
+    let revisions = exclusion_revisions(repository, config_snapshot, sources, excludes)?;
+
+    if !config.allowed_author_emails.is_empty() {
+        let commits = inspection_git(
+            repository,
+            config_snapshot,
+            &["log", "--stdin", "--format=%H %ae"],
+            revisions.as_bytes(),
+        )?;
+        for line in String::from_utf8_lossy(&commits).lines() {
+            let (commit, email) = line.split_once(' ').unwrap_or((line, ""));
+            if !config
+                .allowed_author_emails
+                .iter()
+                .any(|pattern| glob_match(pattern, email))
+            {
+                findings.push(format!(
+                    "author: commit {} by '{email}' is not in allowed_author_emails ({})",
+                    &commit[..commit.len().min(12)],
+                    config.allowed_author_emails.join(", ")
+                ));
+            }
+        }
+    }
+
+    if !config.scan_secrets && config.max_file_size.is_none() {
+        return Ok(findings);
+    }
+    let paths = pushed_paths(repository, config_snapshot, &revisions)?;
+    let mut scanned = Vec::new();
+    for (oid, size) in blob_sizes(repository, config_snapshot, &paths)? {
+        let path = &paths[&oid];
+        if let Some(limit) = config.max_file_size
+            && size > limit
+        {
+            findings.push(format!(
+                "size: {path} is {size} bytes (max_file_size {limit})"
+            ));
+            continue;
+        }
+        if config.scan_secrets && size <= MAX_SCANNED_BLOB {
+            scanned.push(oid);
+        }
+    }
+    for (oid, content) in blob_contents(repository, config_snapshot, &scanned)? {
+        scan_blob(&paths[&oid], &content, &mut findings);
+    }
+    Ok(findings)
+}
+
+/// Every object `revisions` reaches, with the first path it was seen at.
+fn pushed_paths(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    revisions: &str,
+) -> Result<BTreeMap<String, String>, String> {
+    let objects = inspection_git(
+        repository,
+        config_snapshot,
+        &["rev-list", "--objects", "--stdin"],
+        revisions.as_bytes(),
+    )?;
+    let mut paths = BTreeMap::new();
+    for line in String::from_utf8_lossy(&objects).lines() {
+        if let Some((oid, path)) = line.split_once(' ') {
+            paths
+                .entry(oid.to_string())
+                .or_insert_with(|| path.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// The blobs among `objects` with their sizes in bytes.
+fn blob_sizes(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    objects: &BTreeMap<String, String>,
+) -> Result<Vec<(String, u64)>, String> {
+    let listing: Vec<&str> = objects.keys().map(String::as_str).collect();
+    let types = inspection_git(
+        repository,
+        config_snapshot,
+        &[
+            "cat-file",
+            "--batch-check=%(objectname) %(objecttype) %(objectsize)",
+        ],
+        listing.join("\n").as_bytes(),
+    )?;
+    let mut blobs = Vec::new();
+    for line in String::from_utf8_lossy(&types).lines() {
+        let mut fields = line.split(' ');
+        if let (Some(oid), Some("blob"), Some(size)) = (fields.next(), fields.next(), fields.next())
+        {
+            blobs.push((oid.to_string(), size.parse().unwrap_or(0)));
+        }
+    }
+    Ok(blobs)
+}
+
+fn blob_contents(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    oids: &[String],
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    if oids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let contents = inspection_git(
+        repository,
+        config_snapshot,
+        &["cat-file", "--batch"],
+        oids.join("\n").as_bytes(),
+    )?;
+    let mut blobs = Vec::new();
+    let mut rest = contents.as_slice();
+    while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+        let header = String::from_utf8_lossy(&rest[..end]).into_owned();
+        let mut fields = header.split(' ');
+        let (Some(oid), Some(_), Some(size)) = (fields.next(), fields.next(), fields.next()) else {
+            break;
+        };
+        let size: usize = size.parse().unwrap_or(0);
+        let body = &rest[end + 1..];
+        if body.len() < size {
+            break;
+        }
+        blobs.push((oid.to_string(), body[..size].to_vec()));
+        rest = &body[(size + 1).min(body.len())..];
+    }
+    Ok(blobs)
+}
+
+fn format_findings(findings: &[String]) -> String {
+    let mut report = String::new();
+    for finding in findings.iter().take(MAX_REPORTED_FINDINGS) {
+        report.push_str(&format!("  {finding}\n"));
+    }
+    if findings.len() > MAX_REPORTED_FINDINGS {
+        report.push_str(&format!(
+            "  ... and {} more\n",
+            findings.len() - MAX_REPORTED_FINDINGS
+        ));
+    }
+    report
+}
+
+/// Largest blob Git LFS treats as a pointer file.
+const MAX_LFS_POINTER: u64 = 1024;
+
+/// An LFS object a pushed pointer file refers to.
+#[derive(Debug, PartialEq)]
+struct LfsPointer {
+    oid: String,
+    size: u64,
+}
+
+/// Parse a Git LFS pointer file: a `version` line, then `oid sha256:<hex>`
+/// and `size <bytes>` among the remaining keys.
+fn parse_lfs_pointer(content: &[u8]) -> Option<LfsPointer> {
+    let text = std::str::from_utf8(content).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != "version https://git-lfs.github.com/spec/v1" {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(value) = line.strip_prefix("oid sha256:") {
+            oid = Some(value);
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.parse().ok();
+        }
+    }
+    let oid = oid.filter(|oid| {
+        oid.len() == 64 && oid.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })?;
+    Some(LfsPointer {
+        oid: oid.to_string(),
+        size: size?,
+    })
+}
+
+/// Copies of the LFS objects a push references, and the `git lfs push` that
+/// uploads them to the pinned origin.
+struct LfsUpload {
+    _store: TemporaryDirectory,
+    command: Command,
+    objects: usize,
+    bytes: u64,
+}
+
+/// Refuse a worktree `.lfsconfig` that names its own LFS endpoint. The
+/// upload would not honor it, so the objects would land somewhere the
+/// repository's users do not look.
+fn check_lfsconfig(repository: &PinnedRepository) -> Result<(), String> {
+    let path = repository.worktree.command_path.join(".lfsconfig");
+    match fs::symlink_metadata(&path) {
+        Err(_) => return Ok(()),
+        Ok(metadata) if !metadata.is_file() => {
+            return Err(".lfsconfig is not a regular file".to_string());
+        }
+        Ok(_) => {}
+    }
+    let output = Command::new("git")
+        .args(["config", "--no-includes", "--list", "-z", "--file"])
+        .arg(&path)
+        .output()
+        .map_err(|error| format!("failed to read .lfsconfig: {error}"))?;
+    if !output.status.success() {
+        return Err("could not parse .lfsconfig".to_string());
+    }
+    match config_entries(&output.stdout)
+        .into_iter()
+        .find(|(key, _)| is_lfs_url_key(key))
+    {
+        Some((key, _)) => Err(format!(
+            ".lfsconfig sets {key}; LFS objects are only uploaded to the endpoint of the pinned origin"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Find the LFS pointers among the blobs `revisions` adds and stage their
+/// objects for upload. Each object must be a regular file of the recorded
+/// size inside the repository's `lfs/objects`, and is copied into a private
+/// store so it cannot be swapped between the check and the upload. The
+/// upload runs in an empty repository with an unborn `HEAD`, so neither
+/// repo-local config nor a committed `.lfsconfig` can redirect it: the
+/// endpoint is derived from the pinned origin, and credentials come from
+/// the host's system and global config only.
+fn stage_lfs_upload(
+    repository: &PinnedRepository,
+    config_snapshot: &RepositoryConfigSnapshot,
+    revisions: &str,
+    expected_origin: &str,
+) -> Result<Option<LfsUpload>, String> {
+    let paths = pushed_paths(repository, config_snapshot, revisions)?;
+    let candidates: Vec<String> = blob_sizes(repository, config_snapshot, &paths)?
+        .into_iter()
+        .filter(|(_, size)| *size <= MAX_LFS_POINTER)
+        .map(|(oid, _)| oid)
+        .collect();
+    let mut pointers = BTreeMap::new();
+    for (blob, content) in blob_contents(repository, config_snapshot, &candidates)? {
+        if let Some(pointer) = parse_lfs_pointer(&content) {
+            pointers.insert(pointer.oid.clone(), (pointer, paths[&blob].clone()));
+        }
+    }
+    if pointers.is_empty() {
+        return Ok(None);
+    }
+    check_lfsconfig(repository)?;
+
+    let missing = |oid: &str, path: &str| {
+        format!(
+            "LFS object for {path} ({}) is not in .git/lfs/objects; run 'git lfs fetch' or re-add the file",
+            &oid[..12]
+        )
+    };
+    let objects_path = repository.common_dir_command_path.join("lfs/objects");
+    if !objects_path.exists()
+        && let Some((oid, (_, path))) = pointers.iter().next()
+    {
+        return Err(missing(oid, path));
+    }
+    let objects = pin_directory(
+        &objects_path,
+        &repository.allowed_root,
+        "Git LFS objects directory",
+    )?;
+    let store = TemporaryDirectory::create()?;
+    for directory in ["objects", "refs", "lfs/objects"] {
+        fs::create_dir_all(store.path.join(directory))
+            .map_err(|error| format!("could not create private LFS store: {error}"))?;
+    }
+    fs::write(store.path.join("HEAD"), "ref: refs/heads/main\n")
+        .and_then(|()| fs::write(store.path.join("config"), "[core]\n\tbare = true\n"))
+        .map_err(|error| format!("could not create private LFS store: {error}"))?;
+
+    let mut bytes = 0;
+    for (oid, (pointer, path)) in &pointers {
+        let relative = format!("{}/{}/{oid}", &oid[..2], &oid[2..4]);
+        let mut source =
+            File::open(objects.command_path.join(&relative)).map_err(|_| missing(oid, path))?;
+        let resolved = fs::canonicalize(fd_path(&source)).map_err(|_| missing(oid, path))?;
+        if !resolved.starts_with(&objects.resolved_path) {
+            return Err(format!(
+                "LFS object for {path} escapes the Git LFS objects directory"
+            ));
+        }
+        let metadata = source.metadata().map_err(|_| missing(oid, path))?;
+        if !metadata.is_file() || metadata.len() != pointer.size {
+            return Err(format!(
+                "LFS object for {path} ({}) is not the {} bytes its pointer records",
+                &oid[..12],
+                pointer.size
+            ));
+        }
+        let destination = store.path.join("lfs/objects").join(&relative);
+        fs::create_dir_all(destination.parent().expect("object path has a parent"))
+            .map_err(|error| format!("could not create private LFS store: {error}"))?;
+        let mut copy = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&destination)
+            .map_err(|error| format!("could not copy LFS object: {error}"))?;
+        let copied = std::io::copy(&mut (&mut source).take(pointer.size + 1), &mut copy)
+            .map_err(|error| format!("could not copy LFS object: {error}"))?;
+        if copied != pointer.size {
+            return Err(format!("LFS object for {path} changed while it was copied"));
+        }
+        bytes += copied;
+    }
+
+    let remote_name = temporary_remote_name()?;
+    let mut command = Command::new("git");
+    clear_repository_environment(&mut command);
+    command
+        .current_dir(&store.command_path)
+        .env("GIT_DIR", &store.command_path)
+        .env(
+            "GIT_CONFIG_SYSTEM",
+            config_snapshot.directory.command_path.join("system-config"),
+        )
+        .env(
+            "GIT_CONFIG_GLOBAL",
+            config_snapshot.directory.command_path.join("global-config"),
+        );
+    let mut command = harden(command, config_snapshot);
+    command
+        .arg("-c")
+        .arg(format!("remote.{remote_name}.url={expected_origin}"))
+        .args(["lfs", "push", "--object-id"])
+        .arg(&remote_name)
+        .args(pointers.keys());
+
+    Ok(Some(LfsUpload {
+        _store: store,
+        command,
+        objects: pointers.len(),
+        bytes,
+    }))
+}
+
+/// Build the host-side push with every ref it may update spelled out, so the
+/// policy check and the push itself see the same set. `--tags` is only
+/// expanded when tag patterns are configured. A force push leases its
+/// destination against `observed`, the host-observed tracking refs for this
+/// repository. A branch push names the commit it resolved rather than the
+/// branch, so what was inspected and approved is exactly what is sent even if
+/// the agent moves the branch meanwhile. When content checks are enabled,
+/// everything the origin does not already have is inspected and the findings
+/// returned for the caller to refuse on. In LFS mode the objects the pushed
+/// commits reference are staged for upload ahead of the push.
+fn prepare_push(
+    repository: &PinnedRepository,
+    audit: &LocalConfigAudit,
+    expected_origin: &str,
+    push: &Push,
+    config: &Config,
+    observed: &BTreeMap<String, String>,
+) -> Result<PreparedCommand, String> {
+    let config_snapshot = RepositoryConfigSnapshot::create(repository, &audit.snapshot_entries)?;
+    let remote_name = temporary_remote_name()?;
+    let inspect = config.inspects_content();
+    let examine = inspect || config.lfs;
+    let remote = if examine || (push == &Push::Tags && config.tag_patterns.is_some()) {
+        origin_refs(repository, &config_snapshot, expected_origin)?
+    } else {
+        BTreeMap::new()
+    };
+    let mut refspecs = Vec::new();
+    let mut refs = Vec::new();
+    let mut sources = Vec::new();
+    let mut dry_run = false;
+    let mut lease = None;
+    let mut target = None;
+    match push {
+        Push::Branch => {
+            let (source, destination) = resolve_branch_refspec(repository, &config_snapshot)?;
+            let oid = resolve_source_oid(repository, &config_snapshot, &source)?;
+            refspecs.push(format!("{oid}:{destination}"));
+            sources.push(oid.clone());
+            refs.push(destination.clone());
+            target = Some(managed_push::PushTarget {
+                reference: destination,
+                oid,
+            });
+        }
+        Push::Tags if config.tag_patterns.is_some() => {
+            refs = resolve_tag_updates(&local_tags(repository, &config_snapshot)?, &remote);
+            refspecs = refs
+                .iter()
+                .map(|reference| format!("{reference}:{reference}"))
+                .collect();
+            sources.clone_from(&refs);
+            // Nothing to update: let Git report it without writing anything
+            dry_run = refs.is_empty();
+        }
+        Push::Tags => {
+            if examine {
+                sources = local_tags(repository, &config_snapshot)?
+                    .into_keys()
+                    .collect();
+            }
+        }
+        Push::ForceWithLease => {
+            let (source, destination) = resolve_branch_refspec(repository, &config_snapshot)?;
+            let tracking = tracking_ref_for(&audit.origin_fetch_refspecs, &destination)
+                .ok_or_else(|| format!("'{destination}' has no origin tracking ref"))?;
+            let expected = observed.get(&tracking).ok_or_else(|| {
+                format!("no host-observed value for '{tracking}'; run 'git fetch' first")
+            })?;
+            let oid = resolve_source_oid(repository, &config_snapshot, &source)?;
+            lease = Some(format!("--force-with-lease={destination}:{expected}"));
+            refspecs.push(format!("{oid}:{destination}"));
+            sources.push(oid.clone());
+            refs.push(destination.clone());
+            target = Some(managed_push::PushTarget {
+                reference: destination,
+                oid,
+            });
+        }
+    }
+    let excludes: Vec<String> = remote.values().chain(observed.values()).cloned().collect();
+    let findings = if inspect {
+        inspect_push(repository, &config_snapshot, &sources, &excludes, config)?
+    } else {
+        Vec::new()
+    };
+    let lfs_upload = if config.lfs && !sources.is_empty() {
+        let revisions = exclusion_revisions(repository, &config_snapshot, &sources, &excludes)?;
+        stage_lfs_upload(repository, &config_snapshot, &revisions, expected_origin)?
+    } else {
+        None
+    };
+    let mut command = hardened_command(repository, &config_snapshot);
+    command.args([
+        "-c",
+        "push.gpgSign=false",
+        "-c",
+        "push.recurseSubmodules=no",
+    ]);
+    command
+        .arg("-c")
+        .arg(format!("remote.{remote_name}.pushurl={expected_origin}"));
+    for refspec in &audit.origin_fetch_refspecs {
+        command
+            .arg("-c")
+            .arg(format!("remote.{remote_name}.fetch={refspec}"));
+    }
+    command.args(["-c", "push.autoSetupRemote=false"]);
+    command.args(["push", "--no-verify"]);
+    if push == &Push::Tags && refspecs.is_empty() {
+        command.arg("--tags");
+    }
+    if dry_run {
+        command.arg("--dry-run");
+    }
+    if let Some(lease) = lease {
+        command.arg(lease);
+    }
+    command.arg(&remote_name).args(&refspecs);
+
+    Ok(PreparedCommand {
+        _config_snapshot: config_snapshot,
+        command,
+        refs,
+        findings,
+        target,
+        lfs_upload,
+    })
+}
+
+/// Fetch the pinned origin through a command-scoped remote. New objects land
+/// in the workspace object store; ref updates land in the snapshot's private
+/// refs and are returned as tracking updates for the container to apply.
+/// Tags are not imported and stale tracking refs are not pruned, since only
+/// `refs/remotes/origin/*` updates cross back into the container.
+fn prepare_fetch(
+    repository: &PinnedRepository,
+    audit: &LocalConfigAudit,
+    expected_origin: &str,
+) -> Result<PreparedCommand, String> {
+    let config_snapshot = RepositoryConfigSnapshot::create(repository, &audit.snapshot_entries)?;
+    let remote_name = temporary_remote_name()?;
+    let mut command = hardened_command(repository, &config_snapshot);
+    command.args([
+        "-c",
+        "fetch.recurseSubmodules=no",
+        "-c",
+        "submodule.recurse=false",
+        "-c",
+        "fetch.writeCommitGraph=false",
+        "-c",
+        "gc.auto=0",
+        "-c",
+        "maintenance.auto=false",
+    ]);
+    command
+        .arg("-c")
+        .arg(format!("remote.{remote_name}.url={expected_origin}"))
+        .arg("-c")
+        .arg(format!("remote.{remote_name}.promisor=false"));
+    for refspec in &audit.origin_fetch_refspecs {
+        command
+            .arg("-c")
+            .arg(format!("remote.{remote_name}.fetch={refspec}"));
+    }
+    command.args([
+        "fetch",
+        "--no-tags",
+        "--no-prune",
+        "--no-write-fetch-head",
+        "--no-recurse-submodules",
+        "--no-auto-maintenance",
+    ]);
+    command.arg(&remote_name);
+
+    Ok(PreparedCommand {
+        _config_snapshot: config_snapshot,
+        command,
+        refs: Vec::new(),
+        findings: Vec::new(),
+        target: None,
+        lfs_upload: None,
+    })
+}
+
+fn origin_url_at(repository: &PinnedRepository) -> Option<String> {
+    let output = repository
+        .command()
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+pub fn origin_url() -> Option<String> {
+    let cwd = std::env::current_dir().ok()?;
+    managed_push::origin_url_at(&cwd)
+}
+
+fn repository_root_at(directory: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+    fs::canonicalize(path).ok()
+}
+
+pub fn repository_root() -> Option<PathBuf> {
+    repository_root_at(&std::env::current_dir().ok()?)
+}
+
+fn deny(stderr: String) -> Response {
+    Response {
+        exit_code: 1,
+        stdout: String::new(),
+        stderr,
+        tracking_updates: Vec::new(),
+    }
+}
+
+/// Check that a push updates only branches an approval is restricted to.
+/// Tags are outside any branch restriction.
+fn check_approved_branches(patterns: &[String], refs: &[String]) -> Result<(), String> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    if refs.is_empty() {
+        return Err("the approval covers branch pushes only".to_string());
+    }
+    for reference in refs {
+        let covered = reference
+            .strip_prefix("refs/heads/")
+            .is_some_and(|branch| patterns.iter().any(|pattern| glob_match(pattern, branch)));
+        if !covered {
+            return Err(format!(
+                "'{reference}' is outside the approved branches ({})",
+                patterns.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A managed request that needs the approver, and why.
+struct Held {
+    repository: managed_push::Repository,
+    previous: Option<String>,
+    state_dir: PathBuf,
+    reason: String,
+}
+
+/// How a request is gated once its repository is resolved.
+enum Gate {
+    /// Single-repository mode.
+    Open,
+    /// Not approved: recorded as a candidate and refused.
+    Hold(Held),
+    /// Approved, subject to the approval's branches and push target. A
+    /// single-use approval is consumed only once the request is known to be
+    /// covered.
+    Approved(managed_push::Approval, Held),
+}
+
+fn resolve_request_repository(
+    req: &Request,
+    verb: &str,
+    mode: &Mode,
+) -> Result<(PathBuf, String, PathBuf, Gate), Response> {
+    match mode {
+        Mode::Single { repository, origin } => Ok((
+            repository.clone(),
+            origin.clone(),
+            repository.clone(),
+            Gate::Open,
+        )),
+        Mode::Managed {
+            workspace_root,
+            state_dir,
+        } => {
+            let cwd = req.cwd.as_deref().ok_or_else(|| {
+                deny(format!(
+                    "git-proxy: managed {verb} request did not include a working directory"
+                ))
+            })?;
+            let (repository_path, repository) =
+                managed_push::resolve_repository(workspace_root, cwd)
+                    .map_err(|error| deny(format!("git-proxy: {verb} refused: {error}")))?;
+            let approval = managed_push::read_approval(state_dir, &repository.relative_path)
+                .map_err(|error| deny(format!("git-proxy: {verb} refused: {error}")))?;
+
+            match approval {
+                Some(approval) if approval.repository.origin == repository.origin => {
+                    let origin = repository.origin.clone();
+                    let held = Held {
+                        repository,
+                        previous: None,
+                        state_dir: state_dir.clone(),
+                        reason: String::new(),
+                    };
+                    Ok((
+                        repository_path,
+                        origin,
+                        workspace_root.clone(),
+                        Gate::Approved(approval, held),
+                    ))
+                }
+                approval => {
+                    let previous = approval.map(|value| value.repository.origin);
+                    let reason = if let Some(previous) = &previous {
+                        format!("origin changed from {previous} to {}", repository.origin)
+                    } else {
+                        "repository is not approved".to_string()
+                    };
+                    let origin = repository.origin.clone();
+                    Ok((
+                        repository_path,
+                        origin,
+                        workspace_root.clone(),
+                        Gate::Hold(Held {
+                            repository,
+                            previous,
+                            state_dir: state_dir.clone(),
+                            reason,
+                        }),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Record a held request as a candidate and refuse it. A push is resolved
+/// locally first, so the approver sees the exact ref and commit it would send
+/// and a push the policy refuses anyway is never queued. Nothing here
+/// contacts origin.
+fn hold_request(
+    operation: &Operation,
+    cmd_str: &str,
+    repository: &PinnedRepository,
+    audit: &LocalConfigAudit,
+    observed: &BTreeMap<String, String>,
+    held: Held,
+    log: &Arc<Mutex<File>>,
+) -> Response {
+    let verb = operation.verb();
+    let preview = match operation {
+        Operation::Push(push) => {
+            let previewed =
+                preview_push(repository, audit, push, observed).and_then(|(refs, preview)| {
+                    check_push_policy(config(), &refs)?;
+                    if push == &Push::ForceWithLease {
+                        check_force_policy(config(), &refs)?;
+                    }
+                    Ok(preview)
+                });
+            match previewed {
+                Ok(preview) => preview,
+                Err(error) => {
+                    log_line(log, &format!("DENIED  git {} ({})", cmd_str, error));
+                    return deny(format!("git-proxy: push refused: {error}"));
+                }
+            }
+        }
+        Operation::Fetch => None,
+    };
+    let what = if preview.is_some() {
+        "this push or the repository"
+    } else {
+        "the repository"
+    };
+    let id = match managed_push::record_candidate(
+        &held.state_dir,
+        &held.repository,
+        held.previous,
+        preview,
+    ) {
+        Ok(id) => id,
+        Err(error) => return deny(format!("git-proxy: {verb} refused: {error}")),
+    };
+    log_line(
+        log,
+        &format!(
+            "PENDING {} ({}; candidate {})",
+            held.repository.relative_path, held.reason, id
+        ),
+    );
+    deny(format!(
+        "git-proxy: {verb} pending approval for '{}' ({})\nOn the host, run 'claude-sandbox approvals list' (or open the T3 admin portal), approve {what}, and retry.",
+        held.repository.relative_path, held.repository.origin
+    ))
+}
+
+fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response {
+    let cmd_str = req.args.join(" ");
+
+    let operation = match parse_args(&req.args) {
+        Some(operation) => operation,
+        None => {
+            log_line(log, &format!("DENIED  git {} (not allowed)", cmd_str));
+            return deny(format!(
+                "git-proxy: command not allowed: git {}\n\
+                 Only 'git push', 'git push --tags', 'git push --force-with-lease', \
+                 'git fetch' and 'git fetch origin' are bridged to the host.",
+                cmd_str
+            ));
+        }
+    };
+    let verb = operation.verb();
+
+    let (repository_path, expected_origin, allowed_root, gate) =
+        match resolve_request_repository(&req, verb, mode) {
+            Ok(repository) => repository,
+            Err(response) => return response,
+        };
+    let repository_label = repository_path.display();
+    let repository = match pin_repository(&repository_path, &allowed_root) {
+        Ok(repository) => repository,
+        Err(error) => return deny(format!("git-proxy: {verb} refused: {error}")),
+    };
+
+    let audit = match local_config_audit(&repository) {
         Ok(audit) => audit,
         Err(error) => {
             log_line(log, &format!("ERROR   git {} ({})", cmd_str, error));
@@ -1003,10 +2254,10 @@ fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response
             &format!("DENIED  git {} (local config: {})", cmd_str, list),
         );
         return deny(format!(
-            "git-proxy: push refused: the repository's local git config sets \
+            "git-proxy: {} refused: the repository's local git config sets \
                  key(s) the host will not honor: {}. Remove them from .git/config \
                  and try again.",
-            list
+            verb, list
         ));
     }
     for refspec in &audit.origin_fetch_refspecs {
@@ -1015,7 +2266,7 @@ fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response
                 log,
                 &format!("DENIED  git {} (origin fetch refspec: {})", cmd_str, error),
             );
-            return deny(format!("git-proxy: push refused: {error}"));
+            return deny(format!("git-proxy: {verb} refused: {error}"));
         }
     }
 
@@ -1031,37 +2282,185 @@ fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response
                 ),
             );
             return deny(format!(
-                "git-proxy: push refused: remote 'origin' changed since the \
+                "git-proxy: {} refused: remote 'origin' changed since the \
                  sandbox was launched (was {}, now {})",
-                expected_origin, now
+                verb, expected_origin, now
             ));
         }
     }
 
-    let mut prepared = match prepare_push(&repository, &audit, &expected_origin, &push) {
+    let observed = observed_tracking(&repository_path);
+    let gate = match gate {
+        Gate::Hold(held) => {
+            return hold_request(
+                &operation,
+                &cmd_str,
+                &repository,
+                &audit,
+                &observed,
+                held,
+                log,
+            );
+        }
+        gate => gate,
+    };
+
+    let prepared = match &operation {
+        Operation::Push(push) => prepare_push(
+            &repository,
+            &audit,
+            &expected_origin,
+            push,
+            config(),
+            &observed,
+        ),
+        Operation::Fetch => prepare_fetch(&repository, &audit, &expected_origin),
+    };
+    let mut prepared = match prepared {
         Ok(prepared) => prepared,
-        Err(error) => return deny(format!("git-proxy: {error}")),
+        Err(error) => {
+            log_line(log, &format!("DENIED  git {} ({})", cmd_str, error));
+            return deny(format!("git-proxy: {verb} refused: {error}"));
+        }
     };
+    let force = operation == Operation::Push(Push::ForceWithLease);
+    let policy = check_push_policy(config(), &prepared.refs).and_then(|()| {
+        if force {
+            check_force_policy(config(), &prepared.refs)
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(error) = policy {
+        log_line(
+            log,
+            &format!("DENIED  git {} (push policy: {})", cmd_str, error),
+        );
+        return deny(format!("git-proxy: push refused: {error}"));
+    }
+
+    if !prepared.findings.is_empty() {
+        log_line(
+            log,
+            &format!(
+                "DENIED  git {} (content checks: {} finding(s))",
+                cmd_str,
+                prepared.findings.len()
+            ),
+        );
+        return deny(format!(
+            "git-proxy: push refused by host-side content checks:\n{}",
+            format_findings(&prepared.findings)
+        ));
+    }
 
+    if let Gate::Approved(approval, mut held) = gate {
+        let refused = match &operation {
+            Operation::Fetch => None,
+            Operation::Push(_) if approval.scope == managed_push::ApprovalScope::Push => {
+                (prepared.target != approval.target).then(|| {
+                    // The approval is stale; hold the new push for review instead
+                    let _ = managed_push::revoke(&held.state_dir, &held.repository.relative_path);
+                    "branch moved after approval".to_string()
+                })
+            }
+            Operation::Push(_) => check_approved_branches(&approval.branches, &prepared.refs).err(),
+        };
+        if let Some(reason) = refused {
+            held.reason = reason;
+            return hold_request(
+                &operation,
+                &cmd_str,
+                &repository,
+                &audit,
+                &observed,
+                held,
+                log,
+            );
+        }
+        // Single-use approvals are for the push; a fetch before it, as in
+        // `git pull && git push` or a force push's lease check, is free
+        let single_use = approval.scope != managed_push::ApprovalScope::Persistent
+            && operation != Operation::Fetch;
+        if single_use {
+            match managed_push::consume_once(&held.state_dir, &approval) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return deny(
+                        "git-proxy: the one-time approval was already consumed; approve this repository again"
+                            .to_string(),
+                    );
+                }
+                Err(error) => return deny(format!("git-proxy: {verb} refused: {error}")),
+            }
+        }
+    }
+
+    let refs = if prepared.refs.is_empty() {
+        String::new()
+    } else {
+        format!(" -> {}", prepared.refs.join(", "))
+    };
+    let decision = if force { "FORCE  " } else { "ALLOWED" };
     log_line(
         log,
-        &format!("ALLOWED git {} ({})", cmd_str, repository_label),
+        &format!("{decision} git {} ({}){}", cmd_str, repository_label, refs),
     );
 
+    if let Some(upload) = prepared.lfs_upload.as_mut() {
+        log_line(
+            log,
+            &format!(
+                "LFS     git {} ({}) -> {} object(s), {} bytes",
+                cmd_str, repository_label, upload.objects, upload.bytes
+            ),
+        );
+        let failure = match upload.command.output() {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(error) => Some(format!("could not run git lfs: {error}")),
+        };
+        if let Some(error) = failure {
+            log_line(
+                log,
+                &format!("ERROR   git {} (LFS upload: {})", cmd_str, error),
+            );
+            return deny(format!(
+                "git-proxy: push refused: LFS upload failed: {error}"
+            ));
+        }
+    }
+
     match prepared.command.output() {
         Ok(output) => {
             let mut exit_code = output.status.code().unwrap_or(1);
             let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
             let tracking_updates = if output.status.success() {
-                match prepared.tracking_updates(&repository) {
-                    Ok(updates) => updates,
+                let tracked = if operation == Operation::Fetch {
+                    prepared.tracking_refs(&repository).map(|refs| {
+                        record_observed_tracking(&repository_path, refs);
+                    })
+                } else {
+                    Ok(())
+                };
+                match tracked.and_then(|()| prepared.tracking_updates(&repository)) {
+                    Ok(updates) => {
+                        record_observed_tracking(
+                            &repository_path,
+                            updates
+                                .iter()
+                                .map(|update| (update.reference.clone(), update.new_oid.clone()))
+                                .collect(),
+                        );
+                        updates
+                    }
                     Err(error) => {
                         exit_code = 1;
                         if !stderr.is_empty() && !stderr.ends_with('\n') {
                             stderr.push('\n');
                         }
                         stderr.push_str(&format!(
-                            "git-proxy: remote push succeeded, but tracking refs could not be prepared: {error}\n"
+                            "git-proxy: remote {verb} succeeded, but tracking refs could not be prepared: {error}\n"
                         ));
                         Vec::new()
                     }
@@ -1090,7 +2489,8 @@ fn handle_request(req: Request, mode: &Mode, log: &Arc<Mutex<File>>) -> Response
     }
 }
 
-pub fn run(socket_path: &str, log_path: &Path, mode: Mode) {
+pub fn run(socket_path: &str, log_path: &Path, mode: Mode, config: Config) {
+    let _ = CONFIG.set(config);
     let path = Path::new(socket_path);
     let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
         eprintln!(
@@ -1106,68 +2506,25 @@ pub fn run(socket_path: &str, log_path: &Path, mode: Mode) {
         eprintln!("git-proxy: failed to bind {}: {}", socket_path, e);
         std::process::exit(1);
     });
-    let listener = bound.listener;
-    let socket_identity = bound.identity;
-
     log_line(&log, &format!("listening on {} ({mode:?})", socket_path));
-
-    // Watchdog: exit when parent process (podman after exec) dies.
-    // After exec(), our ppid is podman's PID. When podman exits, ppid
-    // becomes 1 (init). Poll every 2s and clean up when that happens.
-    let parent_pid = std::os::unix::process::parent_id();
-    let watchdog_socket = socket_identity.clone();
-    let watchdog_log = Arc::clone(&log);
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(2));
-            let current_ppid = std::os::unix::process::parent_id();
-            if current_ppid != parent_pid {
-                log_line(
-                    &watchdog_log,
-                    &format!(
-                        "parent {} exited (ppid now {}), shutting down",
-                        parent_pid, current_ppid
-                    ),
-                );
-                let _ = watchdog_socket.remove_if_owned();
-                process::exit(0);
-            }
-        }
-    });
-
-    let mode = Arc::new(mode);
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let log = Arc::clone(&log);
-                let mode = Arc::clone(&mode);
-                thread::spawn(move || {
-                    let reader = BufReader::new(&stream);
-                    let mut writer = &stream;
-
-                    // Read exactly one JSON line
-                    let mut line = String::new();
-                    if let Ok(n) = reader.take(1_048_576).read_line(&mut line) {
-                        if n == 0 {
-                            return;
-                        }
-                        let response = match serde_json::from_str::<Request>(&line) {
-                            Ok(req) => handle_request(req, &mode, &log),
-                            Err(e) => {
-                                log_line(&log, &format!("INVALID ({})", e));
-                                deny(format!("git-proxy: invalid request: {}", e))
-                            }
-                        };
-                        let _ = serde_json::to_writer(&mut writer, &response);
-                        let _ = writer.write_all(b"\n");
-                    }
-                });
-            }
-            Err(e) => {
-                log_line(&log, &format!("connection error: {}", e));
-            }
-        }
-    }
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    proxy_socket::serve_json(
+        bound.listener,
+        Arc::clone(&log),
+        1_048_576,
+        move |req: Request| {
+            let label = format!("git {}", req.args.join(" "));
+            let mut response = handle_request(req, &mode, &log);
+            redact::response_fields(
+                &log,
+                &label,
+                &mut [&mut response.stdout, &mut response.stderr],
+            );
+            response
+        },
+        |e| deny(format!("git-proxy: invalid request: {}", e)),
+    );
 }
 
 #[cfg(test)]
@@ -1316,6 +2673,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_force_with_lease_allowed() {
+        assert_eq!(
+            parse_push_args(&strs(&["push", "--force-with-lease"])),
+            Some(Push::ForceWithLease)
+        );
+    }
+
+    #[test]
+    fn test_fetch_origin_allowed() {
+        assert_eq!(parse_args(&strs(&["fetch"])), Some(Operation::Fetch));
+        assert_eq!(
+            parse_args(&strs(&["fetch", "origin"])),
+            Some(Operation::Fetch)
+        );
+        assert_eq!(
+            parse_args(&strs(&["push"])),
+            Some(Operation::Push(Push::Branch))
+        );
+    }
+
+    #[test]
+    fn test_other_fetches_denied() {
+        assert_eq!(parse_args(&strs(&["fetch", "--all"])), None);
+        assert_eq!(parse_args(&strs(&["fetch", "upstream"])), None);
+        assert_eq!(parse_args(&strs(&["fetch", "origin", "main"])), None);
+        assert_eq!(parse_args(&strs(&["fetch", "--upload-pack=sh"])), None);
+        assert_eq!(parse_args(&strs(&["pull"])), None);
+    }
+
     #[test]
     fn managed_push_requires_approval_then_routes_to_repository() {
         let root = test_root("managed-push");
@@ -1373,7 +2760,7 @@ mod tests {
         let candidates = managed_push::list_candidates(&state).unwrap();
         assert_eq!(candidates.len(), 1);
         let (candidate_id, candidate) = &candidates[0];
-        managed_push::approve(
+        managed_push::tests::approve(
             &state,
             &candidate.repository,
             managed_push::ApprovalScope::Persistent,
@@ -1406,12 +2793,27 @@ mod tests {
         );
 
         managed_push::revoke(&state, &candidate.repository.relative_path).unwrap();
-        managed_push::approve(
+        managed_push::tests::approve(
             &state,
             &candidate.repository,
             managed_push::ApprovalScope::Once,
         )
         .unwrap();
+        // `git pull && git push`: the fetch does not spend the approval
+        let fetched = handle_request(
+            Request {
+                args: strs(&["fetch"]),
+                cwd: Some("/workspace/project".to_string()),
+            },
+            &mode,
+            &log,
+        );
+        assert_eq!(fetched.exit_code, 0, "{}", fetched.stderr);
+        assert!(
+            managed_push::read_approval(&state, &candidate.repository.relative_path)
+                .unwrap()
+                .is_some()
+        );
         let one_time = handle_request(request(), &mode, &log);
         assert_eq!(one_time.exit_code, 0, "{}", one_time.stderr);
         apply_tracking_updates(&repository, &one_time.tracking_updates);
@@ -1434,6 +2836,199 @@ mod tests {
         fs::remove_dir_all(root).unwrap();
     }
 
+    #[test]
+    fn managed_push_approval_is_bound_to_the_previewed_commit() {
+        let root = test_root("managed-push-preview");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        let (repository, remote) = initialize_repository(&workspace);
+        let state = root.join("state");
+        let log = test_log(&root);
+        let mode = Mode::Managed {
+            workspace_root: workspace.clone(),
+            state_dir: state.clone(),
+        };
+        let request = || Request {
+            args: strs(&["push"]),
+            cwd: Some("/workspace/project".to_string()),
+        };
+
+        let held = handle_request(request(), &mode, &log);
+        assert!(held.stderr.contains("approve this push"), "{}", held.stderr);
+        let (id, candidate) = managed_push::list_candidates(&state).unwrap().remove(0);
+        let preview = candidate.push.expect("branch push has a preview");
+        assert_eq!(preview.target.reference, "refs/heads/main");
+        assert_eq!(
+            preview.target.oid,
+            git_stdout(&repository, &["rev-parse", "HEAD"])
+        );
+        assert!(preview.commits[0].ends_with(" initial"));
+        assert_eq!(
+            preview.diffstat,
+            vec!["1 file(s) changed, +1 -0", "file.txt | +1 -0"]
+        );
+        managed_push::tests::approve_push(&state, &candidate.repository, preview.target).unwrap();
+        managed_push::remove_candidate(&state, &id).unwrap();
+
+        // The branch moves after approval: the push is held again for review
+        fs::write(repository.join("file.txt"), "moved\n").unwrap();
+        run_git(&repository, &["commit", "-am", "moved"]);
+        let moved = handle_request(request(), &mode, &log);
+        assert!(
+            moved.stderr.contains("pending approval"),
+            "{}",
+            moved.stderr
+        );
+        assert!(
+            managed_push::read_approval(&state, &candidate.repository.relative_path)
+                .unwrap()
+                .is_none()
+        );
+        let (id, candidate) = managed_push::list_candidates(&state).unwrap().remove(0);
+        let preview = candidate.push.unwrap();
+        assert_eq!(
+            preview.target.oid,
+            git_stdout(&repository, &["rev-parse", "HEAD"])
+        );
+        assert!(
+            fs::read_to_string(root.join("proxy.log"))
+                .unwrap()
+                .contains("branch moved after approval")
+        );
+        managed_push::tests::approve_push(&state, &candidate.repository, preview.target).unwrap();
+        managed_push::remove_candidate(&state, &id).unwrap();
+
+        let pushed = handle_request(request(), &mode, &log);
+        assert_eq!(pushed.exit_code, 0, "{}", pushed.stderr);
+        assert_eq!(
+            git_stdout(&remote, &["rev-parse", "refs/heads/main"]),
+            git_stdout(&repository, &["rev-parse", "HEAD"])
+        );
+        assert!(
+            managed_push::read_approval(&state, &candidate.repository.relative_path)
+                .unwrap()
+                .is_none()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn managed_push_approval_can_be_limited_to_branches() {
+        let root = test_root("managed-push-branches");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        let (repository, remote) = initialize_repository(&workspace);
+        let state = root.join("state");
+        let log = test_log(&root);
+        let mode = Mode::Managed {
+            workspace_root: workspace.clone(),
+            state_dir: state.clone(),
+        };
+        let request = || Request {
+            args: strs(&["push"]),
+            cwd: Some("/workspace/project".to_string()),
+        };
+        let approve = |branches: &[&str]| {
+            handle_request(request(), &mode, &log);
+            let (id, _) = managed_push::list_candidates(&state).unwrap().remove(0);
+            let limits = managed_push::ApprovalLimits {
+                duration: Some(3600),
+                branches: strs(branches),
+            };
+            managed_push::approve_candidate(
+                &state,
+                &workspace,
+                &id,
+                managed_push::ApprovalScope::Persistent,
+                None,
+                &limits,
+            )
+            .unwrap();
+        };
+
+        approve(&["agent/*"]);
+        let outside = handle_request(request(), &mode, &log);
+        assert!(
+            outside.stderr.contains("pending approval"),
+            "{}",
+            outside.stderr
+        );
+        assert!(
+            fs::read_to_string(root.join("proxy.log"))
+                .unwrap()
+                .contains("'refs/heads/main' is outside the approved branches (agent/*)")
+        );
+        // The approval stays in place for pushes to the approved branches
+        assert!(
+            managed_push::read_approval(&state, "project")
+                .unwrap()
+                .is_some()
+        );
+
+        managed_push::revoke(&state, "project").unwrap();
+        approve(&["ma*"]);
+        let inside = handle_request(request(), &mode, &log);
+        assert_eq!(inside.exit_code, 0, "{}", inside.stderr);
+        assert_eq!(
+            git_stdout(&remote, &["rev-parse", "refs/heads/main"]),
+            git_stdout(&repository, &["rev-parse", "HEAD"])
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_check_approved_branches() {
+        let refs = strs(&["refs/heads/agent/fix", "refs/heads/agent/docs"]);
+        assert!(check_approved_branches(&[], &[]).is_ok());
+        assert!(check_approved_branches(&strs(&["agent/*"]), &refs).is_ok());
+        assert!(check_approved_branches(&strs(&["agent/fix"]), &refs).is_err());
+        assert!(check_approved_branches(&strs(&["*"]), &strs(&["refs/tags/v1"])).is_err());
+        assert!(check_approved_branches(&strs(&["*"]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_scan_blob_checks_binary_files_for_known_formats() {
+        let token = format!("ghp_{}", "k".repeat(36));
+        let mut findings = Vec::new();
+        scan_blob(
+            "image.bin",
+            format!("\0\x01{token}\0").as_bytes(),
+            &mut findings,
+        );
+        assert_eq!(findings, vec!["secret: image.bin:1 (known secret format)"]);
+
+        let assignment = "API_TOKEN = \"q8Zr3LmX2vT9bKp4WnY7cHs1fJd6GtE0\"";
+        let mut findings = Vec::new();
+        scan_blob("config.env", assignment.as_bytes(), &mut findings);
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        let mut findings = Vec::new();
+        scan_blob(
+            "data.bin",
+            format!("\0{assignment}").as_bytes(),
+            &mut findings,
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn test_summarize_numstat() {
+        let summary = summarize_numstat("1\t2\ta.txt\n3\t0\ta.txt\n-\t-\timage.png\n\n");
+        assert_eq!(
+            summary,
+            vec![
+                "2 file(s) changed, +4 -2",
+                "a.txt | +4 -2",
+                "image.png | binary"
+            ]
+        );
+        let many: String = (0..60).map(|i| format!("1\t0\tf{i:02}\n")).collect();
+        let summary = summarize_numstat(&many);
+        assert_eq!(summary.len(), MAX_PREVIEW_LINES + 2);
+        assert_eq!(summary.last().unwrap(), "... and 10 more files");
+    }
+
     #[test]
     fn single_push_updates_and_repairs_origin_tracking_without_persisting_remote() {
         let root = test_root("single-tracking");
@@ -1474,30 +3069,153 @@ mod tests {
 
         run_git(
             &repository,
-            &["update-ref", "-d", "refs/remotes/origin/main"],
+            &["update-ref", "-d", "refs/remotes/origin/main"],
+        );
+        let repaired = handle_request(push_request(false), &mode, &log);
+        assert_eq!(repaired.exit_code, 0, "{}", repaired.stderr);
+        apply_tracking_updates(&repository, &repaired.tracking_updates);
+        assert_eq!(
+            head,
+            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        );
+
+        run_git(&repository, &["tag", "v1"]);
+        let tracking_before_tags =
+            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"]);
+        let tags = handle_request(push_request(true), &mode, &log);
+        assert_eq!(tags.exit_code, 0, "{}", tags.stderr);
+        apply_tracking_updates(&repository, &tags.tracking_updates);
+        assert_eq!(
+            git_stdout(&remote, &["rev-parse", "refs/tags/v1"]),
+            git_stdout(&repository, &["rev-parse", "refs/tags/v1"])
+        );
+        assert_eq!(
+            tracking_before_tags,
+            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    fn fetch_request() -> Request {
+        Request {
+            args: strs(&["fetch", "origin"]),
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn fetch_imports_objects_and_returns_tracking_updates_without_hooks() {
+        let root = test_root("fetch-tracking");
+        let (repository, remote) = initialize_repository(&root);
+        run_git(&repository, &["push", "origin", "main"]);
+        run_git(&repository, &["fetch", "origin"]);
+
+        let upstream = root.join("upstream");
+        run_git(
+            &root,
+            &[
+                "clone",
+                remote.to_str().unwrap(),
+                upstream.to_str().unwrap(),
+            ],
+        );
+        run_git(&upstream, &["config", "user.name", "Upstream"]);
+        run_git(
+            &upstream,
+            &["config", "user.email", "upstream@example.invalid"],
+        );
+        fs::write(upstream.join("file.txt"), "updated\n").unwrap();
+        run_git(&upstream, &["commit", "-am", "update"]);
+        run_git(&upstream, &["push", "origin", "main"]);
+        run_git(&upstream, &["tag", "v2"]);
+        run_git(&upstream, &["push", "origin", "v2"]);
+        let upstream_head = git_stdout(&upstream, &["rev-parse", "HEAD"]);
+
+        let marker = root.join("hook-ran");
+        let hook = repository.join(".git/hooks/reference-transaction");
+        fs::write(&hook, format!("#!/bin/sh\ntouch '{}'\n", marker.display())).unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let previous = git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"]);
+        let fetched = handle_request(
+            fetch_request(),
+            &single_mode(&repository, &remote),
+            &test_log(&root),
+        );
+        assert_eq!(fetched.exit_code, 0, "{}", fetched.stderr);
+        assert_eq!(fetched.tracking_updates.len(), 1);
+        let update = &fetched.tracking_updates[0];
+        assert_eq!(update.reference, "refs/remotes/origin/main");
+        assert_eq!(update.old_oid.as_deref(), Some(previous.as_str()));
+        assert_eq!(update.new_oid, upstream_head);
+        assert!(!marker.exists());
+        assert_eq!(
+            previous,
+            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        );
+
+        apply_tracking_updates(&repository, &fetched.tracking_updates);
+        assert_eq!(
+            upstream_head,
+            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        );
+        run_git(&repository, &["cat-file", "-e", &upstream_head]);
+        run_git(&repository, &["merge", "--ff-only", "origin/main"]);
+        assert_eq!(
+            fs::read_to_string(repository.join("file.txt")).unwrap(),
+            "updated\n"
+        );
+        let tag = Command::new("git")
+            .arg("-C")
+            .arg(&repository)
+            .args(["rev-parse", "--verify", "--quiet", "refs/tags/v2"])
+            .output()
+            .unwrap();
+        assert!(!tag.status.success());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn fetch_is_refused_when_origin_or_config_changes() {
+        let root = test_root("fetch-refused");
+        let (repository, remote) = initialize_repository(&root);
+        run_git(&repository, &["push", "origin", "main"]);
+        run_git(&repository, &["fetch", "origin"]);
+        let mode = single_mode(&repository, &remote);
+        let log = test_log(&root);
+
+        run_git(
+            &repository,
+            &[
+                "config",
+                "remote.origin.uploadpack",
+                "touch /tmp/pwned; git-upload-pack",
+            ],
         );
-        let repaired = handle_request(push_request(false), &mode, &log);
-        assert_eq!(repaired.exit_code, 0, "{}", repaired.stderr);
-        apply_tracking_updates(&repository, &repaired.tracking_updates);
-        assert_eq!(
-            head,
-            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        let denied = handle_request(fetch_request(), &mode, &log);
+        assert_eq!(denied.exit_code, 1);
+        assert!(denied.stderr.contains("fetch refused"), "{}", denied.stderr);
+        assert!(denied.stderr.contains("remote.origin.uploadpack"));
+        run_git(
+            &repository,
+            &["config", "--unset", "remote.origin.uploadpack"],
         );
 
-        run_git(&repository, &["tag", "v1"]);
-        let tracking_before_tags =
-            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"]);
-        let tags = handle_request(push_request(true), &mode, &log);
-        assert_eq!(tags.exit_code, 0, "{}", tags.stderr);
-        apply_tracking_updates(&repository, &tags.tracking_updates);
-        assert_eq!(
-            git_stdout(&remote, &["rev-parse", "refs/tags/v1"]),
-            git_stdout(&repository, &["rev-parse", "refs/tags/v1"])
-        );
-        assert_eq!(
-            tracking_before_tags,
-            git_stdout(&repository, &["rev-parse", "refs/remotes/origin/main"])
+        run_git(
+            &repository,
+            &[
+                "remote",
+                "set-url",
+                "origin",
+                "https://example.invalid/x.git",
+            ],
         );
+        let redirected = handle_request(fetch_request(), &mode, &log);
+        assert_eq!(redirected.exit_code, 1);
+        assert!(redirected.stderr.contains("remote 'origin' changed"));
+        assert!(redirected.tracking_updates.is_empty());
 
         fs::remove_dir_all(root).unwrap();
     }
@@ -1586,6 +3304,8 @@ mod tests {
             &audit,
             approved_remote.to_str().unwrap(),
             &Push::Branch,
+            &Config::default(),
+            &BTreeMap::new(),
         )
         .unwrap();
 
@@ -1955,8 +3675,15 @@ mod tests {
         let pinned = pin_repository(&linked, &root).unwrap();
         assert_ne!(pinned.git_dir_command_path, pinned.common_dir_command_path);
         let audit = local_config_audit(&pinned).unwrap();
-        let mut prepared =
-            prepare_push(&pinned, &audit, remote.to_str().unwrap(), &Push::Branch).unwrap();
+        let mut prepared = prepare_push(
+            &pinned,
+            &audit,
+            remote.to_str().unwrap(),
+            &Push::Branch,
+            &Config::default(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
         let output = prepared.command.output().unwrap();
         assert!(
             output.status.success(),
@@ -1984,6 +3711,10 @@ mod tests {
         assert_eq!(parse_push_args(&strs(&["--tags"])), None);
         assert_eq!(parse_push_args(&strs(&["push", "--force"])), None);
         assert_eq!(parse_push_args(&strs(&["push", "-f"])), None);
+        assert_eq!(
+            parse_push_args(&strs(&["push", "--force-with-lease=main:abc"])),
+            None
+        );
         assert_eq!(parse_push_args(&strs(&["push", "origin"])), None);
         assert_eq!(parse_push_args(&strs(&["push", "origin", "main"])), None);
         assert_eq!(
@@ -1995,6 +3726,515 @@ mod tests {
         assert_eq!(parse_push_args(&strs(&["push", "--mirror"])), None);
     }
 
+    // ── Push policy ────────────────────────────────────────────────
+
+    fn policy(allowed: &[&str], protected: &[&str], tags: Option<&[&str]>) -> Config {
+        Config {
+            allowed_branches: strs(allowed),
+            protected_branches: strs(protected),
+            tag_patterns: tags.map(strs),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_default_policy_allows_any_ref() {
+        let config = Config::default();
+        assert!(check_push_policy(&config, &strs(&["refs/heads/main"])).is_ok());
+        assert!(check_push_policy(&config, &strs(&["refs/tags/v1"])).is_ok());
+    }
+
+    #[test]
+    fn test_policy_allowed_and_protected_branches() {
+        let config = policy(&["agent/*", "main"], &["main", "release/*"], None);
+        assert!(check_push_policy(&config, &strs(&["refs/heads/agent/fix"])).is_ok());
+        let protected = check_push_policy(&config, &strs(&["refs/heads/main"])).unwrap_err();
+        assert_eq!(protected, "branch 'main' is protected");
+        let outside = check_push_policy(&config, &strs(&["refs/heads/feature"])).unwrap_err();
+        assert!(outside.contains("'feature'"), "{outside}");
+        assert!(outside.contains("agent/*"), "{outside}");
+        let later = check_push_policy(
+            &config,
+            &strs(&["refs/heads/agent/a", "refs/heads/release/1"]),
+        )
+        .unwrap_err();
+        assert_eq!(later, "branch 'release/1' is protected");
+    }
+
+    #[test]
+    fn test_policy_tag_patterns() {
+        let config = policy(&[], &["main"], Some(&["agent-*"]));
+        assert!(check_push_policy(&config, &strs(&["refs/tags/agent-1"])).is_ok());
+        let denied = check_push_policy(&config, &strs(&["refs/tags/v1.0"])).unwrap_err();
+        assert!(denied.contains("tag 'v1.0'"), "{denied}");
+        assert!(check_push_policy(&config, &strs(&["refs/notes/commits"])).is_err());
+    }
+
+    #[test]
+    fn test_force_policy_requires_agent_owned_branch() {
+        let disabled = check_force_policy(&Config::default(), &strs(&["refs/heads/agent/x"]));
+        assert!(disabled.unwrap_err().contains("force pushes are disabled"));
+        let config = Config {
+            agent_owned_branches: strs(&["agent/*"]),
+            ..Config::default()
+        };
+        assert!(check_force_policy(&config, &strs(&["refs/heads/agent/x"])).is_ok());
+        let denied = check_force_policy(&config, &strs(&["refs/heads/main"])).unwrap_err();
+        assert!(denied.contains("branch 'main'"), "{denied}");
+    }
+
+    #[test]
+    fn test_tracking_ref_follows_fetch_refspecs() {
+        let refspecs = strs(&[
+            "+refs/heads/*:refs/remotes/origin/*",
+            "refs/heads/exact:refs/remotes/origin/renamed",
+        ]);
+        assert_eq!(
+            tracking_ref_for(&refspecs, "refs/heads/agent/x").as_deref(),
+            Some("refs/remotes/origin/agent/x")
+        );
+        assert_eq!(
+            tracking_ref_for(&refspecs[1..], "refs/heads/exact").as_deref(),
+            Some("refs/remotes/origin/renamed")
+        );
+        assert_eq!(tracking_ref_for(&refspecs[1..], "refs/heads/other"), None);
+    }
+
+    #[test]
+    fn force_with_lease_uses_host_observed_tracking_ref() {
+        let root = test_root("force-lease");
+        let (repository, remote) = initialize_repository(&root);
+        let origin = remote.to_str().unwrap();
+        run_git(&repository, &["checkout", "-q", "-b", "agent/work"]);
+        run_git(&repository, &["push", "origin", "main", "agent/work"]);
+        let config = Config {
+            agent_owned_branches: strs(&["agent/*"]),
+            ..Config::default()
+        };
+        let pinned = pin_repository(&repository, &repository).unwrap();
+        let force_push = |observed: &BTreeMap<String, String>| {
+            let audit = local_config_audit(&pinned).unwrap();
+            prepare_push(
+                &pinned,
+                &audit,
+                origin,
+                &Push::ForceWithLease,
+                &config,
+                observed,
+            )
+        };
+
+        let unobserved = force_push(&BTreeMap::new()).err().unwrap();
+        assert!(unobserved.contains("run 'git fetch' first"), "{unobserved}");
+
+        let fetched = handle_request(
+            fetch_request(),
+            &single_mode(&repository, &remote),
+            &test_log(&root),
+        );
+        assert_eq!(fetched.exit_code, 0, "{}", fetched.stderr);
+        let observed = observed_tracking(&repository);
+        let base = git_stdout(&repository, &["rev-parse", "HEAD"]);
+        assert_eq!(observed.get("refs/remotes/origin/agent/work"), Some(&base));
+
+        fs::write(repository.join("file.txt"), "rewritten\n").unwrap();
+        run_git(
+            &repository,
+            &["commit", "-q", "--amend", "-am", "rewritten"],
+        );
+        let mut prepared = force_push(&observed).unwrap();
+        assert_eq!(prepared.refs, strs(&["refs/heads/agent/work"]));
+        assert!(check_force_policy(&config, &prepared.refs).is_ok());
+        let output = prepared.command.output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let rewritten = git_stdout(&repository, &["rev-parse", "HEAD"]);
+        assert_eq!(
+            rewritten,
+            git_stdout(&remote, &["rev-parse", "refs/heads/agent/work"])
+        );
+
+        // The lease is still the pre-rewrite value, so a second force push
+        // must not clobber the remote.
+        run_git(&repository, &["commit", "-q", "--amend", "-m", "again"]);
+        let mut stale = force_push(&observed).unwrap();
+        assert!(!stale.command.output().unwrap().status.success());
+        assert_eq!(
+            rewritten,
+            git_stdout(&remote, &["rev-parse", "refs/heads/agent/work"])
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn push_resolves_destination_refs_from_push_default() {
+        let root = test_root("resolve-branch");
+        let (repository, remote) = initialize_repository(&root);
+        let origin = remote.to_str().unwrap();
+        let prepare = |push: &Push, config: &Config| {
+            let pinned = pin_repository(&repository, &repository).unwrap();
+            let audit = local_config_audit(&pinned).unwrap();
+            prepare_push(&pinned, &audit, origin, push, config, &BTreeMap::new())
+                .map(|prepared| prepared.refs)
+        };
+
+        assert_eq!(
+            prepare(&Push::Branch, &Config::default()).unwrap(),
+            strs(&["refs/heads/main"])
+        );
+        run_git(&repository, &["checkout", "-q", "-b", "agent/work"]);
+        run_git(&repository, &["config", "push.default", "upstream"]);
+        assert!(
+            prepare(&Push::Branch, &Config::default())
+                .unwrap_err()
+                .contains("no upstream branch")
+        );
+        run_git(
+            &repository,
+            &["config", "branch.agent/work.remote", "origin"],
+        );
+        run_git(
+            &repository,
+            &[
+                "config",
+                "branch.agent/work.merge",
+                "refs/heads/agent/other",
+            ],
+        );
+        assert_eq!(
+            prepare(&Push::Branch, &Config::default()).unwrap(),
+            strs(&["refs/heads/agent/other"])
+        );
+        run_git(&repository, &["config", "push.default", "matching"]);
+        assert!(
+            prepare(&Push::Branch, &Config::default())
+                .unwrap_err()
+                .contains("push.default=matching")
+        );
+        run_git(&repository, &["config", "--unset", "push.default"]);
+        run_git(&repository, &["checkout", "-q", "--detach"]);
+        assert!(
+            prepare(&Push::Branch, &Config::default())
+                .unwrap_err()
+                .contains("detached")
+        );
+
+        assert!(prepare(&Push::Tags, &Config::default()).unwrap().is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tag_patterns_push_only_new_tags_explicitly() {
+        let root = test_root("resolve-tags");
+        let (repository, remote) = initialize_repository(&root);
+        run_git(&repository, &["tag", "v1"]);
+        run_git(&repository, &["push", "origin", "main", "v1"]);
+        run_git(&repository, &["tag", "agent-1"]);
+        let config = policy(&[], &[], Some(&["agent-*"]));
+
+        let pinned = pin_repository(&repository, &repository).unwrap();
+        let audit = local_config_audit(&pinned).unwrap();
+        let mut prepared = prepare_push(
+            &pinned,
+            &audit,
+            remote.to_str().unwrap(),
+            &Push::Tags,
+            &config,
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(prepared.refs, strs(&["refs/tags/agent-1"]));
+        assert!(check_push_policy(&config, &prepared.refs).is_ok());
+        let output = prepared.command.output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            git_stdout(&repository, &["rev-parse", "refs/tags/agent-1"]),
+            git_stdout(&remote, &["rev-parse", "refs/tags/agent-1"])
+        );
+
+        let prepared = prepare_push(
+            &pinned,
+            &audit,
+            remote.to_str().unwrap(),
+            &Push::Tags,
+            &config,
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert!(prepared.refs.is_empty());
+
+        run_git(&repository, &["tag", "v2"]);
+        let prepared = prepare_push(
+            &pinned,
+            &audit,
+            remote.to_str().unwrap(),
+            &Push::Tags,
+            &config,
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(prepared.refs, strs(&["refs/tags/v2"]));
+        assert!(
+            check_push_policy(&config, &prepared.refs)
+                .unwrap_err()
+                .contains("tag 'v2'")
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // ── Content checks ─────────────────────────────────────────────
+
+    #[test]
+    fn test_high_entropy_assignment() {
+        assert!(high_entropy_assignment(
+            "API_TOKEN = \"x8Fq2LmZ9vKp3RtY7wNb4Hc\""
+        ));
+        assert!(high_entropy_assignment("password: Zq8x2Lm9Vk3Rt7Wn4Hc6Pj"));
+        // Secret-like name, but a readable value
+        assert!(!high_entropy_assignment(
+            "password = \"correct-horse-battery-staple\""
+        ));
+        // Random value without a secret-like name, e.g. a lockfile hash
+        assert!(!high_entropy_assignment(
+            "\"integrity\": \"sha512-x8Fq2LmZ9vKp3RtY7wNb4Hc6Pj1Qs5\""
+        ));
+        assert!(!high_entropy_assignment("token"));
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy("aaaa"), 0.0);
+        assert_eq!(shannon_entropy("abab"), 1.0);
+        assert!(shannon_entropy("x8Fq2LmZ9vKp3RtY7wNb") > 4.0);
+    }
+
+    #[test]
+    fn pushed_secret_is_refused_and_reported() {
+        let root = test_root("content-secret");
+        let (repository, remote) = initialize_repository(&root);
+        run_git(&repository, &["config", "push.default", "current"]);
+        let mode = single_mode(&repository, &remote);
+        let log = test_log(&root);
+        assert_eq!(
+            handle_request(push_request(false), &mode, &log).exit_code,
+            0
+        );
+        let pushed = git_stdout(&remote, &["rev-parse", "refs/heads/main"]);
+
+        let token = format!("ghp_{}", "a1".repeat(18));
+        fs::write(
+            repository.join("creds.txt"),
+            format!("# deploy\nGITHUB_TOKEN={token}\n"),
+        )
+        .unwrap();
+        run_git(&repository, &["add", "creds.txt"]);
+        run_git(&repository, &["commit", "-q", "-m", "add creds"]);
+        let refused = handle_request(push_request(false), &mode, &log);
+        assert_eq!(refused.exit_code, 1);
+        assert!(
+            refused
+                .stderr
+                .contains("secret: creds.txt:2 (known secret format)"),
+            "{}",
+            refused.stderr
+        );
+        assert!(!refused.stderr.contains(&token));
+        assert_eq!(
+            pushed,
+            git_stdout(&remote, &["rev-parse", "refs/heads/main"])
+        );
+
+        // Replacing the offending commit does not hide it from inspection
+        run_git(&repository, &["replace", "HEAD", "HEAD~1"]);
+        let replaced = handle_request(push_request(false), &mode, &log);
+        assert_eq!(replaced.exit_code, 1);
+        assert!(
+            replaced.stderr.contains("creds.txt:2"),
+            "{}",
+            replaced.stderr
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn content_checks_cover_only_unpublished_commits() {
+        let root = test_root("content-range");
+        let (repository, remote) = initialize_repository(&root);
+        fs::write(repository.join("big.bin"), vec![b'x'; 4096]).unwrap();
+        run_git(&repository, &["add", "big.bin"]);
+        run_git(&repository, &["commit", "-q", "-m", "big"]);
+        run_git(&repository, &["push", "-q", "origin", "main"]);
+
+        run_git(
+            &repository,
+            &["config", "user.email", "intruder@example.invalid"],
+        );
+        fs::write(repository.join("large.bin"), vec![b'y'; 2048]).unwrap();
+        fs::write(repository.join("small.txt"), "ok\n").unwrap();
+        run_git(&repository, &["add", "large.bin", "small.txt"]);
+        run_git(&repository, &["commit", "-q", "-m", "more"]);
+
+        let config = Config {
+            max_file_size: Some(1024),
+            allowed_author_emails: strs(&["*@example.com", "test@example.invalid"]),
+            ..Config::default()
+        };
+        let pinned = pin_repository(&repository, &repository).unwrap();
+        let audit = local_config_audit(&pinned).unwrap();
+        let prepared = prepare_push(
+            &pinned,
+            &audit,
+            remote.to_str().unwrap(),
+            &Push::Branch,
+            &config,
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(prepared.findings.len(), 2, "{:?}", prepared.findings);
+        assert!(prepared.findings[0].starts_with("author: commit "));
+        assert!(prepared.findings[0].contains("'intruder@example.invalid'"));
+        assert_eq!(
+            prepared.findings[1],
+            "size: large.bin is 2048 bytes (max_file_size 1024)"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_lfs_pointer() {
+        let oid = "2b73cbb34ed10c11b2e0b424f602ea3d826076cdc4ce3e8141809aadb6cd438e";
+        let pointer =
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize 19\n");
+        assert_eq!(
+            parse_lfs_pointer(pointer.as_bytes()),
+            Some(LfsPointer {
+                oid: oid.to_string(),
+                size: 19
+            })
+        );
+        assert!(parse_lfs_pointer(b"plain text\n").is_none());
+        assert!(
+            parse_lfs_pointer(
+                b"version https://git-lfs.github.com/spec/v1\noid sha256:../../etc\nsize 1\n"
+            )
+            .is_none()
+        );
+        let unsized_pointer =
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\n");
+        assert!(parse_lfs_pointer(unsized_pointer.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn lfs_mode_stages_referenced_objects_from_the_workspace() {
+        let root = test_root("lfs");
+        let (repository, remote) = initialize_repository(&root);
+        let oid = "2b73cbb34ed10c11b2e0b424f602ea3d826076cdc4ce3e8141809aadb6cd438e";
+        fs::write(
+            repository.join("asset.bin"),
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize 19\n"),
+        )
+        .unwrap();
+        run_git(&repository, &["add", "asset.bin"]);
+        run_git(&repository, &["commit", "-q", "-m", "asset"]);
+
+        let config = Config {
+            scan_secrets: false,
+            lfs: true,
+            ..Config::default()
+        };
+        let prepare = || {
+            let pinned = pin_repository(&repository, &repository).unwrap();
+            let audit = local_config_audit(&pinned).unwrap();
+            prepare_push(
+                &pinned,
+                &audit,
+                remote.to_str().unwrap(),
+                &Push::Branch,
+                &config,
+                &BTreeMap::new(),
+            )
+        };
+        let missing = prepare().err().unwrap();
+        assert!(missing.contains("asset.bin"), "{missing}");
+
+        let object = repository.join(".git/lfs/objects/2b/73").join(oid);
+        fs::create_dir_all(object.parent().unwrap()).unwrap();
+        fs::write(&object, "truncated\n").unwrap();
+        let wrong_size = prepare().err().unwrap();
+        assert!(wrong_size.contains("not the 19 bytes"), "{wrong_size}");
+
+        fs::remove_file(&object).unwrap();
+        fs::write(root.join("host-file"), "large file content\n").unwrap();
+        symlink(root.join("host-file"), &object).unwrap();
+        let escaped = prepare().err().unwrap();
+        assert!(escaped.contains("escapes"), "{escaped}");
+
+        fs::remove_file(&object).unwrap();
+        fs::write(&object, "large file content\n").unwrap();
+        let prepared = prepare().unwrap();
+        let upload = prepared.lfs_upload.as_ref().expect("pointer is staged");
+        assert_eq!((upload.objects, upload.bytes), (1, 19));
+        let args: Vec<String> = upload
+            .command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let [.., url, lfs, push, object_id, remote_name, object] = args.as_slice() else {
+            panic!("unexpected upload command: {args:?}");
+        };
+        assert_eq!(
+            [lfs, push, object_id, object],
+            ["lfs", "push", "--object-id", oid]
+        );
+        assert_eq!(
+            *url,
+            format!("remote.{remote_name}.url={}", remote.display())
+        );
+        assert_eq!(
+            fs::read_to_string(upload._store.path.join("lfs/objects/2b/73").join(oid)).unwrap(),
+            "large file content\n"
+        );
+        drop(prepared);
+
+        fs::write(
+            repository.join(".lfsconfig"),
+            "[lfs]\n\turl = https://elsewhere.example/lfs\n",
+        )
+        .unwrap();
+        let redirected = prepare().err().unwrap();
+        assert!(
+            redirected.contains(".lfsconfig sets lfs.url"),
+            "{redirected}"
+        );
+        fs::remove_file(repository.join(".lfsconfig")).unwrap();
+
+        // Objects already on the origin are not uploaded again
+        run_git(&repository, &["push", "-q", "origin", "main"]);
+        assert!(prepare().unwrap().lfs_upload.is_none());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_findings_report_is_capped() {
+        let findings: Vec<String> = (0..25).map(|i| format!("size: f{i}")).collect();
+        let report = format_findings(&findings);
+        assert_eq!(report.lines().count(), MAX_REPORTED_FINDINGS + 1);
+        assert!(report.ends_with("  ... and 5 more\n"));
+    }
+
     // ── Local config audit ─────────────────────────────────────────
 
     #[test]
@@ -2026,6 +4266,10 @@ mod tests {
         assert!(is_denied_key("push.gpgSign"));
         assert!(is_denied_key("push.recurseSubmodules"));
         assert!(is_denied_key("gpg.program"));
+        assert!(is_denied_key("lfs.url"));
+        assert!(is_denied_key("lfs.pushurl"));
+        assert!(is_denied_key("remote.origin.lfsurl"));
+        assert!(is_denied_key("remote.origin.lfsPushUrl"));
     }
 
     #[test]
@@ -2041,6 +4285,7 @@ mod tests {
         assert!(!is_denied_key("pull.rebase"));
         assert!(!is_denied_key("push.default"));
         assert!(!is_denied_key("push.autoSetupRemote"));
+        assert!(!is_denied_key("lfs.repositoryformatversion"));
     }
 
     #[test]
@@ -0,0 +1,602 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use crate::gh_proxy::{CommandDef, check_flags, format_flags, is_help_flag};
+use crate::logging::log_line;
+use crate::{proxy_log, proxy_socket, redact};
+
+#[derive(Deserialize)]
+struct Request {
+    args: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Response {
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+/// Host-side settings from `~/.claude-sandbox/projects/<project>/glab-proxy.json`.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct Config {
+    /// The proxy is only started for projects that opt in.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Self-hosted GitLab hostname; passed to every host `glab` as `GITLAB_HOST`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+const COMMANDS: &[CommandDef] = &[
+    // ── Read commands ──────────────────────────────────────────────
+    CommandDef {
+        group: "mr",
+        subcommand: "list",
+        is_write: false,
+        allowed_flags: &[
+            "--all",
+            "-A",
+            "--assignee",
+            "-a",
+            "--author",
+            "--closed",
+            "-c",
+            "--draft",
+            "-d",
+            "--label",
+            "-l",
+            "--merged",
+            "-M",
+            "--milestone",
+            "-m",
+            "--not-label",
+            "--output",
+            "-F",
+            "--page",
+            "-p",
+            "--per-page",
+            "-P",
+            "--reviewer",
+            "-r",
+            "--search",
+            "--source-branch",
+            "-s",
+            "--target-branch",
+            "-t",
+            "--repo",
+            "-R",
+        ],
+    },
+    CommandDef {
+        group: "mr",
+        subcommand: "view",
+        is_write: false,
+        allowed_flags: &[
+            "--comments",
+            "-c",
+            "--output",
+            "-F",
+            "--page",
+            "-p",
+            "--per-page",
+            "-P",
+            "--system-logs",
+            "-s",
+            "--repo",
+            "-R",
+        ],
+    },
+    CommandDef {
+        group: "mr",
+        subcommand: "diff",
+        is_write: false,
+        allowed_flags: &["--color", "--raw", "--repo", "-R"],
+    },
+    CommandDef {
+        group: "issue",
+        subcommand: "list",
+        is_write: false,
+        allowed_flags: &[
+            "--all",
+            "-A",
+            "--assignee",
+            "-a",
+            "--author",
+            "--closed",
+            "-c",
+            "--confidential",
+            "-C",
+            "--label",
+            "-l",
+            "--milestone",
+            "-m",
+            "--not-label",
+            "--output",
+            "-F",
+            "--page",
+            "-p",
+            "--per-page",
+            "-P",
+            "--search",
+            "--repo",
+            "-R",
+        ],
+    },
+    CommandDef {
+        group: "issue",
+        subcommand: "view",
+        is_write: false,
+        allowed_flags: &[
+            "--comments",
+            "-c",
+            "--output",
+            "-F",
+            "--page",
+            "-p",
+            "--per-page",
+            "-P",
+            "--system-logs",
+            "-s",
+            "--repo",
+            "-R",
+        ],
+    },
+    CommandDef {
+        group: "ci",
+        subcommand: "status",
+        is_write: false,
+        allowed_flags: &["--branch", "-b", "--compact", "-c", "--repo", "-R"],
+    },
+    // ── Write commands (no --repo/-R, nothing that pushes or reads files) ──
+    CommandDef {
+        group: "mr",
+        subcommand: "create",
+        is_write: true,
+        allowed_flags: &[
+            "--title",
+            "-t",
+            "--description",
+            "-d",
+            "--source-branch",
+            "-s",
+            "--target-branch",
+            "-b",
+            "--draft",
+            "--label",
+            "-l",
+            "--assignee",
+            "-a",
+            "--reviewer",
+            "--milestone",
+            "-m",
+            "--related-issue",
+            "-i",
+            "--remove-source-branch",
+            "--squash-before-merge",
+            "--allow-collaboration",
+            "--yes",
+            "-y",
+        ],
+    },
+    CommandDef {
+        group: "mr",
+        subcommand: "note",
+        is_write: true,
+        allowed_flags: &["--message", "-m", "--unique"],
+    },
+    CommandDef {
+        group: "issue",
+        subcommand: "create",
+        is_write: true,
+        allowed_flags: &[
+            "--title",
+            "-t",
+            "--description",
+            "-d",
+            "--label",
+            "-l",
+            "--assignee",
+            "-a",
+            "--milestone",
+            "-m",
+            "--confidential",
+            "-c",
+            "--weight",
+            "-w",
+            "--due-date",
+            "--linked-mr",
+            "--yes",
+            "-y",
+        ],
+    },
+    CommandDef {
+        group: "issue",
+        subcommand: "note",
+        is_write: true,
+        allowed_flags: &["--message", "-m", "--unique"],
+    },
+];
+
+fn find_command(group: &str, subcommand: &str) -> Option<&'static CommandDef> {
+    COMMANDS
+        .iter()
+        .find(|c| c.group == group && c.subcommand == subcommand)
+}
+
+fn glab_command(config: &Config) -> Command {
+    let mut command = Command::new("glab");
+    if let Some(host) = &config.host {
+        command.env("GITLAB_HOST", host);
+    }
+    command
+}
+
+// ── Help text generation (derived from COMMANDS) ──────────────────────
+
+fn help_toplevel() -> String {
+    let mut groups: Vec<&str> = Vec::new();
+    for cmd in COMMANDS {
+        if !groups.contains(&cmd.group) {
+            groups.push(cmd.group);
+        }
+    }
+
+    let mut out =
+        String::from("glab - GitLab CLI (proxy, restricted subset)\n\nAvailable command groups:\n");
+    for group in &groups {
+        let subs: Vec<&str> = COMMANDS
+            .iter()
+            .filter(|c| c.group == *group)
+            .map(|c| c.subcommand)
+            .collect();
+        out.push_str(&format!("  {:12} {}\n", group, subs.join(", ")));
+    }
+    out.push_str("\nRun 'glab <command> -h' for more information about a command.\n");
+    out.push_str(
+        "Note: This is a sandboxed proxy. Only the commands listed above are available.\n",
+    );
+    out
+}
+
+fn help_group(group: &str) -> Option<String> {
+    let cmds: Vec<&CommandDef> = COMMANDS.iter().filter(|c| c.group == group).collect();
+    if cmds.is_empty() {
+        return None;
+    }
+
+    let mut out = format!("glab {} - available subcommands:\n\n", group);
+    for cmd in &cmds {
+        let rw = if cmd.is_write { " (write)" } else { "" };
+        out.push_str(&format!("  {:12}{}\n", cmd.subcommand, rw));
+    }
+    out.push_str(&format!(
+        "\nRun 'glab {} <subcommand> -h' for more information.\n",
+        group
+    ));
+    Some(out)
+}
+
+fn help_command(group: &str, subcommand: &str) -> Option<String> {
+    let cmd = find_command(group, subcommand)?;
+
+    let rw = if cmd.is_write {
+        " (write — workspace repo only, no -R/--repo)"
+    } else {
+        " (read)"
+    };
+    let mut out = format!("glab {} {}{}\n\nAllowed flags:\n", group, subcommand, rw);
+    for line in format_flags(cmd.allowed_flags) {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// Check if args represent a help request and return help text if so.
+fn maybe_help(args: &[String]) -> Option<String> {
+    if args.is_empty() {
+        return Some(help_toplevel());
+    }
+
+    if args.len() == 1 && (is_help_flag(&args[0]) || args[0] == "help") {
+        return Some(help_toplevel());
+    }
+
+    if args.len() == 2 && is_help_flag(&args[1]) {
+        return help_group(&args[0]).or_else(|| Some(help_toplevel()));
+    }
+
+    if args.len() >= 2 && args[2..].iter().any(|a| is_help_flag(a)) {
+        return help_command(&args[0], &args[1]);
+    }
+
+    None
+}
+
+fn reject_reason(args: &[String]) -> Option<String> {
+    if args.len() == 1 && args[0] == "--version" {
+        return None;
+    }
+
+    if args.len() < 2 {
+        return Some(format!("command not allowed: glab {}", args.join(" ")));
+    }
+
+    let group = args[0].as_str();
+    let subcommand = args[1].as_str();
+
+    let cmd = match find_command(group, subcommand) {
+        Some(c) => c,
+        None => {
+            return Some(format!(
+                "command not allowed: glab {} {}",
+                group, subcommand
+            ));
+        }
+    };
+
+    if let Err(flag) = check_flags(args, cmd.allowed_flags) {
+        return Some(format!(
+            "flag not allowed for glab {} {}: {}",
+            group, subcommand, flag
+        ));
+    }
+
+    None
+}
+
+/// Handle one request. `command` is the host `glab` invocation to run
+/// allowed requests with (see [`glab_command`]).
+fn handle_request(req: Request, log: &Arc<Mutex<File>>, mut command: Command) -> Response {
+    let cmd_str = req.args.join(" ");
+
+    if let Some(help_text) = maybe_help(&req.args) {
+        log_line(log, &format!("HELP    glab {}", cmd_str));
+        return Response {
+            exit_code: 0,
+            stdout: help_text,
+            stderr: String::new(),
+        };
+    }
+
+    if let Some(reason) = reject_reason(&req.args) {
+        log_line(log, &format!("DENIED  glab {} ({})", cmd_str, reason));
+        return Response {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: format!("glab-proxy: {}", reason),
+        };
+    }
+
+    log_line(log, &format!("ALLOWED glab {}", cmd_str));
+
+    match command.args(&req.args).output() {
+        Ok(output) => {
+            let exit_code = output.status.code().unwrap_or(1);
+            log_line(log, &format!("EXIT    glab {} -> {}", cmd_str, exit_code));
+            Response {
+                exit_code,
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+        }
+        Err(e) => {
+            log_line(log, &format!("ERROR   glab {} ({})", cmd_str, e));
+            Response {
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: format!("glab-proxy: failed to execute glab: {}", e),
+            }
+        }
+    }
+}
+
+pub fn run(socket_path: &str, log_path: &Path, config: Config) {
+    let path = Path::new(socket_path);
+    let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
+        eprintln!(
+            "glab-proxy: failed to open log {}: {}",
+            log_path.display(),
+            e
+        );
+        std::process::exit(1);
+    });
+    let log = Arc::new(Mutex::new(log_file));
+
+    let bound = proxy_socket::bind(path).unwrap_or_else(|e| {
+        eprintln!("glab-proxy: failed to bind {}: {}", socket_path, e);
+        std::process::exit(1);
+    });
+    log_line(&log, &format!("listening on {}", socket_path));
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    proxy_socket::serve_json(
+        bound.listener,
+        Arc::clone(&log),
+        1_048_576,
+        move |req: Request| {
+            let label = format!("glab {}", req.args.join(" "));
+            let mut response = handle_request(req, &log, glab_command(&config));
+            redact::response_fields(
+                &log,
+                &label,
+                &mut [&mut response.stdout, &mut response.stderr],
+            );
+            response
+        },
+        |e| Response {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: format!("glab-proxy: invalid request: {}", e),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    fn strs(s: &[&str]) -> Vec<String> {
+        s.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn test_root(label: &str) -> PathBuf {
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "claude-sandbox-glab-{label}-{}-{nonce}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn test_log(root: &Path) -> Arc<Mutex<File>> {
+        Arc::new(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(root.join("proxy.log"))
+                .unwrap(),
+        ))
+    }
+
+    /// Install a fake `glab` that records its args and `GITLAB_HOST`.
+    fn fake_glab(root: &Path) -> PathBuf {
+        let bin = root.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let script = bin.join("glab");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$*\" > '{}'\necho \"host=$GITLAB_HOST\"\nexit 3\n",
+                root.join("invoked").display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        bin
+    }
+
+    fn run_fake(root: &Path, config: &Config, args: &[&str]) -> Response {
+        let mut command = glab_command(config);
+        command.env("PATH", fake_glab(root));
+        handle_request(Request { args: strs(args) }, &test_log(root), command)
+    }
+
+    #[test]
+    fn test_read_commands_allowed() {
+        assert!(reject_reason(&strs(&["--version"])).is_none());
+        assert!(reject_reason(&strs(&["mr", "list", "--draft", "-F", "json"])).is_none());
+        assert!(reject_reason(&strs(&["mr", "view", "12", "--comments"])).is_none());
+        assert!(reject_reason(&strs(&["mr", "diff", "12", "--raw"])).is_none());
+        assert!(reject_reason(&strs(&["issue", "list", "--label=bug"])).is_none());
+        assert!(reject_reason(&strs(&["issue", "view", "7", "-R", "group/repo"])).is_none());
+        assert!(reject_reason(&strs(&["ci", "status", "--branch", "main"])).is_none());
+    }
+
+    #[test]
+    fn test_write_commands_allowed() {
+        assert!(
+            reject_reason(&strs(&[
+                "mr",
+                "create",
+                "--title",
+                "t",
+                "--description",
+                "d",
+                "--yes"
+            ]))
+            .is_none()
+        );
+        assert!(reject_reason(&strs(&["mr", "note", "12", "-m", "looks good"])).is_none());
+        assert!(reject_reason(&strs(&["issue", "create", "-t", "bug", "-y"])).is_none());
+        assert!(reject_reason(&strs(&["issue", "note", "7", "--message", "x"])).is_none());
+    }
+
+    #[test]
+    fn test_write_commands_block_repo_and_push() {
+        assert!(reject_reason(&strs(&["mr", "create", "-R", "other/repo"])).is_some());
+        assert!(reject_reason(&strs(&["mr", "note", "1", "--repo=other/repo"])).is_some());
+        assert!(reject_reason(&strs(&["issue", "create", "--repo", "other/repo"])).is_some());
+        let r = reject_reason(&strs(&["mr", "create", "--push"])).unwrap();
+        assert!(r.contains("flag not allowed"));
+        assert!(reject_reason(&strs(&["mr", "create", "--create-source-branch"])).is_some());
+        // --fill pushes the branch with the host's credentials
+        for fill in ["--fill", "-f", "--fill-commit-body"] {
+            let r = reject_reason(&strs(&["mr", "create", fill])).unwrap();
+            assert!(r.contains("flag not allowed"), "{fill}");
+        }
+    }
+
+    #[test]
+    fn test_disallowed_commands() {
+        assert!(reject_reason(&strs(&["mr", "merge", "12"])).is_some());
+        assert!(reject_reason(&strs(&["mr", "approve", "12"])).is_some());
+        assert!(reject_reason(&strs(&["api", "projects"])).is_some());
+        assert!(reject_reason(&strs(&["auth", "status", "--show-token"])).is_some());
+        assert!(reject_reason(&strs(&["repo", "delete"])).is_some());
+        assert!(reject_reason(&strs(&["ci", "run"])).is_some());
+        assert!(reject_reason(&strs(&["mr"])).is_some());
+        assert!(reject_reason(&[]).is_some());
+    }
+
+    #[test]
+    fn test_help() {
+        let h = maybe_help(&[]).unwrap();
+        assert!(h.contains("mr"));
+        assert!(h.contains("ci"));
+        let h = maybe_help(&strs(&["mr", "--help"])).unwrap();
+        assert!(h.contains("create"));
+        assert!(h.contains("(write)"));
+        let h = maybe_help(&strs(&["mr", "create", "-h"])).unwrap();
+        assert!(h.contains("--title"));
+        assert!(h.contains("workspace repo only"));
+        assert!(maybe_help(&strs(&["mr", "list"])).is_none());
+    }
+
+    #[test]
+    fn test_allowed_request_runs_glab_on_path() {
+        let root = test_root("allowed");
+        let config = Config {
+            enabled: true,
+            host: Some("gitlab.example.com".to_string()),
+        };
+        let r = run_fake(&root, &config, &["mr", "view", "12", "--comments"]);
+        assert_eq!(r.exit_code, 3);
+        assert_eq!(r.stdout, "host=gitlab.example.com\n");
+        assert_eq!(
+            fs::read_to_string(root.join("invoked")).unwrap(),
+            "mr view 12 --comments\n"
+        );
+        let log = fs::read_to_string(root.join("proxy.log")).unwrap();
+        assert!(log.contains("ALLOWED glab mr view 12 --comments"));
+        assert!(log.contains("EXIT    glab mr view 12 --comments -> 3"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_denied_request_does_not_run_glab() {
+        let root = test_root("denied");
+        let r = run_fake(&root, &Config::default(), &["mr", "merge", "12"]);
+        assert_eq!(r.exit_code, 1);
+        assert!(r.stderr.contains("command not allowed: glab mr merge"));
+        assert!(!root.join("invoked").exists());
+        let log = fs::read_to_string(root.join("proxy.log")).unwrap();
+        assert!(log.contains("DENIED  glab mr merge 12"));
+
+        let r = run_fake(&root, &Config::default(), &["mr", "create", "-h"]);
+        assert_eq!(r.exit_code, 0);
+        assert!(!root.join("invoked").exists());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -49,7 +49,10 @@ pub fn timestamp() -> String {
     )
 }
 
+/// Append a timestamped line to a proxy log. Secrets in `message` are
+/// redacted before it is written.
 pub fn log_line(log: &Arc<Mutex<File>>, message: &str) {
+    let (message, _) = crate::redact::global().redact(message);
     if let Ok(mut f) = log.lock() {
         let line = format!("{} {}\n", timestamp(), message);
         let _ = f.write_all(line.as_bytes());
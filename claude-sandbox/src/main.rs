@@ -1,11 +1,19 @@
+mod approvals;
+mod checkpoint;
 mod clipboard_proxy;
 mod gh_proxy;
 mod git_proxy;
+mod glab_proxy;
 mod logging;
 mod managed_push;
 mod proxy_log;
 mod proxy_socket;
+mod pty;
+mod redact;
+mod signing_proxy;
+mod ssh_agent_proxy;
 mod ssh_proxy;
+mod ssh_transcript;
 mod t3_admin;
 
 use clap::{Parser, Subcommand};
@@ -17,7 +25,7 @@ use std::collections::HashMap;
 use std::env;
 use std::fs::{self, DirBuilder, File, Permissions};
 use std::hash::{Hash, Hasher};
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::net::TcpListener;
 use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
 use std::os::unix::net::UnixStream;
@@ -36,9 +44,19 @@ const IMAGE: &str = "ghcr.io/nsg/claude-sandbox:latest";
 const GH_PROXY_SUBDIR: &str = ".claude-sandbox";
 const GH_PROXY_SOCKET_NAME: &str = "gh-proxy.sock";
 const GIT_PROXY_SOCKET_NAME: &str = "git-proxy.sock";
+const GIT_PROXY_CONFIG_FILE: &str = "git-proxy.json";
 const CLIPBOARD_PROXY_SOCKET_NAME: &str = "clipboard-proxy.sock";
+const CLIPBOARD_CONFIG_FILE: &str = "clipboard.json";
+const SIGNING_PROXY_SOCKET_NAME: &str = "signing-proxy.sock";
+const SIGNING_CONFIG_FILE: &str = "signing.json";
 const SSH_PROXY_SOCKET_NAME: &str = "ssh-proxy.sock";
 const SSH_PROXY_CONFIG_FILE: &str = "ssh-proxy.json";
+const SSH_AGENT_PROXY_SOCKET_NAME: &str = "ssh-agent.sock";
+const SSH_AGENT_CONFIG_FILE: &str = "ssh-agent.json";
+const GH_PROXY_CONFIG_FILE: &str = "gh-proxy.json";
+const GLAB_PROXY_SOCKET_NAME: &str = "glab-proxy.sock";
+const GLAB_PROXY_CONFIG_FILE: &str = "glab-proxy.json";
+const REDACTION_CONFIG_FILE: &str = "redaction.json";
 const SSHD_CONFIG_FILE: &str = "sshd.json";
 const CONTAINER_PROXY_RUNTIME_DIR: &str = "/run/claude-sandbox";
 // Must match the default session name in config/wrap.sh.
@@ -107,13 +125,19 @@ struct Cli {
     #[arg(long)]
     no_audio: bool,
 
-    /// Allow the agent to run `git push` / `git push --tags`, executed on the host
+    /// Allow the agent to run `git push` / `git push --tags` / `git push --force-with-lease`
+    /// and `git fetch [origin]`, executed on the host
     #[arg(long = "allow-push")]
     allow_push: bool,
 
-    /// Let the T3 admin portal approve repositories for host-side pushes
-    #[arg(long = "t3-managed-push", conflicts_with = "allow_push")]
-    t3_managed_push: bool,
+    /// Route pushes from any repository under the workspace through the host,
+    /// once approved with `claude-sandbox approvals` or the T3 admin portal
+    #[arg(
+        long = "managed-push",
+        alias = "t3-managed-push",
+        conflicts_with = "allow_push"
+    )]
+    managed_push: bool,
 
     /// Enable SSH server in the container
     #[arg(long)]
@@ -127,6 +151,10 @@ struct Cli {
     #[arg(long = "ssh-port")]
     ssh_port: Option<u16>,
 
+    /// Skip the workspace checkpoint saved at launch
+    #[arg(long = "no-checkpoint")]
+    no_checkpoint: bool,
+
     /// Run the command in a named tmux session so keys can be injected
     #[arg(long, global = true)]
     wrap: bool,
@@ -152,6 +180,21 @@ enum Commands {
         /// Persistent log path
         #[arg(long)]
         log: PathBuf,
+        /// Config as JSON string
+        #[arg(long)]
+        config_json: String,
+    },
+    /// Start the glab CLI proxy (internal, spawned for enabled projects)
+    GlabProxy {
+        /// Socket path (absolute)
+        #[arg(long)]
+        socket: String,
+        /// Persistent log path
+        #[arg(long)]
+        log: PathBuf,
+        /// Config as JSON string
+        #[arg(long)]
+        config_json: String,
     },
     /// Start the git push proxy (internal, spawned automatically)
     GitProxy {
@@ -170,6 +213,9 @@ enum Commands {
         /// Host-only managed push state directory
         #[arg(long)]
         state_dir: Option<PathBuf>,
+        /// Push policy as JSON string
+        #[arg(long)]
+        config_json: String,
     },
     /// Start the host-side T3 administration portal (internal)
     T3Admin {
@@ -188,7 +234,7 @@ enum Commands {
         #[arg(long)]
         managed_push: bool,
     },
-    /// Start the clipboard image proxy (internal, spawned automatically)
+    /// Start the clipboard proxy (internal, spawned automatically)
     ClipboardProxy {
         /// Socket path (absolute)
         #[arg(long)]
@@ -196,6 +242,21 @@ enum Commands {
         /// Persistent log path
         #[arg(long)]
         log: PathBuf,
+        /// Config as JSON string
+        #[arg(long)]
+        config_json: String,
+    },
+    /// Start the commit signing proxy (internal, spawned automatically)
+    SigningProxy {
+        /// Socket path (absolute)
+        #[arg(long)]
+        socket: String,
+        /// Persistent log path
+        #[arg(long)]
+        log: PathBuf,
+        /// Signing key config as JSON string
+        #[arg(long)]
+        config_json: String,
     },
     /// Start the SSH proxy (internal, spawned automatically)
     SshProxy {
@@ -209,6 +270,18 @@ enum Commands {
         #[arg(long)]
         config_json: String,
     },
+    /// Start the SSH agent proxy (internal, spawned automatically)
+    SshAgentProxy {
+        /// Socket path (absolute)
+        #[arg(long)]
+        socket: String,
+        /// Persistent log path
+        #[arg(long)]
+        log: PathBuf,
+        /// Config as JSON string
+        #[arg(long)]
+        config_json: String,
+    },
     /// Run a command inside the container
     Run {
         #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
@@ -265,6 +338,93 @@ enum Commands {
     },
     /// List running wrapped terminal sessions
     WrapList,
+    /// Review managed-push requests for this project from a host terminal
+    Approvals {
+        #[command(subcommand)]
+        action: ApprovalsAction,
+    },
+    /// Save, compare and restore checkpoints of the workspace repository
+    Checkpoint {
+        #[command(subcommand)]
+        action: CheckpointAction,
+    },
+    /// Inspect recorded proxy sessions for this project
+    Logs {
+        #[command(subcommand)]
+        target: LogsTarget,
+    },
+}
+
+#[derive(Subcommand)]
+enum ApprovalsAction {
+    /// List pending requests and approved repositories
+    List,
+    /// Approve a pending request by its identifier
+    Approve {
+        id: String,
+        /// once: fetches and the next push; persistent: until revoked;
+        /// push: exactly the previewed ref and commit
+        #[arg(long, default_value = "once", value_parser = ["once", "persistent", "push"])]
+        scope: String,
+        /// Expire the approval after a duration such as 90m, 8h or 2d
+        #[arg(long = "for", value_name = "DURATION")]
+        duration: Option<String>,
+        /// Restrict pushes to branches matching a glob (can be repeated)
+        #[arg(long = "branch", value_name = "PATTERN")]
+        branches: Vec<String>,
+        /// The previewed remote ref, required with --scope push
+        #[arg(long = "ref", value_name = "REF", requires = "oid")]
+        reference: Option<String>,
+        /// The previewed commit, required with --scope push
+        #[arg(long, value_name = "OID", requires = "reference")]
+        oid: Option<String>,
+    },
+    /// Dismiss a pending request
+    Dismiss { id: String },
+    /// Revoke the approval for a workspace-relative repository path
+    Revoke { repository: String },
+}
+
+#[derive(Subcommand)]
+enum CheckpointAction {
+    /// Save the refs, index and uncommitted changes now
+    Create,
+    /// List checkpoints, newest first
+    List,
+    /// Show how refs and the working tree changed since a checkpoint
+    Diff {
+        id: u64,
+        /// Show the full patch instead of a summary
+        #[arg(long)]
+        patch: bool,
+    },
+    /// Put refs, HEAD, the index and the working tree back to a checkpoint
+    Restore {
+        id: u64,
+        /// Do not ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogsTarget {
+    /// List recorded ssh-proxy sessions, or replay one
+    Ssh {
+        /// Session id to replay, as listed or shown in the ssh-proxy log
+        #[arg(long)]
+        session: Option<String>,
+        /// Keep the pauses between output as they were recorded
+        #[arg(long, requires = "session")]
+        timing: bool,
+        /// Print every event, including input, as a timestamped line
+        #[arg(long, requires = "session", conflicts_with = "timing")]
+        events: bool,
+        /// Write the recorded bytes as they are, escape sequences included,
+        /// even to a terminal
+        #[arg(long, requires = "session", conflicts_with = "events")]
+        raw: bool,
+    },
 }
 
 const T3CODE_PORT: u16 = 3773;
@@ -809,41 +969,187 @@ fn start_proxy(name: &str, socket_path: &Path, mut command: Command) -> Result<(
     })
 }
 
+fn load_gh_proxy_config() -> gh_proxy::Config {
+    let path = project_config_path(GH_PROXY_CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Warning: ignoring invalid {}: {}", path.display(), error);
+            gh_proxy::Config::default()
+        }),
+        Err(_) => gh_proxy::Config::default(),
+    }
+}
+
 fn ensure_gh_proxy(runtime_dir: &Path) -> Result<(), String> {
     let socket_path = runtime_dir.join(GH_PROXY_SOCKET_NAME);
+    let config_json = serde_json::to_string(&load_gh_proxy_config())
+        .expect("Failed to serialize gh-proxy config");
     let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
     command
         .arg("gh-proxy")
         .arg("--socket")
         .arg(&socket_path)
         .arg("--log")
-        .arg(proxy_log_path("gh-proxy.log")?);
+        .arg(proxy_log_path("gh-proxy.log")?)
+        .arg("--config-json")
+        .arg(config_json);
     start_proxy("gh-proxy", &socket_path, command)
 }
 
-fn ensure_clipboard_proxy(runtime_dir: &Path) -> Result<(), String> {
+fn load_glab_proxy_config() -> glab_proxy::Config {
+    let path = project_config_path(GLAB_PROXY_CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Warning: ignoring invalid {}: {}", path.display(), error);
+            glab_proxy::Config::default()
+        }),
+        Err(_) => glab_proxy::Config::default(),
+    }
+}
+
+fn ensure_glab_proxy(runtime_dir: &Path, config: &glab_proxy::Config) -> Result<(), String> {
+    let socket_path = runtime_dir.join(GLAB_PROXY_SOCKET_NAME);
+    let config_json = serde_json::to_string(config).expect("Failed to serialize glab-proxy config");
+    let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
+    command
+        .arg("glab-proxy")
+        .arg("--socket")
+        .arg(&socket_path)
+        .arg("--log")
+        .arg(proxy_log_path("glab-proxy.log")?)
+        .arg("--config-json")
+        .arg(config_json);
+    start_proxy("glab-proxy", &socket_path, command)
+}
+
+fn load_clipboard_config() -> clipboard_proxy::Config {
+    let path = project_config_path(CLIPBOARD_CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Warning: ignoring invalid {}: {}", path.display(), error);
+            clipboard_proxy::Config::default()
+        }),
+        Err(_) => clipboard_proxy::Config::default(),
+    }
+}
+
+fn ensure_clipboard_proxy(
+    runtime_dir: &Path,
+    config: &clipboard_proxy::Config,
+) -> Result<(), String> {
     let socket_path = runtime_dir.join(CLIPBOARD_PROXY_SOCKET_NAME);
+    let config_json =
+        serde_json::to_string(config).expect("Failed to serialize clipboard-proxy config");
     let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
     command
         .arg("clipboard-proxy")
         .arg("--socket")
         .arg(&socket_path)
         .arg("--log")
-        .arg(proxy_log_path("clipboard-proxy.log")?);
+        .arg(proxy_log_path("clipboard-proxy.log")?)
+        .arg("--config-json")
+        .arg(config_json);
     start_proxy("clipboard-proxy", &socket_path, command)
 }
 
-fn ensure_git_proxy_single(runtime_dir: &Path, origin_url: &str) -> Result<(), String> {
-    let socket_path = runtime_dir.join(GIT_PROXY_SOCKET_NAME);
+/// Load the host signing key config, if any. Exits on an invalid config so a
+/// broken key setup fails at launch rather than on the first commit.
+fn load_signing_config() -> Option<signing_proxy::Config> {
+    let path = project_config_path(SIGNING_CONFIG_FILE);
+    let contents = fs::read_to_string(&path).ok()?;
+    let mut config: signing_proxy::Config =
+        serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Error: invalid {}: {}", path.display(), error);
+            std::process::exit(1);
+        });
+    if let Some(rest) = config.key.strip_prefix("~/") {
+        config.key = home_dir().join(rest).to_string_lossy().into_owned();
+    }
+    Some(config)
+}
+
+fn ensure_signing_proxy(runtime_dir: &Path, config: &signing_proxy::Config) -> Result<(), String> {
+    let socket_path = runtime_dir.join(SIGNING_PROXY_SOCKET_NAME);
+    let config_json = serde_json::to_string(config).expect("Failed to serialize signing config");
     let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
     command
-        .arg("git-proxy")
+        .arg("signing-proxy")
+        .arg("--socket")
+        .arg(&socket_path)
+        .arg("--log")
+        .arg(proxy_log_path("signing-proxy.log")?)
+        .arg("--config-json")
+        .arg(config_json);
+    start_proxy("signing-proxy", &socket_path, command)
+}
+
+/// Load the SSH agent key allowlist, if any. Exits on an invalid config so a
+/// missing key file or agent socket fails at launch rather than on first use.
+fn load_ssh_agent_config() -> Option<ssh_agent_proxy::Config> {
+    let path = project_config_path(SSH_AGENT_CONFIG_FILE);
+    let contents = fs::read_to_string(&path).ok()?;
+    let mut config: ssh_agent_proxy::Config =
+        serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Error: invalid {}: {}", path.display(), error);
+            std::process::exit(1);
+        });
+    if let Err(error) = config.resolve(&home_dir(), env::var("SSH_AUTH_SOCK").ok()) {
+        eprintln!("Error: {}: {}", path.display(), error);
+        std::process::exit(1);
+    }
+    Some(config)
+}
+
+fn ensure_ssh_agent_proxy(
+    runtime_dir: &Path,
+    config: &ssh_agent_proxy::Config,
+) -> Result<(), String> {
+    let socket_path = runtime_dir.join(SSH_AGENT_PROXY_SOCKET_NAME);
+    let config_json = serde_json::to_string(config).expect("Failed to serialize ssh agent config");
+    let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
+    command
+        .arg("ssh-agent-proxy")
         .arg("--socket")
         .arg(&socket_path)
         .arg("--log")
+        .arg(proxy_log_path("ssh-agent-proxy.log")?)
+        .arg("--config-json")
+        .arg(config_json);
+    start_proxy("ssh-agent-proxy", &socket_path, command)
+}
+
+/// Load the push policy. Exits on an invalid config so a typo never silently
+/// lifts a branch restriction.
+fn load_git_proxy_config() -> git_proxy::Config {
+    let path = project_config_path(GIT_PROXY_CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Error: invalid {}: {}", path.display(), error);
+            std::process::exit(1);
+        }),
+        Err(_) => git_proxy::Config::default(),
+    }
+}
+
+fn git_proxy_command(socket_path: &Path) -> Result<Command, String> {
+    let config_json = serde_json::to_string(&load_git_proxy_config())
+        .expect("Failed to serialize git-proxy config");
+    let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
+    command
+        .arg("git-proxy")
+        .arg("--socket")
+        .arg(socket_path)
+        .arg("--log")
         .arg(proxy_log_path("git-proxy.log")?)
-        .arg("--origin-url")
-        .arg(origin_url);
+        .arg("--config-json")
+        .arg(config_json);
+    Ok(command)
+}
+
+fn ensure_git_proxy_single(runtime_dir: &Path, origin_url: &str) -> Result<(), String> {
+    let socket_path = runtime_dir.join(GIT_PROXY_SOCKET_NAME);
+    let mut command = git_proxy_command(&socket_path)?;
+    command.arg("--origin-url").arg(origin_url);
     start_proxy("git-proxy", &socket_path, command)
 }
 
@@ -853,13 +1159,8 @@ fn ensure_git_proxy_managed(
     state_dir: &Path,
 ) -> Result<(), String> {
     let socket_path = runtime_dir.join(GIT_PROXY_SOCKET_NAME);
-    let mut command = Command::new(env::current_exe().expect("Could not get executable path"));
+    let mut command = git_proxy_command(&socket_path)?;
     command
-        .arg("git-proxy")
-        .arg("--socket")
-        .arg(&socket_path)
-        .arg("--log")
-        .arg(proxy_log_path("git-proxy.log")?)
         .arg("--workspace-root")
         .arg(workspace_root)
         .arg("--state-dir")
@@ -867,13 +1168,199 @@ fn ensure_git_proxy_managed(
     start_proxy("managed git-proxy", &socket_path, command)
 }
 
-fn ssh_proxy_host_config_path() -> PathBuf {
+/// Host-only managed push state for this project. Exits if it cannot be
+/// created or would land inside the agent-mounted workspace.
+fn prepare_managed_push_state() -> PathBuf {
+    let cwd = env::current_dir().expect("Could not get current directory");
+    let state = managed_push::state_dir(&home_dir(), &project_instance_name(&cwd));
+    let state = managed_push::prepare_state_dir(&state).unwrap_or_else(|error| {
+        eprintln!("Error: could not prepare managed push state: {error}");
+        std::process::exit(1);
+    });
+    let canonical_workspace = fs::canonicalize(&cwd).unwrap_or_else(|error| {
+        eprintln!("Error: could not resolve workspace: {error}");
+        std::process::exit(1);
+    });
+    if state.starts_with(&canonical_workspace) {
+        eprintln!("Error: managed push state must be outside the agent-mounted workspace");
+        std::process::exit(1);
+    }
+    state
+}
+
+fn run_approvals(action: &ApprovalsAction) {
+    let cwd = env::current_dir().expect("Could not get current directory");
+    let state = managed_push::state_dir(&home_dir(), &project_instance_name(&cwd));
+    let result = match action {
+        ApprovalsAction::List => approvals::list(&state, &cwd),
+        ApprovalsAction::Approve {
+            id,
+            scope,
+            duration,
+            branches,
+            reference,
+            oid,
+        } => approvals::parse_scope(scope).and_then(|scope| {
+            let limits = managed_push::ApprovalLimits {
+                duration: duration
+                    .as_deref()
+                    .map(managed_push::parse_duration)
+                    .transpose()?,
+                branches: branches.clone(),
+            };
+            let reviewed = reference
+                .clone()
+                .zip(oid.clone())
+                .map(|(reference, oid)| managed_push::PushTarget { reference, oid });
+            approvals::approve(&state, &cwd, id, scope, reviewed.as_ref(), &limits)
+        }),
+        ApprovalsAction::Dismiss { id } => approvals::dismiss(&state, id),
+        ApprovalsAction::Revoke { repository } => approvals::revoke(&state, repository),
+    };
+    match result {
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(error) => {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Host-only checkpoint state for this project and the repository it
+/// covers. Fails if the state would land inside the agent-mounted
+/// workspace.
+fn checkpoint_target() -> Result<(PathBuf, PathBuf), String> {
+    let root = git_proxy::repository_root()
+        .ok_or("checkpoints need a git repository in the current directory")?;
+    let cwd = env::current_dir().map_err(|error| format!("no current directory: {error}"))?;
+    let state = checkpoint::state_dir(&home_dir(), &project_instance_name(&cwd));
+    let state = managed_push::prepare_state_dir(&state)
+        .map_err(|error| format!("could not prepare checkpoint state: {error}"))?;
+    let canonical_workspace =
+        fs::canonicalize(&cwd).map_err(|error| format!("could not resolve workspace: {error}"))?;
+    if state.starts_with(&canonical_workspace) {
+        return Err("checkpoint state must be outside the agent-mounted workspace".to_string());
+    }
+    Ok((state, root))
+}
+
+/// Checkpoint the workspace repository before the agent starts, so the
+/// session can be undone. Failure only warns: it never blocks a launch.
+fn checkpoint_on_launch(quiet: bool) {
+    if git_proxy::repository_root().is_none() {
+        return;
+    }
+    let created = checkpoint_target().and_then(|(state, root)| {
+        checkpoint::create(&state, &root, "launch").map(|saved| (saved, root))
+    });
+    match created {
+        Ok((saved, root)) if !quiet => eprintln!(
+            "Saved checkpoint {} of {} (undo with 'claude-sandbox checkpoint restore {}')",
+            saved.id,
+            root.display(),
+            saved.id
+        ),
+        Ok(_) => {}
+        Err(error) => eprintln!("Warning: could not checkpoint the workspace: {error}"),
+    }
+}
+
+fn run_checkpoint(action: &CheckpointAction) {
+    let (state, root) = checkpoint_target().unwrap_or_else(|error| {
+        eprintln!("Error: {error}");
+        std::process::exit(1);
+    });
+    let result = match action {
+        CheckpointAction::Create => checkpoint::create(&state, &root, "manual")
+            .map(|saved| format!("Saved checkpoint {}.", saved.id)),
+        CheckpointAction::List => checkpoint::list(&state),
+        CheckpointAction::Diff { id, patch } => checkpoint::diff(&state, &root, *id, *patch),
+        CheckpointAction::Restore { id, yes } => {
+            let prompt = format!(
+                "Restore {} to checkpoint {id}? Refs, HEAD, the index and tracked files are replaced",
+                root.display()
+            );
+            if !yes
+                && !Confirm::new()
+                    .with_prompt(prompt)
+                    .default(false)
+                    .interact()
+                    .unwrap_or(false)
+            {
+                return;
+            }
+            checkpoint::restore(&state, &root, *id)
+        }
+    };
+    match result {
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(error) => {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_logs(target: &LogsTarget) {
+    let result = match target {
+        LogsTarget::Ssh {
+            session,
+            timing,
+            events,
+            raw,
+        } => proxy_log_path("ssh").and_then(|dir| match session {
+            Some(id) => ssh_transcript::replay(
+                &dir,
+                id,
+                *timing,
+                *events,
+                !*raw && (std::io::stdout().is_terminal() || std::io::stderr().is_terminal()),
+                &mut std::io::stdout(),
+                &mut std::io::stderr(),
+            )
+            .map(|_| String::new()),
+            None => ssh_transcript::list(&dir),
+        }),
+    };
+    match result {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(error) => {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Host-side per-project config file under `~/.claude-sandbox/projects/<instance>/`.
+fn project_config_path(file_name: &str) -> PathBuf {
     let cwd = env::current_dir().expect("Could not get current directory");
     let instance = project_instance_name(&cwd);
     home_dir()
         .join(".claude-sandbox/projects")
         .join(instance)
-        .join(SSH_PROXY_CONFIG_FILE)
+        .join(file_name)
+}
+
+/// Load user redaction patterns and install them for this process. Exits on
+/// an invalid config so a typo never silently disables a pattern.
+fn install_redaction() {
+    let path = project_config_path(REDACTION_CONFIG_FILE);
+    let config = match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            eprintln!("Error: invalid {}: {}", path.display(), error);
+            std::process::exit(1);
+        }),
+        Err(_) => redact::Config::default(),
+    };
+    if let Err(error) = redact::install(&config) {
+        eprintln!("Error: {}: {}", path.display(), error);
+        std::process::exit(1);
+    }
+}
+
+fn ssh_proxy_host_config_path() -> PathBuf {
+    project_config_path(SSH_PROXY_CONFIG_FILE)
 }
 
 fn ssh_proxy_workspace_symlink_path() -> PathBuf {
@@ -974,8 +1461,25 @@ fn run_container(
         });
     };
 
+    // Validate redaction patterns up front; every proxy loads them again
+    install_redaction();
     require_proxy(ensure_gh_proxy(&proxy_runtime_dir));
-    require_proxy(ensure_clipboard_proxy(&proxy_runtime_dir));
+    let glab_config = load_glab_proxy_config();
+    if glab_config.enabled {
+        require_proxy(ensure_glab_proxy(&proxy_runtime_dir, &glab_config));
+    }
+    require_proxy(ensure_clipboard_proxy(
+        &proxy_runtime_dir,
+        &load_clipboard_config(),
+    ));
+    let signing = load_signing_config().map(|config| {
+        let signing_key = config.container_signing_key().unwrap_or_else(|error| {
+            eprintln!("Error: signing key: {error}");
+            std::process::exit(1);
+        });
+        require_proxy(ensure_signing_proxy(&proxy_runtime_dir, &config));
+        (config.format, signing_key)
+    });
 
     match (managed_push_state, allow_push, git_proxy::origin_url()) {
         (Some(state_dir), true, _) => {
@@ -1003,6 +1507,10 @@ fn run_container(
         require_proxy(ensure_ssh_proxy(&proxy_runtime_dir, &ssh_proxy_config));
     }
     ensure_ssh_proxy_symlink();
+    let ssh_agent = load_ssh_agent_config();
+    if let Some(config) = &ssh_agent {
+        require_proxy(ensure_ssh_agent_proxy(&proxy_runtime_dir, config));
+    }
 
     let home = home_dir();
     let claude_dir = home.join(".claude");
@@ -1102,6 +1610,20 @@ fn run_container(
         cmd.args(["-p", &format!("{}:{}", port, port)]);
     }
 
+    if let Some((format, signing_key)) = &signing {
+        cmd.arg("-e")
+            .arg(format!("GIT_SIGNING_FORMAT={}", format.as_str()))
+            .arg("-e")
+            .arg(format!("GIT_SIGNING_KEY={}", signing_key));
+    }
+
+    if ssh_agent.is_some() {
+        cmd.arg("-e").arg(format!(
+            "SSH_AUTH_SOCK={}/{}",
+            CONTAINER_PROXY_RUNTIME_DIR, SSH_AGENT_PROXY_SOCKET_NAME
+        ));
+    }
+
     if let Some(ssh_cfg) = ssh {
         cmd.arg("-e")
             .arg(format!("SSH_AUTHORIZED_KEY={}", ssh_cfg.authorized_key));
@@ -1138,9 +1660,44 @@ fn run_container(
 }
 
 fn run_internal_command(command: Option<&Commands>) -> bool {
+    if matches!(
+        command,
+        Some(
+            Commands::GhProxy { .. }
+                | Commands::GlabProxy { .. }
+                | Commands::GitProxy { .. }
+                | Commands::ClipboardProxy { .. }
+                | Commands::SigningProxy { .. }
+                | Commands::SshProxy { .. }
+                | Commands::SshAgentProxy { .. }
+        )
+    ) {
+        install_redaction();
+    }
     match command {
-        Some(Commands::GhProxy { socket, log }) => {
-            gh_proxy::run(socket, log);
+        Some(Commands::GhProxy {
+            socket,
+            log,
+            config_json,
+        }) => {
+            let config: gh_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("gh-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
+            gh_proxy::run(socket, log, config);
+        }
+        Some(Commands::GlabProxy {
+            socket,
+            log,
+            config_json,
+        }) => {
+            let config: glab_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("glab-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
+            glab_proxy::run(socket, log, config);
         }
         Some(Commands::GitProxy {
             socket,
@@ -1148,7 +1705,13 @@ fn run_internal_command(command: Option<&Commands>) -> bool {
             origin_url,
             workspace_root,
             state_dir,
+            config_json,
         }) => {
+            let config: git_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("git-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
             let mode = match (
                 origin_url.as_ref(),
                 workspace_root.as_ref(),
@@ -1170,7 +1733,7 @@ fn run_internal_command(command: Option<&Commands>) -> bool {
                     std::process::exit(2);
                 }
             };
-            git_proxy::run(socket, log, mode);
+            git_proxy::run(socket, log, mode, config);
         }
         Some(Commands::T3Admin {
             port,
@@ -1191,8 +1754,29 @@ fn run_internal_command(command: Option<&Commands>) -> bool {
                 managed_push: *managed_push,
             });
         }
-        Some(Commands::ClipboardProxy { socket, log }) => {
-            clipboard_proxy::run(socket, log);
+        Some(Commands::ClipboardProxy {
+            socket,
+            log,
+            config_json,
+        }) => {
+            let config: clipboard_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("clipboard-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
+            clipboard_proxy::run(socket, log, config);
+        }
+        Some(Commands::SigningProxy {
+            socket,
+            log,
+            config_json,
+        }) => {
+            let config: signing_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("signing-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
+            signing_proxy::run(socket, log, config);
         }
         Some(Commands::SshProxy {
             socket,
@@ -1206,6 +1790,18 @@ fn run_internal_command(command: Option<&Commands>) -> bool {
                 });
             ssh_proxy::run(socket, log, &config);
         }
+        Some(Commands::SshAgentProxy {
+            socket,
+            log,
+            config_json,
+        }) => {
+            let config: ssh_agent_proxy::Config =
+                serde_json::from_str(config_json).unwrap_or_else(|error| {
+                    eprintln!("ssh-agent-proxy: invalid config JSON: {error}");
+                    std::process::exit(1);
+                });
+            ssh_agent_proxy::run(socket, log, config);
+        }
         _ => return false,
     }
     true
@@ -1216,10 +1812,6 @@ fn main() {
     if run_internal_command(cli.command.as_ref()) {
         return;
     }
-    if cli.t3_managed_push && !matches!(&cli.command, Some(Commands::T3code { .. })) {
-        eprintln!("Error: --t3-managed-push can only be used with the t3code command");
-        std::process::exit(2);
-    }
     let client = Client::new();
 
     let update_status = check_available_updates(&client);
@@ -1267,6 +1859,21 @@ fn main() {
         None
     };
 
+    let managed_push_state = cli.managed_push.then(prepare_managed_push_state);
+    let launches_container = matches!(
+        cli.command,
+        None | Some(
+            Commands::Shell
+                | Commands::Run { .. }
+                | Commands::Codex { .. }
+                | Commands::Opencode { .. }
+                | Commands::T3code { .. }
+        )
+    );
+    if launches_container && !cli.no_checkpoint {
+        checkpoint_on_launch(cli.quiet);
+    }
+
     match cli.command {
         Some(Commands::Shell) => {
             run_container(
@@ -1280,8 +1887,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push,
-                None,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 None,
             );
         }
@@ -1296,10 +1903,13 @@ fn main() {
         }
         Some(
             Commands::GhProxy { .. }
+            | Commands::GlabProxy { .. }
             | Commands::GitProxy { .. }
             | Commands::T3Admin { .. }
             | Commands::ClipboardProxy { .. }
-            | Commands::SshProxy { .. },
+            | Commands::SigningProxy { .. }
+            | Commands::SshProxy { .. }
+            | Commands::SshAgentProxy { .. },
         ) => unreachable!("internal commands are dispatched before update checks"),
         Some(Commands::Run { command }) => {
             let cmd_str = command.join(" ");
@@ -1314,8 +1924,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push,
-                None,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 None,
             );
         }
@@ -1336,8 +1946,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push,
-                None,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 None,
             );
         }
@@ -1358,8 +1968,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push,
-                None,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 None,
             );
         }
@@ -1374,12 +1984,6 @@ fn main() {
                 eprintln!("T3CODE_PAIR_ADMIN_PIN must contain 4 to 12 digits");
                 std::process::exit(2);
             }
-            if cli.t3_managed_push && pair_admin_pin.is_none() {
-                eprintln!(
-                    "Error: --t3-managed-push requires T3CODE_PAIR_ADMIN_PIN so repositories can be approved"
-                );
-                std::process::exit(2);
-            }
             let pair_admin_port = pair_admin_pin.as_ref().map(|_| {
                 let mut excluded_ports = cli.ports.clone();
                 excluded_ports.push(port);
@@ -1388,24 +1992,9 @@ fn main() {
             let cwd = env::current_dir().expect("Could not get current directory");
             let instance_name = project_instance_name(&cwd);
             let instance_dir = format!("/root/.t3/instances/{}", instance_name);
-            let mut push_state_dir = managed_push::state_dir(&home_dir(), &instance_name);
-            if cli.t3_managed_push {
-                push_state_dir =
-                    managed_push::prepare_state_dir(&push_state_dir).unwrap_or_else(|error| {
-                        eprintln!("Error: could not prepare managed push state: {error}");
-                        std::process::exit(1);
-                    });
-                let canonical_workspace = fs::canonicalize(&cwd).unwrap_or_else(|error| {
-                    eprintln!("Error: could not resolve T3 workspace: {error}");
-                    std::process::exit(1);
-                });
-                if push_state_dir.starts_with(&canonical_workspace) {
-                    eprintln!(
-                        "Error: managed push state must be outside the agent-mounted T3 workspace"
-                    );
-                    std::process::exit(1);
-                }
-            }
+            let push_state_dir = managed_push_state
+                .clone()
+                .unwrap_or_else(|| managed_push::state_dir(&home_dir(), &instance_name));
             let container_name = if cli.wrap {
                 wrap_container_name(&cwd)
             } else {
@@ -1437,7 +2026,7 @@ fn main() {
                     t3_base_dir: &instance_dir,
                     workspace_root: &cwd,
                     state_dir: &push_state_dir,
-                    managed_push: cli.t3_managed_push,
+                    managed_push: cli.managed_push,
                 });
             }
 
@@ -1445,7 +2034,6 @@ fn main() {
                 format!("T3CODE_PORT={}", port),
                 format!("T3CODE_BASE_DIR={}", instance_dir),
             ];
-            let managed_state = cli.t3_managed_push.then_some(push_state_dir.as_path());
             let named_container = pair_admin_pin.as_ref().map(|_| container_name.as_str());
 
             run_container(
@@ -1459,8 +2047,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push || cli.t3_managed_push,
-                managed_state,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 named_container,
             );
         }
@@ -1482,6 +2070,15 @@ fn main() {
         Some(Commands::WrapList) => {
             print_wrap_sessions();
         }
+        Some(Commands::Approvals { action }) => {
+            run_approvals(&action);
+        }
+        Some(Commands::Checkpoint { action }) => {
+            run_checkpoint(&action);
+        }
+        Some(Commands::Logs { target }) => {
+            run_logs(&target);
+        }
         None => {
             let tool = default_tool();
             let inner_cmd = if cli.args.is_empty() {
@@ -1500,8 +2097,8 @@ fn main() {
                 !cli.no_audio,
                 true,
                 cli.wrap,
-                cli.allow_push,
-                None,
+                cli.allow_push || cli.managed_push,
+                managed_push_state.as_deref(),
                 None,
             );
         }
@@ -16,11 +16,29 @@ pub struct Repository {
     pub branch: Option<String>,
 }
 
+/// The exact remote ref and commit a per-push approval is bound to.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PushTarget {
+    pub reference: String,
+    pub oid: String,
+}
+
+/// What a held push would send, as shown to the approver.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PushPreview {
+    pub target: PushTarget,
+    /// `<short oid> <subject>` for each commit not yet seen on origin.
+    pub commits: Vec<String>,
+    pub diffstat: Vec<String>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Candidate {
     pub repository: Repository,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub previously_approved_origin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push: Option<PushPreview>,
     pub requested_at: u64,
 }
 
@@ -29,15 +47,61 @@ pub struct Candidate {
 pub enum ApprovalScope {
     Once,
     Persistent,
+    /// One push of exactly the approved ref and commit.
+    Push,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Approval {
     pub repository: Repository,
     pub scope: ApprovalScope,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<PushTarget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Branch globs pushes are restricted to. Empty allows any branch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<String>,
     pub approved_at: u64,
 }
 
+impl Approval {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Approve,
+    Revoke,
+    Consume,
+    Expire,
+}
+
+impl HistoryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryKind::Approve => "approve",
+            HistoryKind::Revoke => "revoke",
+            HistoryKind::Consume => "consume",
+            HistoryKind::Expire => "expire",
+        }
+    }
+}
+
+/// One line of a repository's append-only approval history.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryEvent {
+    pub event: HistoryKind,
+    pub at: u64,
+    pub relative_path: String,
+    pub origin: String,
+    /// The approval's scope and limits at the time of the event.
+    pub detail: String,
+}
+
 pub fn state_dir(home: &Path, instance: &str) -> PathBuf {
     home.join(".claude-sandbox/projects")
         .join(instance)
@@ -55,7 +119,7 @@ fn ensure_private_dir(path: &Path) -> Result<(), String> {
         .map_err(|e| format!("could not protect {}: {}", path.display(), e))
 }
 
-fn atomic_write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+pub(crate) fn atomic_write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
     let parent = path
         .parent()
         .ok_or_else(|| format!("{} has no parent directory", path.display()))?;
@@ -152,16 +216,31 @@ fn approvals_dir(state: &Path) -> PathBuf {
     state.join("approved")
 }
 
+fn history_dir(state: &Path) -> PathBuf {
+    state.join("history")
+}
+
+/// Record a pending request. A request without a push, such as a held
+/// fetch, keeps the preview of a push already waiting for the repository.
 pub fn record_candidate(
     state: &Path,
     repository: &Repository,
     previously_approved_origin: Option<String>,
+    push: Option<PushPreview>,
 ) -> Result<String, String> {
     let id = candidate_id(repository);
     let path = state_file(&candidates_dir(state), &id)?;
+    let push = match push {
+        Some(push) => Some(push),
+        None => read_json::<Candidate>(&path)
+            .ok()
+            .filter(|existing| existing.repository.relative_path == repository.relative_path)
+            .and_then(|existing| existing.push),
+    };
     let candidate = Candidate {
         repository: repository.clone(),
         previously_approved_origin,
+        push,
         requested_at: unix_time(),
     };
     atomic_write_json(&path, &candidate)?;
@@ -202,32 +281,63 @@ pub fn remove_candidate(state: &Path, id: &str) -> Result<(), String> {
     }
 }
 
-pub fn approve(state: &Path, repository: &Repository, scope: ApprovalScope) -> Result<(), String> {
+/// Optional limits on what an approval covers.
+#[derive(Clone, Debug, Default)]
+pub struct ApprovalLimits {
+    /// Seconds until the approval expires.
+    pub duration: Option<u64>,
+    /// Branch globs the approval is restricted to. Empty allows any branch.
+    pub branches: Vec<String>,
+}
+
+fn write_approval(
+    state: &Path,
+    repository: &Repository,
+    scope: ApprovalScope,
+    target: Option<PushTarget>,
+    limits: &ApprovalLimits,
+) -> Result<(), String> {
     let id = approval_id(&repository.relative_path);
     let path = state_file(&approvals_dir(state), &id)?;
-    atomic_write_json(
-        &path,
-        &Approval {
-            repository: repository.clone(),
-            scope,
-            approved_at: unix_time(),
-        },
-    )
+    let approved_at = unix_time();
+    let expires_at = limits
+        .duration
+        .map(|duration| {
+            approved_at
+                .checked_add(duration)
+                .ok_or_else(|| "approval duration is too long".to_string())
+        })
+        .transpose()?;
+    let approval = Approval {
+        repository: repository.clone(),
+        scope,
+        target,
+        expires_at,
+        branches: limits.branches.clone(),
+        approved_at,
+    };
+    atomic_write_json(&path, &approval)?;
+    append_history(state, &approval, HistoryKind::Approve)
 }
 
 pub fn read_approval(state: &Path, relative_path: &str) -> Result<Option<Approval>, String> {
     let path = state_file(&approvals_dir(state), &approval_id(relative_path))?;
     match read_json::<Approval>(&path) {
-        Ok(approval) if approval.repository.relative_path == relative_path => Ok(Some(approval)),
-        Ok(_) => Err(format!(
+        Ok(approval) if approval.repository.relative_path != relative_path => Err(format!(
             "approval identifier collision for repository {relative_path}"
         )),
+        Ok(approval) if approval.is_expired(unix_time()) => {
+            expire_approvals(state)?;
+            Ok(None)
+        }
+        Ok(approval) => Ok(Some(approval)),
         Err(_) if !path.exists() => Ok(None),
         Err(error) => Err(error),
     }
 }
 
 pub fn list_approvals(state: &Path) -> Result<Vec<(String, Approval)>, String> {
+    expire_approvals(state)?;
     let mut approvals: Vec<(String, Approval)> = list_json(&approvals_dir(state))?;
     approvals.sort_by(|left, right| {
         left.1
@@ -238,28 +348,231 @@ pub fn list_approvals(state: &Path) -> Result<Vec<(String, Approval)>, String> {
     Ok(approvals)
 }
 
-pub fn revoke(state: &Path, relative_path: &str) -> Result<(), String> {
+/// Remove an approval file, returning the approval if this call removed it.
+fn take_approval(state: &Path, relative_path: &str) -> Result<Option<Approval>, String> {
     let path = state_file(&approvals_dir(state), &approval_id(relative_path))?;
+    let approval = read_json::<Approval>(&path).ok();
     match fs::remove_file(&path) {
-        Ok(()) => Ok(()),
-        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Ok(()) => Ok(approval),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
         Err(error) => Err(format!("could not remove {}: {}", path.display(), error)),
     }
 }
 
+pub fn revoke(state: &Path, relative_path: &str) -> Result<(), String> {
+    if let Some(approval) = take_approval(state, relative_path)? {
+        append_history(state, &approval, HistoryKind::Revoke)?;
+    }
+    Ok(())
+}
+
+/// Remove a single-use (`Once` or `Push`) approval. Returns `false` when
+/// another request already consumed it.
 pub fn consume_once(state: &Path, approval: &Approval) -> Result<bool, String> {
-    if approval.scope != ApprovalScope::Once {
+    if approval.scope == ApprovalScope::Persistent {
         return Ok(true);
     }
-    let path = state_file(
-        &approvals_dir(state),
-        &approval_id(&approval.repository.relative_path),
-    )?;
-    match fs::remove_file(&path) {
-        Ok(()) => Ok(true),
-        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
-        Err(error) => Err(format!("could not consume {}: {}", path.display(), error)),
+    match take_approval(state, &approval.repository.relative_path)? {
+        Some(taken) => {
+            append_history(state, &taken, HistoryKind::Consume)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Remove approvals past their expiry and return their repositories to
+/// pending, so they are reviewed again.
+pub fn expire_approvals(state: &Path) -> Result<(), String> {
+    let now = unix_time();
+    for (_, approval) in list_json::<Approval>(&approvals_dir(state))? {
+        if !approval.is_expired(now) {
+            continue;
+        }
+        if let Some(expired) = take_approval(state, &approval.repository.relative_path)? {
+            append_history(state, &expired, HistoryKind::Expire)?;
+            record_candidate(state, &expired.repository, None, None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Approve a pending candidate with `scope`, after checking that the
+/// repository still has the origin that was requested. An origin change
+/// replaces the candidate with a fresh one for review instead.
+///
+/// `reviewed` is the push the approver was shown. A held push replaces the
+/// candidate, so a push approval is refused unless the candidate still
+/// previews exactly that ref and commit.
+pub fn approve_candidate(
+    state: &Path,
+    workspace_root: &Path,
+    id: &str,
+    scope: ApprovalScope,
+    reviewed: Option<&PushTarget>,
+    limits: &ApprovalLimits,
+) -> Result<Repository, String> {
+    let candidate = read_candidate(state, id)?;
+    let pending = candidate.push.as_ref().map(|push| &push.target);
+    if reviewed.is_some() && reviewed != pending {
+        return Err(
+            "The pending push changed after it was shown. Review it again before approving."
+                .to_string(),
+        );
+    }
+    if scope == ApprovalScope::Push && pending.is_some() && reviewed.is_none() {
+        return Err("Name the previewed ref and commit to approve this push.".to_string());
+    }
+    let (_, current) =
+        resolve_relative_repository(workspace_root, &candidate.repository.relative_path)?;
+    if current.origin != candidate.repository.origin {
+        record_candidate(state, &current, candidate.previously_approved_origin, None)?;
+        remove_candidate(state, id)?;
+        return Err(
+            "The repository origin changed. Review the new candidate before approving.".to_string(),
+        );
+    }
+    let target = match (scope, candidate.push) {
+        (ApprovalScope::Push, Some(push)) => Some(push.target),
+        (ApprovalScope::Push, None) => {
+            return Err("This request has no push to approve.".to_string());
+        }
+        _ => None,
+    };
+    write_approval(state, &current, scope, target, limits)?;
+    remove_candidate(state, id)?;
+    Ok(current)
+}
+
+/// Parse a duration such as `90m`, `8h` or `2d` into seconds.
+pub fn parse_duration(value: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid duration '{value}'; use minutes, hours or days, e.g. 8h");
+    let split = value.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    match amount.checked_mul(unit) {
+        Some(seconds) if seconds > 0 => Ok(seconds),
+        _ => Err(invalid()),
+    }
+}
+
+/// Rough human duration, e.g. `7h 59m`.
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..60 => "<1m".to_string(),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86_400 => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+        _ => format!("{}d {}h", seconds / 86_400, seconds % 86_400 / 3600),
+    }
+}
+
+/// One-line summary of what an approval allows, including its limits.
+pub fn scope_label(approval: &Approval) -> String {
+    let mut label = match (approval.scope, &approval.target) {
+        (ApprovalScope::Push, Some(target)) => format!(
+            "this push only — {} at {}",
+            target.reference,
+            &target.oid[..target.oid.len().min(12)]
+        ),
+        (ApprovalScope::Once, _) => "next push only".to_string(),
+        (ApprovalScope::Persistent, _) => "persistent".to_string(),
+        (ApprovalScope::Push, None) => "invalid push approval".to_string(),
+    };
+    if !approval.branches.is_empty() {
+        label.push_str(&format!(", branches {}", approval.branches.join(", ")));
+    }
+    if let Some(expires_at) = approval.expires_at {
+        label.push_str(&format!(
+            ", expires in {}",
+            format_duration(expires_at.saturating_sub(unix_time()))
+        ));
+    }
+    label
+}
+
+/// Why an approval cannot currently be used, if it cannot.
+pub fn suspension(workspace_root: &Path, approval: &Approval) -> Option<String> {
+    match resolve_relative_repository(workspace_root, &approval.repository.relative_path) {
+        Ok((_, current)) if current.origin == approval.repository.origin => None,
+        Ok((_, current)) => Some(format!("origin is now {}", current.origin)),
+        Err(_) => Some("repository unavailable".to_string()),
+    }
+}
+
+/// Confirmation shown after approving `relative_path` with `scope`.
+pub fn approved_message(scope: ApprovalScope, relative_path: &str) -> String {
+    match scope {
+        ApprovalScope::Once => format!("Approved the next push from {relative_path}."),
+        ApprovalScope::Persistent => format!("Approved persistent pushes from {relative_path}."),
+        ApprovalScope::Push => format!(
+            "Approved this push from {relative_path}. Retry it to send exactly the reviewed commits."
+        ),
+    }
+}
+
+fn history_file(state: &Path, relative_path: &str) -> PathBuf {
+    history_dir(state).join(format!("{}.jsonl", approval_id(relative_path)))
+}
+
+/// Append one event to the repository's history. The file is only ever
+/// appended to, so it records every decision even after the approval is gone.
+fn append_history(state: &Path, approval: &Approval, event: HistoryKind) -> Result<(), String> {
+    let path = history_file(state, &approval.repository.relative_path);
+    ensure_private_dir(&history_dir(state))?;
+    let entry = HistoryEvent {
+        event,
+        at: unix_time(),
+        relative_path: approval.repository.relative_path.clone(),
+        origin: approval.repository.origin.clone(),
+        detail: scope_label(approval),
+    };
+    let mut line = serde_json::to_vec(&entry)
+        .map_err(|e| format!("could not encode history for {}: {}", path.display(), e))?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&line))
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+/// History events for every repository, newest first.
+pub fn list_history(state: &Path) -> Result<Vec<HistoryEvent>, String> {
+    let directory = history_dir(state);
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut events = Vec::new();
+    for entry in fs::read_dir(&directory)
+        .map_err(|e| format!("could not list {}: {}", directory.display(), e))?
+    {
+        let path = entry
+            .map_err(|e| format!("could not read history entry: {}", e))?
+            .path();
+        if path.extension().and_then(|v| v.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        events.extend(
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str::<HistoryEvent>(line).ok()),
+        );
     }
+    // Newest first; reversing before the stable sort keeps events logged in
+    // the same second in reverse order of writing.
+    events.reverse();
+    events.sort_by_key(|event| std::cmp::Reverse(event.at));
+    Ok(events)
 }
 
 pub fn resolve_repository(
@@ -370,9 +683,33 @@ fn unix_time() -> u64 {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Approve a repository directly, without a pending candidate.
+    pub(crate) fn approve(
+        state: &Path,
+        repository: &Repository,
+        scope: ApprovalScope,
+    ) -> Result<(), String> {
+        write_approval(state, repository, scope, None, &ApprovalLimits::default())
+    }
+
+    /// Approve a single push of `target`, replacing any repository approval.
+    pub(crate) fn approve_push(
+        state: &Path,
+        repository: &Repository,
+        target: PushTarget,
+    ) -> Result<(), String> {
+        write_approval(
+            state,
+            repository,
+            ApprovalScope::Push,
+            Some(target),
+            &ApprovalLimits::default(),
+        )
+    }
+
     #[test]
     fn identifiers_are_stable_and_scoped() {
         let repo = Repository {
@@ -384,12 +721,161 @@ mod tests {
         assert_ne!(candidate_id(&repo), approval_id(&repo.relative_path));
     }
 
+    #[test]
+    fn reads_state_written_before_push_previews() {
+        let candidate: Candidate = serde_json::from_str(
+            r#"{"repository":{"relative_path":".","origin":"o"},"requested_at":1}"#,
+        )
+        .unwrap();
+        assert!(candidate.push.is_none());
+        let approval: Approval = serde_json::from_str(
+            r#"{"repository":{"relative_path":".","origin":"o"},"scope":"once","approved_at":1}"#,
+        )
+        .unwrap();
+        assert!(approval.target.is_none());
+    }
+
     #[test]
     fn rejects_container_paths_outside_workspace() {
         let error = resolve_repository(Path::new("/"), "/etc").unwrap_err();
         assert!(error.contains("/workspace"));
     }
 
+    #[test]
+    fn parses_approval_durations() {
+        assert_eq!(parse_duration("90m"), Ok(5400));
+        assert_eq!(parse_duration("8h"), Ok(28_800));
+        assert_eq!(parse_duration("2d"), Ok(172_800));
+        for invalid in ["", "h", "0h", "8", "8w", "-1h"] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(format_duration(28_740), "7h 59m");
+    }
+
+    #[test]
+    fn expired_approvals_return_to_pending_with_history() {
+        let state = std::env::temp_dir().join(format!(
+            "claude-sandbox-managed-push-expiry-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&state);
+        let repo = Repository {
+            relative_path: "project".to_string(),
+            origin: "git@example.test:org/project.git".to_string(),
+            branch: None,
+        };
+        let limits = ApprovalLimits {
+            duration: Some(3600),
+            branches: vec!["agent/*".to_string()],
+        };
+        let endless = ApprovalLimits {
+            duration: parse_duration(&format!("{}d", u64::MAX / 86_400)).ok(),
+            branches: Vec::new(),
+        };
+        assert!(endless.duration.is_some());
+        assert_eq!(
+            write_approval(&state, &repo, ApprovalScope::Persistent, None, &endless),
+            Err("approval duration is too long".to_string())
+        );
+        assert!(read_approval(&state, "project").unwrap().is_none());
+        write_approval(&state, &repo, ApprovalScope::Persistent, None, &limits).unwrap();
+        let approval = read_approval(&state, "project").unwrap().unwrap();
+        assert_eq!(approval.branches, ["agent/*"]);
+        assert!(scope_label(&approval).contains("expires in"));
+        assert!(list_candidates(&state).unwrap().is_empty());
+
+        let path = state_file(&approvals_dir(&state), &approval_id("project")).unwrap();
+        atomic_write_json(
+            &path,
+            &Approval {
+                expires_at: Some(approval.approved_at),
+                ..approval
+            },
+        )
+        .unwrap();
+        assert!(read_approval(&state, "project").unwrap().is_none());
+        let pending = list_candidates(&state).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.repository, repo);
+
+        approve(&state, &repo, ApprovalScope::Once).unwrap();
+        let once = read_approval(&state, "project").unwrap().unwrap();
+        assert!(consume_once(&state, &once).unwrap());
+        assert!(!consume_once(&state, &once).unwrap());
+        let events: Vec<HistoryKind> = list_history(&state)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.event)
+            .collect();
+        assert_eq!(
+            events,
+            [
+                HistoryKind::Consume,
+                HistoryKind::Approve,
+                HistoryKind::Expire,
+                HistoryKind::Approve
+            ]
+        );
+        fs::remove_dir_all(&state).unwrap();
+    }
+
+    #[test]
+    fn push_approvals_are_bound_to_the_reviewed_push() {
+        let state = std::env::temp_dir().join(format!(
+            "claude-sandbox-managed-push-reviewed-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&state);
+        let repo = Repository {
+            relative_path: "project".to_string(),
+            origin: "git@example.test:org/project.git".to_string(),
+            branch: Some("main".to_string()),
+        };
+        let target = |oid: &str| PushTarget {
+            reference: "refs/heads/main".to_string(),
+            oid: oid.to_string(),
+        };
+        let preview = |oid: &str| PushPreview {
+            target: target(oid),
+            commits: Vec::new(),
+            diffstat: Vec::new(),
+        };
+        let limits = ApprovalLimits::default();
+        record_candidate(&state, &repo, None, Some(preview("aaaa"))).unwrap();
+        // A held fetch does not erase the pending push
+        record_candidate(&state, &repo, None, None).unwrap();
+        let (_, pending) = list_candidates(&state).unwrap().remove(0);
+        assert_eq!(pending.push.unwrap().target, target("aaaa"));
+        // The agent pushes again after the approver loaded the preview
+        let id = record_candidate(&state, &repo, None, Some(preview("bbbb"))).unwrap();
+
+        let workspace = Path::new("/nonexistent");
+        for scope in [ApprovalScope::Push, ApprovalScope::Once] {
+            let error = approve_candidate(
+                &state,
+                workspace,
+                &id,
+                scope,
+                Some(&target("aaaa")),
+                &limits,
+            )
+            .unwrap_err();
+            assert!(error.contains("changed after it was shown"), "{error}");
+        }
+        let error = approve_candidate(&state, workspace, &id, ApprovalScope::Push, None, &limits)
+            .unwrap_err();
+        assert!(
+            error.contains("Name the previewed ref and commit"),
+            "{error}"
+        );
+        assert!(read_approval(&state, "project").unwrap().is_none());
+        assert_eq!(
+            read_candidate(&state, &id).unwrap().push.unwrap().target,
+            target("bbbb")
+        );
+        fs::remove_dir_all(&state).unwrap();
+    }
+
     #[test]
     fn validates_state_identifiers() {
         assert!(state_file(Path::new("/tmp"), "0123456789abcdef").is_ok());
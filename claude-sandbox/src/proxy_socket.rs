@@ -1,8 +1,15 @@
-use std::fs::{self, DirBuilder, Permissions};
-use std::io;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs::{self, DirBuilder, File, Permissions};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
 use std::os::unix::net::UnixListener;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::logging::log_line;
 
 pub struct BoundSocket {
     pub listener: UnixListener,
@@ -56,6 +63,79 @@ impl SocketIdentity {
     }
 }
 
+/// Exit when the parent process (podman after exec) dies, removing the
+/// socket if it is still ours. After exec(), our ppid is podman's PID. When
+/// podman exits, ppid becomes 1 (init). Poll every 2s and clean up when
+/// that happens.
+pub fn exit_with_parent(identity: SocketIdentity, log: Arc<Mutex<File>>) {
+    let parent_pid = std::os::unix::process::parent_id();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_secs(2));
+            let current_ppid = std::os::unix::process::parent_id();
+            if current_ppid != parent_pid {
+                log_line(
+                    &log,
+                    &format!(
+                        "parent {} exited (ppid now {}), shutting down",
+                        parent_pid, current_ppid
+                    ),
+                );
+                let _ = identity.remove_if_owned();
+                std::process::exit(0);
+            }
+        }
+    });
+}
+
+/// Answer one JSON request line per connection, each on its own thread.
+/// At most `max_request` bytes are read. A line that does not parse is
+/// logged as `INVALID` and answered with `invalid(error)`.
+pub fn serve_json<Q, R>(
+    listener: UnixListener,
+    log: Arc<Mutex<File>>,
+    max_request: u64,
+    handle: impl Fn(Q) -> R + Send + Sync + 'static,
+    invalid: fn(String) -> R,
+) where
+    Q: DeserializeOwned + 'static,
+    R: Serialize + 'static,
+{
+    let handle = Arc::new(handle);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let log = Arc::clone(&log);
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || {
+                    let reader = BufReader::new(&stream);
+                    let mut writer = &stream;
+
+                    // Read exactly one JSON line
+                    let mut line = String::new();
+                    if let Ok(n) = reader.take(max_request).read_line(&mut line) {
+                        if n == 0 {
+                            return;
+                        }
+                        let response = match serde_json::from_str::<Q>(&line) {
+                            Ok(req) => handle(req),
+                            Err(e) => {
+                                log_line(&log, &format!("INVALID ({})", e));
+                                invalid(e.to_string())
+                            }
+                        };
+                        let _ = serde_json::to_writer(&mut writer, &response);
+                        let _ = writer.write_all(b"\n");
+                    }
+                });
+            }
+            Err(e) => {
+                log_line(&log, &format!("connection error: {}", e));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,4 +193,40 @@ mod tests {
         fs::remove_file(&target).unwrap();
         fs::remove_dir(&root).unwrap();
     }
+
+    #[test]
+    fn serve_json_answers_one_line_per_connection() {
+        let root = test_root("serve");
+        let path = root.join("proxy.sock");
+        let bound = bind(&path).unwrap();
+        fs::create_dir_all(&root).unwrap();
+        let log = Arc::new(Mutex::new(File::create(root.join("proxy.log")).unwrap()));
+        let serving = Arc::clone(&log);
+        thread::spawn(move || {
+            serve_json(
+                bound.listener,
+                serving,
+                64,
+                |value: u32| value * 2,
+                |_| u32::MAX,
+            )
+        });
+
+        let ask = |request: &[u8]| {
+            let mut stream = UnixStream::connect(&path).unwrap();
+            stream.write_all(request).unwrap();
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).unwrap();
+            response
+        };
+        assert_eq!(ask(b"21\n"), "42\n");
+        assert_eq!(ask(b"not json\n"), format!("{}\n", u32::MAX));
+        assert!(
+            fs::read_to_string(root.join("proxy.log"))
+                .unwrap()
+                .contains("INVALID (")
+        );
+        bound.identity.remove_if_owned().unwrap();
+        let _ = fs::remove_dir_all(&root);
+    }
 }
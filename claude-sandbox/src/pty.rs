@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+
+/// A pseudo-terminal pair. The master side stays with the proxy; the slave
+/// side becomes the controlling terminal of the process it is attached to.
+pub struct Pty {
+    pub master: File,
+    slave: File,
+}
+
+fn winsize(cols: u16, rows: u16) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn set_cloexec(file: &File) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor this process owns
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub fn open(cols: u16, rows: u16) -> io::Result<Pty> {
+    let mut master = -1;
+    let mut slave = -1;
+    let size = winsize(cols, rows);
+    // SAFETY: openpty writes two new descriptors, which are owned below
+    let rc = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: both descriptors were just created and are not shared
+    let pty = unsafe {
+        Pty {
+            master: File::from_raw_fd(master),
+            slave: File::from_raw_fd(slave),
+        }
+    };
+    set_cloexec(&pty.master)?;
+    set_cloexec(&pty.slave)?;
+    Ok(pty)
+}
+
+/// Change the terminal size. The kernel signals the foreground process
+/// with SIGWINCH, which ssh passes on to the remote side.
+pub fn resize(master: &File, cols: u16, rows: u16) -> io::Result<()> {
+    let size = winsize(cols, rows);
+    // SAFETY: TIOCSWINSZ reads a winsize from a valid pointer
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Pty {
+    /// Run `command` with the slave as its stdin, stdout and controlling
+    /// terminal, in a new session. Consumes the slave side so that reads
+    /// from the master end once the command exits.
+    pub fn attach(self, command: &mut Command) -> io::Result<File> {
+        command
+            .stdin(Stdio::from(self.slave.try_clone()?))
+            .stdout(Stdio::from(self.slave));
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(self.master)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn attached_command_sees_a_terminal_of_the_requested_size() {
+        let pty = open(91, 17).unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "test -t 0 && stty size"]);
+        let mut master = pty.attach(&mut command).unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+
+        let mut output = Vec::new();
+        let mut buf = [0u8; 256];
+        // Reading the master fails with EIO once the slave side is closed
+        while let Ok(n) = master.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert!(child.wait().unwrap().success());
+        assert_eq!(String::from_utf8_lossy(&output).trim(), "17 91");
+    }
+
+    #[test]
+    fn resize_updates_the_terminal_size() {
+        let pty = open(80, 24).unwrap();
+        resize(&pty.master, 132, 50).unwrap();
+        let mut size = winsize(0, 0);
+        // SAFETY: TIOCGWINSZ writes a winsize to a valid pointer
+        let rc = unsafe { libc::ioctl(pty.master.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+        assert_eq!(rc, 0);
+        assert_eq!((size.ws_col, size.ws_row), (132, 50));
+    }
+}
@@ -0,0 +1,730 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs::{DirBuilder, File};
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::{env, fs, process, thread};
+
+use crate::git_proxy::{self, PinnedRepository, RepositoryConfigSnapshot};
+use crate::logging::log_line;
+use crate::{proxy_log, proxy_socket, redact};
+
+const CONTAINER_WORKSPACE: &str = "/workspace";
+// Commit and tag objects are small; anything larger is not a signing payload.
+const MAX_PAYLOAD: usize = 1_048_576;
+const MAX_SUMMARY: usize = 72;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Ssh,
+    Openpgp,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Ssh => "ssh",
+            Format::Openpgp => "openpgp",
+        }
+    }
+}
+
+/// Host-side settings from `~/.claude-sandbox/projects/<project>/signing.json`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Config {
+    /// Signature format, as in Git's `gpg.format`.
+    pub format: Format,
+    /// SSH: path to the private key, or to a `.pub` whose private half is
+    /// loaded in the host ssh-agent. OpenPGP: key id passed to `gpg -u`.
+    pub key: String,
+}
+
+impl Config {
+    /// The `user.signingkey` value for the container's Git. Only public
+    /// material leaves the host.
+    pub fn container_signing_key(&self) -> Result<String, String> {
+        match self.format {
+            Format::Openpgp => Ok(self.key.clone()),
+            Format::Ssh => {
+                let public = if self.key.ends_with(".pub") {
+                    self.key.clone()
+                } else {
+                    format!("{}.pub", self.key)
+                };
+                let key = fs::read_to_string(&public)
+                    .map_err(|error| format!("could not read {public}: {error}"))?;
+                Ok(format!("key::{}", key.trim()))
+            }
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct Request {
+    format: Format,
+    #[serde(default)]
+    namespace: Option<String>,
+    payload_b64: String,
+    #[serde(default)]
+    cwd: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Response {
+    exit_code: i32,
+    signature: String,
+    stderr: String,
+}
+
+fn deny(stderr: String) -> Response {
+    Response {
+        exit_code: 1,
+        signature: String::new(),
+        stderr,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ObjectKind {
+    Commit,
+    Tag,
+}
+
+/// What the host learned from a signing payload before agreeing to sign it.
+#[derive(Debug)]
+struct SigningPayload {
+    kind: ObjectKind,
+    /// Objects the payload points at, with the type each must have.
+    references: Vec<(String, &'static str)>,
+    summary: String,
+}
+
+fn is_object_id(value: &str) -> bool {
+    matches!(value.len(), 40 | 64)
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Parse a payload as an unsigned commit or tag object exactly as Git lays
+/// them out, so the host never signs arbitrary data with the user's key.
+fn parse_payload(payload: &[u8]) -> Result<SigningPayload, String> {
+    let text = std::str::from_utf8(payload).map_err(|_| "payload is not UTF-8".to_string())?;
+    let (headers, message) = text
+        .split_once("\n\n")
+        .ok_or("payload has no message separator")?;
+    let mut lines = headers.lines().filter(|line| !line.starts_with(' '));
+    let mut references = Vec::new();
+
+    let kind = match lines.next().and_then(|line| line.split_once(' ')) {
+        Some(("tree", oid)) if is_object_id(oid) => {
+            references.push((oid.to_string(), "tree"));
+            let mut line = lines.next();
+            while let Some(("parent", oid)) = line.and_then(|line| line.split_once(' ')) {
+                if !is_object_id(oid) {
+                    return Err(format!("invalid parent id '{oid}'"));
+                }
+                references.push((oid.to_string(), "commit"));
+                line = lines.next();
+            }
+            if !line.is_some_and(|line| line.starts_with("author ")) {
+                return Err("commit has no author".to_string());
+            }
+            if !lines
+                .next()
+                .is_some_and(|line| line.starts_with("committer "))
+            {
+                return Err("commit has no committer".to_string());
+            }
+            ObjectKind::Commit
+        }
+        Some(("object", oid)) if is_object_id(oid) => {
+            let kind = match lines.next().and_then(|line| line.strip_prefix("type ")) {
+                Some("commit") => "commit",
+                Some("tree") => "tree",
+                Some("blob") => "blob",
+                Some("tag") => "tag",
+                _ => return Err("tag has no valid type".to_string()),
+            };
+            references.push((oid.to_string(), kind));
+            if !lines.next().is_some_and(|line| line.starts_with("tag ")) {
+                return Err("tag has no name".to_string());
+            }
+            if !lines.next().is_some_and(|line| line.starts_with("tagger ")) {
+                return Err("tag has no tagger".to_string());
+            }
+            ObjectKind::Tag
+        }
+        _ => return Err("payload is not a commit or tag object".to_string()),
+    };
+    if headers
+        .lines()
+        .any(|line| line.starts_with("gpgsig ") || line.starts_with("gpgsig-sha256 "))
+    {
+        return Err("payload is already signed".to_string());
+    }
+
+    let summary = message
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("")
+        .trim();
+    let summary = match summary.char_indices().nth(MAX_SUMMARY) {
+        Some((index, _)) => format!("{}...", &summary[..index]),
+        None => summary.to_string(),
+    };
+    Ok(SigningPayload {
+        kind,
+        references,
+        summary,
+    })
+}
+
+/// Map the container working directory to a repository under the host
+/// workspace, pinned for the reference check. The Git directory must stay
+/// inside the workspace too, so a planted `.git` file cannot point the
+/// check at another host repository.
+fn workspace_repository(
+    workspace_root: &Path,
+    container_cwd: &str,
+) -> Result<PinnedRepository, String> {
+    let workspace_root = fs::canonicalize(workspace_root)
+        .map_err(|error| format!("could not resolve workspace root: {error}"))?;
+    let relative = Path::new(container_cwd)
+        .strip_prefix(CONTAINER_WORKSPACE)
+        .map_err(|_| format!("working directory must be inside {CONTAINER_WORKSPACE}"))?;
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err("working directory contains an invalid path component".to_string());
+    }
+    let directory = fs::canonicalize(workspace_root.join(relative))
+        .map_err(|error| format!("could not resolve working directory: {error}"))?;
+    let output = locate_git(&directory)
+        .args(["rev-parse", "--show-toplevel", "--absolute-git-dir"])
+        .output()
+        .map_err(|error| format!("failed to locate repository: {error}"))?;
+    if !output.status.success() {
+        return Err("working directory is not in a git repository".to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut paths = stdout.lines().map(fs::canonicalize);
+    let (Some(Ok(top)), Some(Ok(git_dir))) = (paths.next(), paths.next()) else {
+        return Err("could not resolve repository paths".to_string());
+    };
+    if !top.starts_with(&workspace_root) || !git_dir.starts_with(&workspace_root) {
+        return Err("repository escapes the mounted workspace".to_string());
+    }
+    git_proxy::pin_repository(&top, &workspace_root)
+}
+
+/// Git run in an agent-writable directory to find its repository, with
+/// hooks and the filesystem monitor off and no repository environment.
+fn locate_git(directory: &Path) -> Command {
+    let mut command = Command::new("git");
+    git_proxy::clear_repository_environment(&mut command);
+    command
+        .arg("-C")
+        .arg(directory)
+        .args([
+            "-c",
+            "core.hooksPath=/dev/null",
+            "-c",
+            "core.fsmonitor=false",
+        ])
+        .env("GIT_NO_LAZY_FETCH", "1")
+        .env("GIT_NO_REPLACE_OBJECTS", "1")
+        .env("GIT_TERMINAL_PROMPT", "0");
+    command
+}
+
+/// Every object the payload points at must already exist in the repository
+/// with the expected type. The lookup runs against a config snapshot, so
+/// nothing the repository configures runs, and ignores replace refs.
+fn check_references(
+    repository: &PinnedRepository,
+    references: &[(String, &str)],
+) -> Result<(), String> {
+    let snapshot = RepositoryConfigSnapshot::create(repository, &[])?;
+    let mut child = git_proxy::hardened_command(repository, &snapshot)
+        .env("GIT_NO_REPLACE_OBJECTS", "1")
+        .args(["cat-file", "--batch-check=%(objectname) %(objecttype)"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("could not inspect repository: {error}"))?;
+    let input: String = references
+        .iter()
+        .map(|(oid, _)| format!("{oid}\n"))
+        .collect();
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(input.as_bytes())
+        .map_err(|error| format!("could not inspect repository: {error}"))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("could not inspect repository: {error}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for ((oid, expected), line) in references.iter().zip(stdout.lines()) {
+        if line != format!("{oid} {expected}") {
+            return Err(format!(
+                "{expected} {oid} is not in the workspace repository"
+            ));
+        }
+    }
+    if stdout.lines().count() != references.len() {
+        return Err("could not inspect repository".to_string());
+    }
+    Ok(())
+}
+
+struct SigningDirectory {
+    path: PathBuf,
+}
+
+impl SigningDirectory {
+    fn create() -> Result<Self, String> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = env::temp_dir().join(format!(
+            "claude-sandbox-sign-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&path)
+            .map_err(|error| format!("could not create signing directory: {error}"))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for SigningDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Sign with the configured host key. Returns the armored signature and any
+/// status output the container's Git expects on stderr.
+fn sign(config: &Config, payload: &[u8]) -> Result<(String, String), String> {
+    match config.format {
+        Format::Ssh => {
+            let directory = SigningDirectory::create()?;
+            let buffer = directory.path.join("payload");
+            fs::write(&buffer, payload)
+                .map_err(|error| format!("could not write signing payload: {error}"))?;
+            let mut command = Command::new("ssh-keygen");
+            command
+                .args(["-Y", "sign", "-n", "git", "-f"])
+                .arg(&config.key);
+            if config.key.ends_with(".pub") {
+                command.arg("-U");
+            }
+            let output = command
+                .arg(&buffer)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|error| format!("failed to run ssh-keygen: {error}"))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "ssh-keygen failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            let signature = fs::read_to_string(directory.path.join("payload.sig"))
+                .map_err(|error| format!("could not read signature: {error}"))?;
+            Ok((signature, String::new()))
+        }
+        Format::Openpgp => {
+            let mut child = Command::new("gpg")
+                .args(["--batch", "--no-tty", "--status-fd=2", "-bsau"])
+                .arg(&config.key)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|error| format!("failed to run gpg: {error}"))?;
+            let mut stdin = child.stdin.take().expect("piped stdin");
+            let input = payload.to_vec();
+            let writer = thread::spawn(move || stdin.write_all(&input));
+            let output = child
+                .wait_with_output()
+                .map_err(|error| format!("failed to run gpg: {error}"))?;
+            let _ = writer.join();
+            let status = String::from_utf8_lossy(&output.stderr).into_owned();
+            if !output.status.success() || !status.contains("[GNUPG:] SIG_CREATED ") {
+                return Err(format!("gpg failed: {}", status.trim()));
+            }
+            // Only status lines go back; gpg's human-readable chatter may
+            // name local key details.
+            let status = status
+                .lines()
+                .filter(|line| line.starts_with("[GNUPG:] "))
+                .map(|line| format!("{line}\n"))
+                .collect::<String>();
+            Ok((
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+                format!("\n{status}"),
+            ))
+        }
+    }
+}
+
+fn handle_request(
+    req: Request,
+    config: &Config,
+    workspace_root: &Path,
+    log: &Arc<Mutex<File>>,
+) -> Response {
+    if req.format != config.format {
+        log_line(
+            log,
+            &format!(
+                "DENIED  {} signature (host signs with {})",
+                req.format.as_str(),
+                config.format.as_str()
+            ),
+        );
+        return deny(format!(
+            "signing-proxy: the host signs with {} keys; set gpg.format={}",
+            config.format.as_str(),
+            config.format.as_str()
+        ));
+    }
+    if req.format == Format::Ssh && req.namespace.as_deref() != Some("git") {
+        log_line(log, "DENIED  ssh signature (namespace is not 'git')");
+        return deny("signing-proxy: only the 'git' signature namespace is signed".to_string());
+    }
+    let payload = match base64::engine::general_purpose::STANDARD.decode(&req.payload_b64) {
+        Ok(payload) if payload.len() <= MAX_PAYLOAD => payload,
+        Ok(_) => return deny("signing-proxy: payload is too large".to_string()),
+        Err(error) => return deny(format!("signing-proxy: invalid payload: {error}")),
+    };
+    let parsed = match parse_payload(&payload) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            log_line(log, &format!("DENIED  signature ({error})"));
+            return deny(format!("signing-proxy: refusing to sign: {error}"));
+        }
+    };
+    let kind = match parsed.kind {
+        ObjectKind::Commit => "commit",
+        ObjectKind::Tag => "tag",
+    };
+    let repository = match req
+        .cwd
+        .as_deref()
+        .ok_or_else(|| "request did not include a working directory".to_string())
+        .and_then(|cwd| workspace_repository(workspace_root, cwd))
+        .and_then(|repository| {
+            check_references(&repository, &parsed.references).map(|()| repository)
+        }) {
+        Ok(repository) => repository,
+        Err(error) => {
+            log_line(
+                log,
+                &format!("DENIED  {kind} \"{}\" ({error})", parsed.summary),
+            );
+            return deny(format!("signing-proxy: refusing to sign: {error}"));
+        }
+    };
+    let label = repository
+        .worktree_path()
+        .strip_prefix(workspace_root)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .display()
+        .to_string();
+
+    match sign(config, &payload) {
+        Ok((signature, stderr)) => {
+            log_line(
+                log,
+                &format!("SIGNED  {kind} \"{}\" ({label})", parsed.summary),
+            );
+            Response {
+                exit_code: 0,
+                signature,
+                stderr,
+            }
+        }
+        Err(error) => {
+            log_line(
+                log,
+                &format!("ERROR   {kind} \"{}\" ({error})", parsed.summary),
+            );
+            deny(format!("signing-proxy: {error}"))
+        }
+    }
+}
+
+pub fn run(socket_path: &str, log_path: &Path, config: Config) {
+    let config = CONFIG.get_or_init(|| config);
+    let workspace_root = env::current_dir()
+        .and_then(fs::canonicalize)
+        .unwrap_or_else(|e| {
+            eprintln!("signing-proxy: could not resolve workspace: {}", e);
+            std::process::exit(1);
+        });
+    let path = Path::new(socket_path);
+    let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
+        eprintln!(
+            "signing-proxy: failed to open log {}: {}",
+            log_path.display(),
+            e
+        );
+        std::process::exit(1);
+    });
+    let log = Arc::new(Mutex::new(log_file));
+
+    let bound = proxy_socket::bind(path).unwrap_or_else(|e| {
+        eprintln!("signing-proxy: failed to bind {}: {}", socket_path, e);
+        std::process::exit(1);
+    });
+    log_line(&log, &format!("listening on {}", socket_path));
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    proxy_socket::serve_json(
+        bound.listener,
+        Arc::clone(&log),
+        2 * MAX_PAYLOAD as u64,
+        move |req: Request| {
+            let mut response = handle_request(req, config, &workspace_root, &log);
+            redact::response_fields(&log, "signing", &mut [&mut response.stderr]);
+            response
+        },
+        |e| deny(format!("signing-proxy: invalid request: {}", e)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+    fn test_root(label: &str) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let root = env::temp_dir().join(format!(
+            "signing-proxy-test-{label}-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::canonicalize(root).unwrap()
+    }
+
+    fn run_git(directory: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn test_log(root: &Path) -> Arc<Mutex<File>> {
+        Arc::new(Mutex::new(File::create(root.join("signing.log")).unwrap()))
+    }
+
+    fn commit_payload(tree: &str, parent: Option<&str>, message: &str) -> String {
+        let parent = parent
+            .map(|parent| format!("parent {parent}\n"))
+            .unwrap_or_default();
+        format!(
+            "tree {tree}\n{parent}author A <a@example.invalid> 1700000000 +0000\n\
+             committer A <a@example.invalid> 1700000000 +0000\n\n{message}\n"
+        )
+    }
+
+    fn request(format: Format, payload: &str, cwd: &str) -> Request {
+        Request {
+            format,
+            namespace: Some("git".to_string()),
+            payload_b64: base64::engine::general_purpose::STANDARD.encode(payload),
+            cwd: Some(cwd.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_commit_and_tag() {
+        let commit =
+            parse_payload(commit_payload(TREE, Some(TREE), "Fix the thing\n\nBody").as_bytes())
+                .unwrap();
+        assert_eq!(commit.kind, ObjectKind::Commit);
+        assert_eq!(commit.summary, "Fix the thing");
+        assert_eq!(
+            commit.references,
+            vec![(TREE.to_string(), "tree"), (TREE.to_string(), "commit")]
+        );
+
+        let tag = format!(
+            "object {TREE}\ntype commit\ntag v1\ntagger A <a@example.invalid> 1700000000 +0000\n\nRelease v1\n"
+        );
+        let tag = parse_payload(tag.as_bytes()).unwrap();
+        assert_eq!(tag.kind, ObjectKind::Tag);
+        assert_eq!(tag.summary, "Release v1");
+    }
+
+    #[test]
+    fn test_parse_rejects_arbitrary_data() {
+        assert!(parse_payload(b"hello world").is_err());
+        assert!(parse_payload(b"tree nothex\n\nmessage").is_err());
+        assert!(parse_payload(format!("tree {TREE}\n\nno author").as_bytes()).is_err());
+        let signed = commit_payload(TREE, None, "msg").replacen(
+            "\n\n",
+            "\ngpgsig -----BEGIN SSH SIGNATURE-----\n -----END SSH SIGNATURE-----\n\n",
+            1,
+        );
+        assert_eq!(
+            parse_payload(signed.as_bytes()).unwrap_err(),
+            "payload is already signed"
+        );
+        let long = "x".repeat(100);
+        let parsed = parse_payload(commit_payload(TREE, None, &long).as_bytes()).unwrap();
+        assert_eq!(parsed.summary.len(), MAX_SUMMARY + 3);
+    }
+
+    #[test]
+    fn test_workspace_repository_must_stay_in_workspace() {
+        let root = test_root("workspace");
+        let workspace = root.join("workspace");
+        let outside = root.join("outside");
+        fs::create_dir_all(workspace.join("project")).unwrap();
+        run_git(&workspace.join("project"), &["init", "-q"]);
+        run_git(&root, &["init", "-q", "outside"]);
+
+        assert_eq!(
+            workspace_repository(&workspace, "/workspace/project")
+                .unwrap()
+                .worktree_path(),
+            workspace.join("project")
+        );
+        assert!(workspace_repository(&workspace, "/tmp").is_err());
+        assert!(workspace_repository(&workspace, "/workspace/../outside").is_err());
+
+        fs::create_dir_all(workspace.join("planted")).unwrap();
+        fs::write(
+            workspace.join("planted/.git"),
+            format!("gitdir: {}\n", outside.join(".git").display()),
+        )
+        .unwrap();
+        assert_eq!(
+            workspace_repository(&workspace, "/workspace/planted")
+                .err()
+                .unwrap(),
+            "repository escapes the mounted workspace"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ssh_signing_requires_objects_from_the_workspace_repository() {
+        let root = test_root("ssh-sign");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        run_git(&workspace, &["init", "-q"]);
+        let tree = run_git(&workspace, &["write-tree"]);
+        let key = root.join("id_ed25519");
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(generated.success());
+        let config = Config {
+            format: Format::Ssh,
+            key: key.to_str().unwrap().to_string(),
+        };
+        assert!(
+            config
+                .container_signing_key()
+                .unwrap()
+                .starts_with("key::ssh-ed25519 ")
+        );
+        let log = test_log(&root);
+
+        let payload = commit_payload(&tree, None, "Signed commit");
+        let signed = handle_request(
+            request(Format::Ssh, &payload, "/workspace"),
+            &config,
+            &workspace,
+            &log,
+        );
+        assert_eq!(signed.exit_code, 0, "{}", signed.stderr);
+        assert!(
+            signed
+                .signature
+                .starts_with("-----BEGIN SSH SIGNATURE-----")
+        );
+        let signature = root.join("payload.sig");
+        fs::write(&signature, &signed.signature).unwrap();
+        let mut verify = Command::new("ssh-keygen")
+            .args(["-Y", "check-novalidate", "-n", "git", "-s"])
+            .arg(&signature)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        verify
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(payload.as_bytes())
+            .unwrap();
+        assert!(verify.wait().unwrap().success());
+        let logged = fs::read_to_string(root.join("signing.log")).unwrap();
+        assert!(
+            logged.contains("SIGNED  commit \"Signed commit\" (.)"),
+            "{logged}"
+        );
+
+        let foreign = commit_payload(&"1".repeat(40), None, "Foreign tree");
+        let refused = handle_request(
+            request(Format::Ssh, &foreign, "/workspace"),
+            &config,
+            &workspace,
+            &log,
+        );
+        assert_eq!(refused.exit_code, 1);
+        assert!(refused.stderr.contains("not in the workspace repository"));
+
+        let mut other_namespace = request(Format::Ssh, &payload, "/workspace");
+        other_namespace.namespace = Some("file".to_string());
+        assert_eq!(
+            handle_request(other_namespace, &config, &workspace, &log).exit_code,
+            1
+        );
+        let wrong_format = handle_request(
+            request(Format::Openpgp, &payload, "/workspace"),
+            &config,
+            &workspace,
+            &log,
+        );
+        assert!(wrong_format.stderr.contains("gpg.format=ssh"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,920 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::{fs, thread};
+
+use crate::logging::log_line;
+use crate::{proxy_log, proxy_socket, ssh_proxy};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENTC_EXTENSION: u8 = 27;
+const SSH_MSG_USERAUTH_REQUEST: u8 = 50;
+const SESSION_BIND: &[u8] = b"session-bind@openssh.com";
+// OpenSSH's own limit on agent messages
+const MAX_MESSAGE: usize = 256 * 1024;
+
+/// Host-side settings from `~/.claude-sandbox/projects/<project>/ssh-agent.json`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Keys the container may see and use. Every other key in the host
+    /// agent stays hidden.
+    pub keys: Vec<KeyRule>,
+    /// Host agent socket. Defaults to `$SSH_AUTH_SOCK` at launch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Used to name the hosts sessions are bound to. Defaults to
+    /// `~/.ssh/known_hosts`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub known_hosts: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct KeyRule {
+    /// A public key (`ssh-ed25519 AAAA...`), a `SHA256:` fingerprint, or the
+    /// path of a `.pub` file.
+    pub key: String,
+    /// Host names the key may authenticate to, checked against the host key
+    /// the session is bound to. Empty allows any destination.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+}
+
+fn expand_home(path: &str, home: &Path) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => home.join(rest).to_string_lossy().into_owned(),
+        None => path.to_string(),
+    }
+}
+
+impl Config {
+    /// Fill in the defaults and read key files, so the proxy only sees
+    /// `type base64` keys and fingerprints. `auth_sock` is the launching
+    /// shell's `$SSH_AUTH_SOCK`.
+    pub fn resolve(&mut self, home: &Path, auth_sock: Option<String>) -> Result<(), String> {
+        if self.keys.is_empty() {
+            return Err("no keys listed".to_string());
+        }
+        self.agent = match self.agent.take().or(auth_sock) {
+            Some(agent) if !agent.is_empty() => Some(expand_home(&agent, home)),
+            _ => return Err("SSH_AUTH_SOCK is not set and no agent socket is configured".into()),
+        };
+        self.known_hosts = Some(match self.known_hosts.take() {
+            Some(path) => expand_home(&path, home),
+            None => home.join(".ssh/known_hosts").to_string_lossy().into_owned(),
+        });
+        for rule in &mut self.keys {
+            if ssh_proxy::is_fingerprint_pin(&rule.key) {
+                continue;
+            }
+            if let Some(key) = ssh_proxy::public_key_pin(&rule.key) {
+                rule.key = key;
+                continue;
+            }
+            let path = expand_home(&rule.key, home);
+            let contents = fs::read_to_string(&path)
+                .map_err(|error| format!("could not read key {}: {}", rule.key, error))?;
+            rule.key = ssh_proxy::public_key_pin(&contents)
+                .ok_or(format!("{} does not hold a public key", rule.key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Cursor over the SSH wire encoding used by agent messages.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (first, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(*first)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let (bytes, rest) = self.data.split_first_chunk::<4>()?;
+        self.data = rest;
+        Some(u32::from_be_bytes(*bytes))
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if len > self.data.len() {
+            return None;
+        }
+        let (string, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(string)
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+/// One length-prefixed agent message, type byte first. `None` at a clean end
+/// of stream.
+fn read_message(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len == 0 || len > MAX_MESSAGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("agent message of {} bytes", len),
+        ));
+    }
+    let mut message = vec![0u8; len];
+    stream.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let mut framed = Vec::with_capacity(message.len() + 4);
+    put_string(&mut framed, message);
+    stream.write_all(&framed)
+}
+
+/// The `type base64` form of a public key blob, as written in `.pub` files.
+fn key_line(blob: &[u8]) -> String {
+    let key_type = Reader { data: blob }.string().unwrap_or_default();
+    format!(
+        "{} {}",
+        String::from_utf8_lossy(key_type),
+        base64::engine::general_purpose::STANDARD.encode(blob)
+    )
+}
+
+/// Operations that change the host agent, refused with their names logged.
+fn mutating_operation(message_type: u8) -> Option<&'static str> {
+    match message_type {
+        17 => Some("add identity"),
+        18 => Some("remove identity"),
+        19 => Some("remove all identities"),
+        20 => Some("add smartcard key"),
+        21 => Some("remove smartcard key"),
+        22 => Some("lock"),
+        23 => Some("unlock"),
+        25 => Some("add constrained identity"),
+        26 => Some("add constrained smartcard key"),
+        _ => None,
+    }
+}
+
+/// A `session-bind@openssh.com` extension the host agent accepted: the
+/// server's host key signed `session_id`.
+struct Binding {
+    host_key: Vec<u8>,
+    session_id: Vec<u8>,
+    forwarding: bool,
+}
+
+fn parse_session_bind(payload: &[u8]) -> Option<Binding> {
+    let mut reader = Reader { data: payload };
+    let host_key = reader.string()?.to_vec();
+    let session_id = reader.string()?.to_vec();
+    reader.string()?;
+    let forwarding = reader.byte()? != 0;
+    Some(Binding {
+        host_key,
+        session_id,
+        forwarding,
+    })
+}
+
+/// The session id, user name and key of an SSH `publickey` user
+/// authentication request, the only data a client signs to log in.
+fn parse_userauth(data: &[u8]) -> Option<(&[u8], String, &[u8])> {
+    let mut reader = Reader { data };
+    let session_id = reader.string()?;
+    if reader.byte()? != SSH_MSG_USERAUTH_REQUEST {
+        return None;
+    }
+    let user = String::from_utf8_lossy(reader.string()?).into_owned();
+    reader.string()?;
+    if reader.string()? != b"publickey" || reader.byte()? == 0 {
+        return None;
+    }
+    reader.string()?;
+    let key = reader.string()?;
+    Some((session_id, user, key))
+}
+
+/// The `type base64` keys in `ssh-keygen -F` output. Revoked and
+/// certificate authority entries are left out.
+fn parse_known_hosts_keys(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#') && !line.trim_start().starts_with('@'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            Some(format!("{} {}", fields.next()?, fields.next()?))
+        })
+        .collect()
+}
+
+fn known_hosts_keys(known_hosts: &str, host: &str) -> Vec<String> {
+    Command::new("ssh-keygen")
+        .args(["-F", host, "-f", known_hosts])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map(|output| parse_known_hosts_keys(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Plain (unhashed) host names listed for `key` in a known_hosts file.
+fn known_host_names(contents: &str, key: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(hosts), Some(key_type), Some(base64)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if hosts.starts_with(['#', '@', '|']) || format!("{} {}", key_type, base64) != key {
+            continue;
+        }
+        names.extend(
+            hosts
+                .split(',')
+                .filter(|name| !name.starts_with('!') && !name.contains(['*', '?']))
+                .map(str::to_string),
+        );
+    }
+    names
+}
+
+static AGENT: OnceLock<Agent> = OnceLock::new();
+
+struct Agent {
+    config: Config,
+    fingerprints: Mutex<HashMap<Vec<u8>, String>>,
+}
+
+impl Agent {
+    fn fingerprint(&self, blob: &[u8]) -> String {
+        if let Some(fingerprint) = self.fingerprints.lock().unwrap().get(blob) {
+            return fingerprint.clone();
+        }
+        let fingerprint = ssh_proxy::key_fingerprint(&key_line(blob))
+            .unwrap_or_else(|| "unknown key".to_string());
+        self.fingerprints
+            .lock()
+            .unwrap()
+            .insert(blob.to_vec(), fingerprint.clone());
+        fingerprint
+    }
+
+    fn matching_rule(&self, blob: &[u8]) -> Option<&KeyRule> {
+        let line = key_line(blob);
+        self.config.keys.iter().find(|rule| {
+            rule.key == line
+                || (ssh_proxy::is_fingerprint_pin(&rule.key) && rule.key == self.fingerprint(blob))
+        })
+    }
+
+    fn known_hosts(&self) -> &str {
+        self.config.known_hosts.as_deref().unwrap_or_default()
+    }
+
+    /// How a bound host is shown in the log: its known_hosts name when there
+    /// is a plain entry for its key, and its key fingerprint.
+    fn host_label(&self, host_key: &[u8]) -> String {
+        let fingerprint = self.fingerprint(host_key);
+        let names = fs::read_to_string(self.known_hosts())
+            .map(|contents| known_host_names(&contents, &key_line(host_key)))
+            .unwrap_or_default();
+        match names.first() {
+            Some(name) => format!("{} (host key {})", name, fingerprint),
+            None => format!("host key {}", fingerprint),
+        }
+    }
+
+    /// The allowed host name every hop of the session is bound to, or why a
+    /// key limited to `rule.hosts` may not sign `data`.
+    fn check_destination(
+        &self,
+        rule: &KeyRule,
+        blob: &[u8],
+        data: &[u8],
+        bindings: &[Binding],
+    ) -> Result<String, String> {
+        let last = bindings.last().ok_or(
+            "the session is not bound to a host (needs OpenSSH 8.9 or later in the container)",
+        )?;
+        if last.forwarding {
+            return Err("the key cannot be used through a forwarded agent".to_string());
+        }
+        match parse_userauth(data) {
+            Some((session_id, _, key)) if session_id == last.session_id && key == blob => {}
+            _ => {
+                return Err(
+                    "the data is not a login to the host the session is bound to".to_string(),
+                );
+            }
+        }
+        let mut name = String::new();
+        for binding in bindings {
+            let key = key_line(&binding.host_key);
+            name = rule
+                .hosts
+                .iter()
+                .find(|host| known_hosts_keys(self.known_hosts(), host).contains(&key))
+                .cloned()
+                .ok_or_else(|| {
+                    format!(
+                        "host key {} does not belong to {}",
+                        self.fingerprint(&binding.host_key),
+                        rule.hosts.join(", ")
+                    )
+                })?;
+        }
+        Ok(name)
+    }
+
+    /// What is being signed, for the log.
+    fn describe(&self, data: &[u8], bindings: &[Binding]) -> String {
+        if data.starts_with(b"SSHSIG") {
+            let namespace = Reader { data: &data[6..] }.string().unwrap_or_default();
+            return format!(
+                "signature in namespace {:?}",
+                String::from_utf8_lossy(namespace)
+            );
+        }
+        let host = match bindings.last() {
+            Some(binding) => self.host_label(&binding.host_key),
+            None => "no session binding".to_string(),
+        };
+        match parse_userauth(data) {
+            Some((_, user, _)) => format!("login as {} to {}", user, host),
+            None => format!("{} bytes of data ({})", data.len(), host),
+        }
+    }
+}
+
+fn failure() -> Vec<u8> {
+    vec![SSH_AGENT_FAILURE]
+}
+
+/// Relays one container connection to its own host agent connection, since
+/// session bindings belong to the connection they were made on.
+struct Session<'a> {
+    agent: &'a Agent,
+    upstream: Option<UnixStream>,
+    bindings: Vec<Binding>,
+}
+
+impl Session<'_> {
+    fn forward(&mut self, message: &[u8]) -> Result<Vec<u8>, String> {
+        if self.upstream.is_none() {
+            let path = self.agent.config.agent.as_deref().unwrap_or_default();
+            let stream = UnixStream::connect(path).map_err(|error| {
+                format!("could not reach the host agent at {}: {}", path, error)
+            })?;
+            self.upstream = Some(stream);
+        }
+        let upstream = self.upstream.as_mut().unwrap();
+        write_message(upstream, message)
+            .and_then(|()| read_message(upstream))
+            .map_err(|error| format!("host agent: {}", error))?
+            .ok_or("the host agent closed the connection".to_string())
+    }
+
+    fn identities(&mut self, request: &[u8], log: &Arc<Mutex<File>>) -> Vec<u8> {
+        let answer = match self.forward(request) {
+            Ok(answer) => answer,
+            Err(error) => {
+                log_line(log, &format!("ERROR   list keys ({})", error));
+                return failure();
+            }
+        };
+        let mut reader = Reader { data: &answer };
+        if reader.byte() != Some(SSH_AGENT_IDENTITIES_ANSWER) {
+            return failure();
+        }
+        let count = reader.u32().unwrap_or_default();
+        let mut offered = Vec::new();
+        for _ in 0..count {
+            let (Some(blob), Some(comment)) = (reader.string(), reader.string()) else {
+                return failure();
+            };
+            if self.agent.matching_rule(blob).is_some() {
+                offered.push((blob, comment));
+            }
+        }
+        let mut filtered = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        filtered.extend_from_slice(&(offered.len() as u32).to_be_bytes());
+        for (blob, comment) in offered {
+            put_string(&mut filtered, blob);
+            put_string(&mut filtered, comment);
+        }
+        filtered
+    }
+
+    fn sign(&mut self, request: &[u8], log: &Arc<Mutex<File>>) -> Vec<u8> {
+        let mut reader = Reader {
+            data: &request[1..],
+        };
+        let (Some(blob), Some(data)) = (reader.string(), reader.string()) else {
+            return failure();
+        };
+        let agent = self.agent;
+        let fingerprint = agent.fingerprint(blob);
+        let description = agent.describe(data, &self.bindings);
+        let Some(rule) = agent.matching_rule(blob) else {
+            log_line(
+                log,
+                &format!(
+                    "DENIED  {} {} (key not listed in ssh-agent.json)",
+                    fingerprint, description
+                ),
+            );
+            return failure();
+        };
+        if !rule.hosts.is_empty()
+            && let Err(reason) = agent.check_destination(rule, blob, data, &self.bindings)
+        {
+            log_line(
+                log,
+                &format!("DENIED  {} {} ({})", fingerprint, description, reason),
+            );
+            return failure();
+        }
+        match self.forward(request) {
+            Ok(response) if response.first() == Some(&SSH_AGENT_SIGN_RESPONSE) => {
+                log_line(log, &format!("SIGNED  {} {}", fingerprint, description));
+                response
+            }
+            Ok(_) => {
+                log_line(
+                    log,
+                    &format!(
+                        "ERROR   {} {} (the host agent refused)",
+                        fingerprint, description
+                    ),
+                );
+                failure()
+            }
+            Err(error) => {
+                log_line(
+                    log,
+                    &format!("ERROR   {} {} ({})", fingerprint, description, error),
+                );
+                failure()
+            }
+        }
+    }
+
+    /// Only session binding is passed on; the host agent verifies the host
+    /// key's signature over the session id before the binding is recorded.
+    fn extension(&mut self, request: &[u8], log: &Arc<Mutex<File>>) -> Vec<u8> {
+        let mut reader = Reader {
+            data: &request[1..],
+        };
+        if reader.string() != Some(SESSION_BIND) {
+            return failure();
+        }
+        let Some(binding) = parse_session_bind(reader.data) else {
+            return failure();
+        };
+        match self.forward(request) {
+            Ok(response) if response.first() == Some(&SSH_AGENT_SUCCESS) => {
+                self.bindings.push(binding);
+                response
+            }
+            Ok(response) => response,
+            Err(error) => {
+                log_line(log, &format!("ERROR   session bind ({})", error));
+                failure()
+            }
+        }
+    }
+
+    fn handle(&mut self, request: &[u8], log: &Arc<Mutex<File>>) -> Vec<u8> {
+        match request[0] {
+            SSH_AGENTC_REQUEST_IDENTITIES => self.identities(request, log),
+            SSH_AGENTC_SIGN_REQUEST => self.sign(request, log),
+            SSH_AGENTC_EXTENSION => self.extension(request, log),
+            other => {
+                if let Some(operation) = mutating_operation(other) {
+                    log_line(
+                        log,
+                        &format!(
+                            "DENIED  {} (the container cannot change the agent)",
+                            operation
+                        ),
+                    );
+                }
+                failure()
+            }
+        }
+    }
+}
+
+fn handle_connection(mut stream: UnixStream, agent: &Agent, log: &Arc<Mutex<File>>) {
+    let mut session = Session {
+        agent,
+        upstream: None,
+        bindings: Vec::new(),
+    };
+    loop {
+        let request = match read_message(&mut stream) {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(error) => {
+                log_line(log, &format!("INVALID ({})", error));
+                return;
+            }
+        };
+        let response = session.handle(&request, log);
+        if write_message(&mut stream, &response).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn run(socket_path: &str, log_path: &Path, config: Config) {
+    let agent = AGENT.get_or_init(|| Agent {
+        config,
+        fingerprints: Mutex::new(HashMap::new()),
+    });
+    let path = Path::new(socket_path);
+    let log_file = proxy_log::open(log_path).unwrap_or_else(|e| {
+        eprintln!(
+            "ssh-agent-proxy: failed to open log {}: {}",
+            log_path.display(),
+            e
+        );
+        std::process::exit(1);
+    });
+    let log = Arc::new(Mutex::new(log_file));
+
+    let bound = proxy_socket::bind(path).unwrap_or_else(|e| {
+        eprintln!("ssh-agent-proxy: failed to bind {}: {}", socket_path, e);
+        std::process::exit(1);
+    });
+    let listener = bound.listener;
+
+    log_line(&log, &format!("listening on {}", socket_path));
+    log_line(
+        &log,
+        &format!(
+            "keys: {}",
+            serde_json::to_string(&agent.config.keys).unwrap_or_default()
+        ),
+    );
+
+    proxy_socket::exit_with_parent(bound.identity, Arc::clone(&log));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let log = Arc::clone(&log);
+                thread::spawn(move || handle_connection(stream, agent, &log));
+            }
+            Err(e) => {
+                log_line(&log, &format!("connection error: {}", e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn test_dir(label: &str) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "ssh-agent-proxy-test-{label}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn ed25519(seed: u8) -> Vec<u8> {
+        let mut blob = Vec::new();
+        put_string(&mut blob, b"ssh-ed25519");
+        put_string(&mut blob, &[seed; 32]);
+        blob
+    }
+
+    fn sign_request(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut message = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut message, key);
+        put_string(&mut message, data);
+        message.extend_from_slice(&0u32.to_be_bytes());
+        message
+    }
+
+    fn userauth(session_id: &[u8], user: &str, key: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        put_string(&mut data, session_id);
+        data.push(SSH_MSG_USERAUTH_REQUEST);
+        put_string(&mut data, user.as_bytes());
+        put_string(&mut data, b"ssh-connection");
+        put_string(&mut data, b"publickey");
+        data.push(1);
+        put_string(&mut data, b"ssh-ed25519");
+        put_string(&mut data, key);
+        data
+    }
+
+    fn session_bind(host_key: &[u8], session_id: &[u8], forwarding: bool) -> Vec<u8> {
+        let mut message = vec![SSH_AGENTC_EXTENSION];
+        put_string(&mut message, SESSION_BIND);
+        put_string(&mut message, host_key);
+        put_string(&mut message, session_id);
+        put_string(&mut message, b"signature");
+        message.push(forwarding as u8);
+        message
+    }
+
+    /// A host agent holding `keys` that signs anything and accepts every
+    /// session binding. Returns the message types it received.
+    fn fake_agent(dir: &Path, keys: Vec<Vec<u8>>) -> (String, Arc<Mutex<Vec<u8>>>) {
+        let path = dir.join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&received);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                while let Ok(Some(message)) = read_message(&mut stream) {
+                    seen.lock().unwrap().push(message[0]);
+                    let response = match message[0] {
+                        SSH_AGENTC_REQUEST_IDENTITIES => {
+                            let mut answer = vec![SSH_AGENT_IDENTITIES_ANSWER];
+                            answer.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+                            for (i, key) in keys.iter().enumerate() {
+                                put_string(&mut answer, key);
+                                put_string(&mut answer, format!("key{i}").as_bytes());
+                            }
+                            answer
+                        }
+                        SSH_AGENTC_SIGN_REQUEST => {
+                            let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+                            put_string(&mut response, b"signature");
+                            response
+                        }
+                        SSH_AGENTC_EXTENSION => vec![SSH_AGENT_SUCCESS],
+                        _ => failure(),
+                    };
+                    write_message(&mut stream, &response).unwrap();
+                }
+            }
+        });
+        (path.to_string_lossy().into_owned(), received)
+    }
+
+    fn test_log(dir: &Path) -> (Arc<Mutex<File>>, PathBuf) {
+        let path = dir.join("proxy.log");
+        (Arc::new(Mutex::new(proxy_log::open(&path).unwrap())), path)
+    }
+
+    fn agent(upstream: String, known_hosts: &Path, keys: Vec<KeyRule>) -> Agent {
+        Agent {
+            config: Config {
+                keys,
+                agent: Some(upstream),
+                known_hosts: Some(known_hosts.to_string_lossy().into_owned()),
+            },
+            fingerprints: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn rule(key: &[u8], hosts: &[&str]) -> KeyRule {
+        KeyRule {
+            key: key_line(key),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn only_listed_keys_are_offered() {
+        let dir = test_dir("identities");
+        let (upstream, _) = fake_agent(&dir, vec![ed25519(1), ed25519(2), ed25519(3)]);
+        let (log, _) = test_log(&dir);
+        let agent = agent(
+            upstream,
+            &dir.join("known_hosts"),
+            vec![rule(&ed25519(2), &[])],
+        );
+        let mut session = Session {
+            agent: &agent,
+            upstream: None,
+            bindings: Vec::new(),
+        };
+
+        let answer = session.handle(&[SSH_AGENTC_REQUEST_IDENTITIES], &log);
+        let mut reader = Reader { data: &answer };
+        assert_eq!(reader.byte(), Some(SSH_AGENT_IDENTITIES_ANSWER));
+        assert_eq!(reader.u32(), Some(1));
+        assert_eq!(reader.string(), Some(&ed25519(2)[..]));
+        assert_eq!(reader.string(), Some(&b"key1"[..]));
+        assert!(reader.data.is_empty());
+    }
+
+    #[test]
+    fn agent_changes_are_refused_without_reaching_the_host_agent() {
+        let dir = test_dir("mutating");
+        let (upstream, received) = fake_agent(&dir, vec![ed25519(1)]);
+        let (log, log_path) = test_log(&dir);
+        let agent = agent(
+            upstream,
+            &dir.join("known_hosts"),
+            vec![rule(&ed25519(1), &[])],
+        );
+        let mut session = Session {
+            agent: &agent,
+            upstream: None,
+            bindings: Vec::new(),
+        };
+
+        for message_type in [17, 18, 19, 22, 23, 25] {
+            assert_eq!(session.handle(&[message_type], &log), failure());
+        }
+        let mut query = vec![SSH_AGENTC_EXTENSION];
+        put_string(&mut query, b"query");
+        assert_eq!(session.handle(&query, &log), failure());
+        assert!(received.lock().unwrap().is_empty());
+        let logged = fs::read_to_string(log_path).unwrap();
+        assert!(logged.contains("DENIED  remove all identities"));
+        assert!(logged.contains("DENIED  lock"));
+    }
+
+    #[test]
+    fn unlisted_keys_cannot_sign() {
+        let dir = test_dir("unlisted");
+        let (upstream, received) = fake_agent(&dir, vec![ed25519(1), ed25519(2)]);
+        let (log, log_path) = test_log(&dir);
+        let agent = agent(
+            upstream,
+            &dir.join("known_hosts"),
+            vec![rule(&ed25519(1), &[])],
+        );
+        let mut session = Session {
+            agent: &agent,
+            upstream: None,
+            bindings: Vec::new(),
+        };
+
+        let signed = session.handle(&sign_request(&ed25519(1), b"anything"), &log);
+        assert_eq!(signed[0], SSH_AGENT_SIGN_RESPONSE);
+        assert_eq!(
+            session.handle(&sign_request(&ed25519(2), b"anything"), &log),
+            failure()
+        );
+        assert_eq!(*received.lock().unwrap(), [SSH_AGENTC_SIGN_REQUEST]);
+        let logged = fs::read_to_string(log_path).unwrap();
+        assert!(logged.contains("SIGNED  SHA256:"));
+        assert!(logged.contains("(key not listed in ssh-agent.json)"));
+    }
+
+    #[test]
+    fn host_limited_keys_sign_only_logins_to_bound_allowed_hosts() {
+        let dir = test_dir("hosts");
+        let known_hosts = dir.join("known_hosts");
+        let github = ed25519(7);
+        fs::write(&known_hosts, format!("github.com {}\n", key_line(&github))).unwrap();
+        let key = ed25519(1);
+        let (upstream, _) = fake_agent(&dir, vec![key.clone()]);
+        let (log, log_path) = test_log(&dir);
+        let agent = agent(upstream, &known_hosts, vec![rule(&key, &["github.com"])]);
+        let mut session = Session {
+            agent: &agent,
+            upstream: None,
+            bindings: Vec::new(),
+        };
+        let sign = |session: &mut Session, data: &[u8]| {
+            session.handle(&sign_request(&key, data), &log)[0] == SSH_AGENT_SIGN_RESPONSE
+        };
+
+        // Not bound yet
+        assert!(!sign(&mut session, &userauth(b"sid", "git", &key)));
+        assert_eq!(
+            session.handle(&session_bind(&github, b"sid", false), &log),
+            [SSH_AGENT_SUCCESS]
+        );
+        assert!(sign(&mut session, &userauth(b"sid", "git", &key)));
+        // Another session's login, or data that is not a login
+        assert!(!sign(&mut session, &userauth(b"other", "git", &key)));
+        assert!(!sign(&mut session, b"SSHSIG"));
+
+        session.handle(&session_bind(&ed25519(8), b"sid2", false), &log);
+        assert!(!sign(&mut session, &userauth(b"sid2", "git", &key)));
+
+        let logged = fs::read_to_string(log_path).unwrap();
+        assert!(logged.contains("login as git to github.com (host key SHA256:"));
+        assert!(logged.contains("(the session is not bound to a host"));
+        assert!(logged.contains("does not belong to github.com"));
+    }
+
+    #[test]
+    fn forwarded_sessions_cannot_use_host_limited_keys() {
+        let dir = test_dir("forwarding");
+        let known_hosts = dir.join("known_hosts");
+        let github = ed25519(7);
+        fs::write(&known_hosts, format!("github.com {}\n", key_line(&github))).unwrap();
+        let key = ed25519(1);
+        let (upstream, _) = fake_agent(&dir, vec![key.clone()]);
+        let (log, _) = test_log(&dir);
+        let agent = agent(upstream, &known_hosts, vec![rule(&key, &["github.com"])]);
+        let mut session = Session {
+            agent: &agent,
+            upstream: None,
+            bindings: Vec::new(),
+        };
+
+        session.handle(&session_bind(&github, b"sid", true), &log);
+        let response = session.handle(&sign_request(&key, &userauth(b"sid", "git", &key)), &log);
+        assert_eq!(response, failure());
+    }
+
+    #[test]
+    fn known_hosts_parsing() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFEz";
+        let output = format!(
+            "# Host github.com found: line 3\n|1|salt|hash {key}\n@revoked github.com ssh-rsa AAAA\n"
+        );
+        assert_eq!(parse_known_hosts_keys(&output), [key]);
+
+        let contents = format!(
+            "github.com,140.82.121.4 {key}\n|1|salt|hash {key}\n*.example.com {key}\n\
+             gitlab.com ssh-rsa AAAAB3\n[git.internal]:2222 {key} comment\n"
+        );
+        assert_eq!(
+            known_host_names(&contents, key),
+            ["github.com", "140.82.121.4", "[git.internal]:2222"]
+        );
+    }
+
+    #[test]
+    fn config_resolves_key_files_and_defaults() {
+        let home = test_dir("config");
+        fs::create_dir_all(home.join(".ssh")).unwrap();
+        let public = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFEz";
+        fs::write(
+            home.join(".ssh/id_ed25519.pub"),
+            format!("{public} me@laptop\n"),
+        )
+        .unwrap();
+        let mut config: Config = serde_json::from_str(
+            r#"{"keys": [{"key": "~/.ssh/id_ed25519.pub", "hosts": ["github.com"]},
+                         {"key": "SHA256:abc"}]}"#,
+        )
+        .unwrap();
+
+        config
+            .resolve(&home, Some("/tmp/agent.sock".to_string()))
+            .unwrap();
+        assert_eq!(config.keys[0].key, public);
+        assert_eq!(config.keys[1].key, "SHA256:abc");
+        assert_eq!(config.agent.as_deref(), Some("/tmp/agent.sock"));
+        assert_eq!(
+            config.known_hosts,
+            Some(home.join(".ssh/known_hosts").to_string_lossy().into_owned())
+        );
+
+        let mut missing: Config =
+            serde_json::from_str(r#"{"keys": [{"key": "~/.ssh/nope.pub"}]}"#).unwrap();
+        assert!(
+            missing
+                .resolve(&home, Some("/tmp/agent.sock".to_string()))
+                .is_err()
+        );
+        let mut no_agent: Config =
+            serde_json::from_str(r#"{"keys": [{"key": "SHA256:abc"}]}"#).unwrap();
+        assert!(
+            no_agent
+                .resolve(&home, None)
+                .unwrap_err()
+                .contains("SSH_AUTH_SOCK")
+        );
+    }
+}
@@ -1,18 +1,29 @@
 use crate::logging::log_line;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
 use std::time::Duration;
 use std::{process, thread};
 
-use crate::{proxy_log, proxy_socket};
+use crate::{git_proxy, proxy_log, proxy_socket, pty, redact, ssh_transcript};
 
 #[derive(Deserialize)]
 struct Request {
     args: Vec<String>,
+    /// Terminal size, sent by the client when it runs in a terminal.
+    #[serde(default)]
+    pty: Option<PtySize>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct PtySize {
+    cols: u16,
+    rows: u16,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -20,9 +31,130 @@ pub struct Config {
     #[serde(default)]
     pub git: Vec<String>,
     #[serde(default)]
-    pub command: Vec<String>,
+    pub command: Vec<CommandRule>,
     #[serde(default)]
-    pub host: Vec<String>,
+    pub host: Vec<HostRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forward: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transfer: Vec<TransferRule>,
+    /// Expected host keys per host name, as public keys
+    /// (`ssh-ed25519 AAAA...`) or fingerprints (`SHA256:...`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub host_keys: BTreeMap<String, Vec<String>>,
+    /// Deny every host without an entry in `host_keys`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_pinned_host_keys: bool,
+    /// Read the host user's `~/.ssh/config` (aliases, `ProxyCommand`,
+    /// identities). Ignored by default.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub use_ssh_config: bool,
+    /// `user@host` destinations that may open interactive terminal sessions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interactive: Vec<String>,
+}
+
+/// A `command` entry: either an exact `user@host command` string or a
+/// structured rule constraining each argument of a fixed program.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum CommandRule {
+    Exact(String),
+    Pattern(CommandPattern),
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct CommandPattern {
+    pub dest: String,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<ArgConstraint>,
+    /// Let shell metacharacters (`;`, `|`, `$`, globs, ...) through
+    /// unquoted. Off by default since the remote shell interprets them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_metacharacters: bool,
+    /// Record the session to `logs/ssh/` (see `claude-sandbox logs ssh`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub transcript: bool,
+}
+
+/// A `host` entry: a `user@host` destination that may run any command,
+/// either as a plain string or as an object with per-rule options.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum HostRule {
+    Dest(String),
+    Options(HostOptions),
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct HostOptions {
+    pub dest: String,
+    /// Record the session to `logs/ssh/` (see `claude-sandbox logs ssh`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub transcript: bool,
+}
+
+impl HostRule {
+    fn dest(&self) -> &str {
+        match self {
+            HostRule::Dest(dest) => dest,
+            HostRule::Options(options) => &options.dest,
+        }
+    }
+
+    fn transcript(&self) -> bool {
+        match self {
+            HostRule::Dest(_) => false,
+            HostRule::Options(options) => options.transcript,
+        }
+    }
+}
+
+/// Constraint on one positional argument of a structured command rule.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgConstraint {
+    Literal(String),
+    Glob(String),
+    /// Must match the whole argument.
+    Regex(String),
+    OneOf(Vec<String>),
+    Range {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<i64>,
+    },
+}
+
+/// A `transfer` entry: scp or rsync copies in one direction between the
+/// container and a path prefix on `dest`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TransferRule {
+    pub dest: String,
+    pub direction: TransferDirection,
+    pub path: String,
+    /// Cap on the bytes relayed in the transfer direction, protocol
+    /// overhead included. Unlimited when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+impl TransferDirection {
+    fn as_str(self) -> &'static str {
+        match self {
+            TransferDirection::Upload => "upload",
+            TransferDirection::Download => "download",
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -41,24 +173,70 @@ struct ParsedRequest {
 
 const GIT_SERVICES: &[&str] = &["git-receive-pack", "git-upload-pack", "git-upload-archive"];
 
+/// Access a git rule grants, written as a `:read` or `:write` suffix on the
+/// entry. Entries without a suffix grant write, which includes read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GitAccess {
+    Read,
+    Write,
+}
+
+impl GitAccess {
+    fn for_service(service: &str) -> GitAccess {
+        if service == "git-receive-pack" {
+            GitAccess::Write
+        } else {
+            GitAccess::Read
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            GitAccess::Read => "read",
+            GitAccess::Write => "write",
+        }
+    }
+}
+
 const FRAME_EXIT: u8 = 0;
 const FRAME_STDOUT: u8 = 1;
 const FRAME_STDERR: u8 = 2;
+// Client to proxy, interactive sessions only
+const FRAME_STDIN: u8 = 3;
+const FRAME_RESIZE: u8 = 4;
 const MAX_FRAME: usize = 65536;
+/// A partial line is redacted and sent once this much is held back, or
+/// once the remote side pauses for `PARTIAL_LINE_IDLE`, so prompts and
+/// progress output are not stalled waiting for a newline.
+const PARTIAL_LINE_BYTES: usize = 4096;
+const PARTIAL_LINE_IDLE: Duration = Duration::from_millis(100);
+/// Interactive sessions flush sooner, so typed characters echo without lag.
+const INTERACTIVE_PARTIAL_LINE_IDLE: Duration = Duration::from_millis(20);
 
 pub fn default_config() -> Config {
     Config {
         git: vec![],
         command: vec![],
         host: vec![],
+        forward: vec![],
+        transfer: vec![],
+        host_keys: BTreeMap::new(),
+        require_pinned_host_keys: false,
+        use_ssh_config: false,
+        interactive: vec![],
     }
 }
 
 pub fn is_empty(config: &Config) -> bool {
-    config.git.is_empty() && config.command.is_empty() && config.host.is_empty()
+    config.git.is_empty()
+        && config.command.is_empty()
+        && config.host.is_empty()
+        && config.forward.is_empty()
+        && config.transfer.is_empty()
+        && config.interactive.is_empty()
 }
 
-fn glob_match(pattern: &str, text: &str) -> bool {
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
     let p = pattern.as_bytes();
     let t = text.as_bytes();
     let mut pi = 0;
@@ -90,6 +268,14 @@ fn glob_match(pattern: &str, text: &str) -> bool {
     pi == p.len()
 }
 
+fn parse_destination(dest: &str) -> Option<(String, String)> {
+    let (user, host) = dest.split_once('@')?;
+    if user.is_empty() || host.is_empty() {
+        return None;
+    }
+    Some((user.to_string(), host.to_string()))
+}
+
 fn parse_request(args: &[String]) -> Result<ParsedRequest, String> {
     let usage = "expected format: ssh user@host command [args...]";
 
@@ -117,19 +303,15 @@ fn parse_request(args: &[String]) -> Result<ParsedRequest, String> {
     }
 
     let dest = &args[0];
-    let at_pos = dest
-        .find('@')
-        .ok_or(format!("destination must be user@host. {}", usage))?;
-    let user = dest[..at_pos].to_string();
-    let host = dest[at_pos + 1..].to_string();
-
-    if user.is_empty() || host.is_empty() {
-        return Err(format!("destination must be user@host. {}", usage));
-    }
+    let (user, host) =
+        parse_destination(dest).ok_or(format!("destination must be user@host. {}", usage))?;
 
     let command = args[1..].join(" ");
     if command.is_empty() {
-        return Err(format!("interactive sessions are not supported. {}", usage));
+        return Err(format!(
+            "interactive sessions need an interactive rule in ssh-proxy.json. {}",
+            usage
+        ));
     }
 
     Ok(ParsedRequest {
@@ -194,71 +376,695 @@ fn extract_repo(command: &str) -> Option<String> {
     parse_git_command(command).map(|(_, repo)| repo)
 }
 
-fn parse_git_entry(entry: &str) -> (&str, Option<&str>) {
+fn parse_git_entry(entry: &str) -> (&str, Option<&str>, GitAccess) {
+    let (entry, access) = if let Some(rest) = entry.strip_suffix(":read") {
+        (rest, GitAccess::Read)
+    } else if let Some(rest) = entry.strip_suffix(":write") {
+        (rest, GitAccess::Write)
+    } else {
+        (entry, GitAccess::Write)
+    };
     match entry.find('/') {
         Some(pos) => (
             &entry[..pos],
             Some(entry[pos..].strip_prefix('/').unwrap_or(&entry[pos..])),
+            access,
         ),
-        None => (entry, None),
+        None => (entry, None, access),
     }
 }
 
+/// Access granted to a git request by the first rule whose host and repo
+/// patterns match it, or `None` if no rule matches or it is not git.
+fn matching_git_access(req: &ParsedRequest, rules: &[String]) -> Option<GitAccess> {
+    if req.user != "git" {
+        return None;
+    }
+    let (_, repo) = parse_git_command(&req.command)?;
+
+    let mut granted = None;
+    for entry in rules {
+        let (host_pattern, repo_pattern, access) = parse_git_entry(entry);
+
+        if !glob_match(host_pattern, &req.host) {
+            continue;
+        }
+        if repo_pattern.is_some_and(|pattern| !glob_match(pattern, &repo)) {
+            continue;
+        }
+        if access == GitAccess::Write {
+            return Some(access);
+        }
+        granted = Some(access);
+    }
+
+    granted
+}
+
 fn check_git_rules(req: &ParsedRequest, rules: &[String]) -> bool {
+    let Some((service, _)) = parse_git_command(&req.command) else {
+        return false;
+    };
+    match matching_git_access(req, rules) {
+        Some(GitAccess::Write) => true,
+        Some(GitAccess::Read) => GitAccess::for_service(service) == GitAccess::Read,
+        None => false,
+    }
+}
+
+/// The access a git protocol invocation needs, for the proxy log.
+fn requested_git_access(args: &[String]) -> Option<GitAccess> {
+    let req = parse_request(args).ok()?;
     if req.user != "git" {
+        return None;
+    }
+    parse_git_command(&req.command).map(|(service, _)| GitAccess::for_service(service))
+}
+
+/// Characters the remote shell acts on when they appear unquoted.
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '&', '|', '<', '>', '(', ')', '$', '`', '*', '?', '[', ']', '{', '}', '~', '#', '!', '\n',
+    '\r',
+];
+
+/// Split a remote command into words the way a POSIX shell would, handling
+/// single quotes, double quotes and backslash escapes. Unless
+/// `allow_metacharacters` is set, any character the shell would act on
+/// (outside single quotes, or `$`/`` ` `` inside double quotes) is an error.
+fn shell_words(command: &str, allow_metacharacters: bool) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c @ ('$' | '`')) if !allow_metacharacters => {
+                            return Err(format!("shell metacharacter '{}' in command", c));
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('\n') => {}
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash in command".to_string()),
+                }
+            }
+            c if SHELL_METACHARACTERS.contains(&c) && !allow_metacharacters => {
+                return Err(format!("shell metacharacter {:?} in command", c));
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn arg_matches(constraint: &ArgConstraint, arg: &str) -> bool {
+    match constraint {
+        ArgConstraint::Literal(literal) => arg == literal,
+        ArgConstraint::Glob(pattern) => glob_match(pattern, arg),
+        ArgConstraint::Regex(pattern) => {
+            regex::Regex::new(&format!("^(?:{})$", pattern)).is_ok_and(|regex| regex.is_match(arg))
+        }
+        ArgConstraint::OneOf(values) => values.iter().any(|value| value == arg),
+        ArgConstraint::Range { min, max } => arg
+            .parse::<i64>()
+            .is_ok_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)),
+    }
+}
+
+fn pattern_matches(req: &ParsedRequest, pattern: &CommandPattern) -> bool {
+    if req.dest != pattern.dest {
         return false;
     }
-    let Some((_, repo)) = parse_git_command(&req.command) else {
+    let Ok(words) = shell_words(&req.command, pattern.allow_metacharacters) else {
         return false;
     };
+    let Some((program, args)) = words.split_first() else {
+        return false;
+    };
+    *program == pattern.program
+        && args.len() == pattern.args.len()
+        && pattern
+            .args
+            .iter()
+            .zip(args)
+            .all(|(constraint, arg)| arg_matches(constraint, arg))
+}
 
-    for entry in rules {
-        let (host_pattern, repo_pattern) = parse_git_entry(entry);
-
-        if !glob_match(host_pattern, &req.host) {
+/// Problems with structured command rules that would make them never
+/// match, reported when the proxy starts.
+fn command_rule_errors(rules: &[CommandRule]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for rule in rules {
+        let CommandRule::Pattern(pattern) = rule else {
             continue;
+        };
+        for constraint in &pattern.args {
+            if let ArgConstraint::Regex(regex) = constraint
+                && let Err(e) = regex::Regex::new(&format!("^(?:{})$", regex))
+            {
+                errors.push(format!(
+                    "{} {}: invalid regex {:?}: {}",
+                    pattern.dest, pattern.program, regex, e
+                ));
+            }
         }
+    }
+    errors
+}
+
+fn check_command_rules(req: &ParsedRequest, rules: &[CommandRule]) -> bool {
+    let full = format!("{} {}", req.dest, req.command);
+    rules.iter().any(|rule| match rule {
+        CommandRule::Exact(exact) => *exact == full,
+        CommandRule::Pattern(pattern) => pattern_matches(req, pattern),
+    })
+}
 
-        if let Some(pattern) = repo_pattern {
-            if glob_match(pattern, &repo) {
-                return true;
+/// A remote scp or rsync server invocation, reduced to what transfer rules
+/// constrain.
+#[derive(Debug, PartialEq)]
+struct Transfer {
+    direction: TransferDirection,
+    paths: Vec<String>,
+}
+
+/// `scp -t`/`scp -f` as run by `scp -O` on the remote side. Only the flags
+/// scp itself passes are accepted.
+fn parse_scp_command(words: &[String]) -> Option<Transfer> {
+    let mut direction = None;
+    let mut rest = &words[1..];
+    while let Some(word) = rest.first() {
+        if word == "--" {
+            rest = &rest[1..];
+            break;
+        }
+        let Some(flags) = word.strip_prefix('-') else {
+            break;
+        };
+        if flags.is_empty() {
+            return None;
+        }
+        for flag in flags.chars() {
+            match flag {
+                't' | 'f' => {
+                    let this = if flag == 't' {
+                        TransferDirection::Upload
+                    } else {
+                        TransferDirection::Download
+                    };
+                    if direction.replace(this).is_some_and(|other| other != this) {
+                        return None;
+                    }
+                }
+                'd' | 'p' | 'r' | 'v' => {}
+                _ => return None,
+            }
+        }
+        rest = &rest[1..];
+    }
+
+    match rest {
+        [path] => Some(Transfer {
+            direction: direction?,
+            paths: vec![path.clone()],
+        }),
+        _ => None,
+    }
+}
+
+/// Long options an rsync client may pass to `rsync --server` that neither
+/// name extra remote paths nor run anything.
+const RSYNC_SERVER_OPTIONS: &[&str] = &[
+    "--sender",
+    "--delete",
+    "--delete-before",
+    "--delete-during",
+    "--delete-delay",
+    "--delete-after",
+    "--partial",
+    "--inplace",
+    "--append",
+    "--append-verify",
+    "--size-only",
+    "--ignore-times",
+    "--checksum",
+    "--existing",
+    "--ignore-existing",
+    "--numeric-ids",
+    "--safe-links",
+    "--no-inc-recursive",
+    "--no-i-r",
+    "--force",
+];
+
+const RSYNC_SERVER_VALUE_OPTIONS: &[&str] = &[
+    "--timeout=",
+    "--bwlimit=",
+    "--max-size=",
+    "--min-size=",
+    "--compress-level=",
+    "--modify-window=",
+];
+
+/// Short rsync options that let a transfer leave the path prefix through a
+/// symlink. The prefix check is lexical, so an upload must not create
+/// symlinks (`l`) or follow them on the receiver (`K`), and neither side
+/// may copy what a symlink points to (`L`, `k`).
+const RSYNC_UPLOAD_SYMLINK_FLAGS: &[char] = &['l', 'L', 'k', 'K'];
+const RSYNC_DOWNLOAD_SYMLINK_FLAGS: &[char] = &['L', 'k'];
+
+/// `rsync --server [--sender] <flags> . <path>...`, the remote half of an
+/// rsync over ssh.
+fn parse_rsync_command(words: &[String]) -> Option<Transfer> {
+    if words.get(1).map(String::as_str) != Some("--server") {
+        return None;
+    }
+    let mut direction = TransferDirection::Upload;
+    let mut short_options = String::new();
+    let mut rest = &words[2..];
+    while let Some(word) = rest.first() {
+        if word == "." {
+            rest = &rest[1..];
+            break;
+        }
+        if word == "--sender" {
+            direction = TransferDirection::Download;
+        } else if word.starts_with("--") {
+            let allowed = RSYNC_SERVER_OPTIONS.contains(&word.as_str())
+                || RSYNC_SERVER_VALUE_OPTIONS.iter().any(|prefix| {
+                    word.strip_prefix(prefix).is_some_and(|value| {
+                        !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric())
+                    })
+                });
+            if !allowed {
+                return None;
             }
         } else {
-            return true;
+            // Compact short options; anything after `e` is the capability
+            // string. `-s` would move the paths out of the command line.
+            let flags = word.strip_prefix('-')?;
+            let options = flags.split_once('e').map_or(flags, |(options, _)| options);
+            if flags.is_empty()
+                || options.contains('s')
+                || !flags.chars().all(|c| c.is_ascii_alphabetic() || c == '.')
+            {
+                return None;
+            }
+            short_options.push_str(options);
         }
+        rest = &rest[1..];
+    }
+
+    let symlink_flags = match direction {
+        TransferDirection::Upload => RSYNC_UPLOAD_SYMLINK_FLAGS,
+        TransferDirection::Download => RSYNC_DOWNLOAD_SYMLINK_FLAGS,
+    };
+    if short_options.contains(symlink_flags)
+        || rest.is_empty()
+        || rest.iter().any(|path| path.starts_with('-'))
+    {
+        return None;
     }
+    Some(Transfer {
+        direction,
+        paths: rest.to_vec(),
+    })
+}
 
-    false
+fn parse_transfer_command(command: &str) -> Option<Transfer> {
+    let words = shell_words(command, false).ok()?;
+    match words.first()?.as_str() {
+        "scp" => parse_scp_command(&words),
+        "rsync" => parse_rsync_command(&words),
+        _ => None,
+    }
 }
 
-fn check_command_rules(req: &ParsedRequest, rules: &[String]) -> bool {
-    let full = format!("{} {}", req.dest, req.command);
-    rules.contains(&full)
+/// Whether `path` names `prefix` or something below it, without `..`
+/// components that could climb back out.
+fn is_under_prefix(path: &str, prefix: &str) -> bool {
+    if path.split('/').any(|component| component == "..") {
+        return false;
+    }
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_end_matches('/');
+    if prefix.is_empty() {
+        return path.starts_with('/') || path.is_empty();
+    }
+    path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn matching_transfer_rule<'a>(
+    req: &ParsedRequest,
+    rules: &'a [TransferRule],
+) -> Option<&'a TransferRule> {
+    let transfer = parse_transfer_command(&req.command)?;
+    rules.iter().find(|rule| {
+        rule.dest == req.dest
+            && rule.direction == transfer.direction
+            && transfer
+                .paths
+                .iter()
+                .all(|path| is_under_prefix(path, &rule.path))
+    })
+}
+
+fn check_host_rules(req: &ParsedRequest, rules: &[HostRule]) -> bool {
+    rules.iter().any(|rule| rule.dest() == req.dest)
 }
 
-fn check_host_rules(req: &ParsedRequest, rules: &[String]) -> bool {
-    rules.contains(&req.dest)
+/// Whether an allowed request should be recorded: any matching `host` or
+/// structured `command` rule that asks for a transcript is enough.
+fn wants_transcript(req: &ParsedRequest, config: &Config) -> bool {
+    config
+        .host
+        .iter()
+        .any(|rule| rule.transcript() && rule.dest() == req.dest)
+        || config.command.iter().any(|rule| match rule {
+            CommandRule::Pattern(pattern) => pattern.transcript && pattern_matches(req, pattern),
+            CommandRule::Exact(_) => false,
+        })
 }
 
 fn check_allowed(args: &[String], config: &Config) -> Result<(), String> {
     let req = parse_request(args)?;
 
+    if config.require_pinned_host_keys && !config.host_keys.contains_key(&req.host) {
+        return Err(format!(
+            "denied: {} has no pinned host key (ask the user to add it to host_keys in ssh-proxy.json)",
+            req.host
+        ));
+    }
+
     if check_git_rules(&req, &config.git) {
         return Ok(());
     }
     if check_command_rules(&req, &config.command) {
         return Ok(());
     }
+    if matching_transfer_rule(&req, &config.transfer).is_some() {
+        return Ok(());
+    }
     if check_host_rules(&req, &config.host) {
         return Ok(());
     }
 
+    if matching_git_access(&req, &config.git) == Some(GitAccess::Read) {
+        return Err(format!(
+            "denied: {} {} (ssh-proxy.json only grants read access to this repository; \
+             ask the user to add a :write git rule to allow pushing)",
+            req.dest, req.command
+        ));
+    }
+
     Err(format!(
         "denied: {} {} (ask the user to update ssh-proxy.json to allow this command)",
         req.dest, req.command
     ))
 }
 
+/// What ssh prints when the server's key is unknown or does not match.
+const HOST_KEY_FAILURE: &[u8] = b"Host key verification failed";
+
+/// How the proxy runs the host ssh: which config it reads and which keys it
+/// trusts for each host.
+struct HostKeyPolicy {
+    pins: BTreeMap<String, Vec<String>>,
+    use_ssh_config: bool,
+    /// Private directory holding a generated known_hosts file per pinned host.
+    dir: PathBuf,
+    resolved: Mutex<HashMap<String, PathBuf>>,
+}
+
+pub(crate) fn is_fingerprint_pin(pin: &str) -> bool {
+    pin.starts_with("SHA256:")
+}
+
+/// The `type base64` part of a public key pin, without any comment.
+pub(crate) fn public_key_pin(pin: &str) -> Option<String> {
+    let mut fields = pin.split_whitespace();
+    let (key_type, key) = (fields.next()?, fields.next()?);
+    let valid = (key_type.starts_with("ssh-")
+        || key_type.starts_with("ecdsa-")
+        || key_type.starts_with("sk-"))
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='));
+    valid.then(|| format!("{} {}", key_type, key))
+}
+
+/// Problems with `host_keys` entries, reported when the proxy starts.
+fn host_key_errors(pins: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (host, keys) in pins {
+        if !is_valid_forward_host(host) {
+            errors.push(format!("{:?}: invalid host name", host));
+        }
+        if keys.is_empty() {
+            errors.push(format!("{}: no keys listed", host));
+        }
+        for key in keys {
+            if !is_fingerprint_pin(key) && public_key_pin(key).is_none() {
+                errors.push(format!(
+                    "{}: {:?} is neither a public key nor a SHA256: fingerprint",
+                    host, key
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// The fingerprint in one line of `ssh-keygen -l` output
+/// (`256 SHA256:... comment (ED25519)`).
+fn parse_keygen_fingerprint(output: &str) -> Option<&str> {
+    output
+        .split_whitespace()
+        .nth(1)
+        .filter(|field| is_fingerprint_pin(field))
+}
+
+pub(crate) fn key_fingerprint(key: &str) -> Option<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(["-l", "-E", "sha256", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let _ = child
+        .stdin
+        .take()?
+        .write_all(format!("{}\n", key).as_bytes());
+    let output = child.wait_with_output().ok()?;
+    parse_keygen_fingerprint(&String::from_utf8_lossy(&output.stdout)).map(str::to_string)
+}
+
+impl HostKeyPolicy {
+    fn new(config: &Config, dir: PathBuf) -> HostKeyPolicy {
+        HostKeyPolicy {
+            pins: config.host_keys.clone(),
+            use_ssh_config: config.use_ssh_config,
+            dir,
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Known_hosts lines for a pinned host. Public key pins are used as
+    /// they are; fingerprint pins select among the keys the server offers
+    /// to `ssh-keyscan`, so a server without the pinned key fails
+    /// verification later in ssh itself.
+    fn known_hosts_lines(host: &str, pins: &[String]) -> Vec<String> {
+        let mut lines: Vec<String> = pins
+            .iter()
+            .filter_map(|pin| public_key_pin(pin))
+            .map(|key| format!("{} {}", host, key))
+            .collect();
+
+        let fingerprints: Vec<&String> =
+            pins.iter().filter(|pin| is_fingerprint_pin(pin)).collect();
+        if fingerprints.is_empty() {
+            return lines;
+        }
+        let Ok(output) = Command::new("ssh-keyscan")
+            .args(["-T", "5", host])
+            .stderr(Stdio::null())
+            .output()
+        else {
+            return lines;
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((_, key)) = line.split_once(' ') else {
+                continue;
+            };
+            let Some(key) = public_key_pin(key) else {
+                continue;
+            };
+            if key_fingerprint(&key).is_some_and(|fingerprint| fingerprints.contains(&&fingerprint))
+            {
+                lines.push(format!("{} {}", host, key));
+            }
+        }
+        lines
+    }
+
+    /// The generated known_hosts file for a pinned host. Fingerprint pins
+    /// are resolved again until at least one key matches.
+    fn known_hosts_file(&self, host: &str, pins: &[String]) -> Result<PathBuf, String> {
+        if let Some(path) = self.resolved.lock().unwrap().get(host) {
+            return Ok(path.clone());
+        }
+        if !is_valid_forward_host(host) {
+            return Err(format!("invalid pinned host name {:?}", host));
+        }
+        let lines = Self::known_hosts_lines(host, pins);
+        let path = self.dir.join(format!("known_hosts-{}", host));
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        std::fs::write(&path, contents)
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        if !lines.is_empty() {
+            self.resolved
+                .lock()
+                .unwrap()
+                .insert(host.to_string(), path.clone());
+        }
+        Ok(path)
+    }
+
+    /// `/usr/bin/ssh` set up for `host`: strict host key checking always,
+    /// against only the pinned keys when the host has any, and without the
+    /// user's ssh config unless the project opts in.
+    fn ssh_command(&self, host: &str) -> Result<Command, String> {
+        let mut command = Command::new("/usr/bin/ssh");
+        if !self.use_ssh_config {
+            command.args(["-F", "none"]);
+        }
+        command.args(["-o", "StrictHostKeyChecking=yes"]);
+        if let Some(pins) = self.pins.get(host) {
+            let known_hosts = self.known_hosts_file(host, pins)?;
+            command
+                .arg("-o")
+                .arg(format!("UserKnownHostsFile={}", known_hosts.display()))
+                .args(["-o", "GlobalKnownHostsFile=/dev/null"])
+                .arg("-o")
+                .arg(format!("HostKeyAlias={}", host))
+                .args(["-o", "UpdateHostKeys=no", "-o", "CheckHostIP=no"]);
+        }
+        Ok(command)
+    }
+}
+
+/// Reader that notes whether ssh reported a host key verification failure
+/// anywhere in the stream.
+struct HostKeySniffer<R> {
+    inner: R,
+    tail: Vec<u8>,
+    failed: Arc<AtomicBool>,
+}
+
+impl<R: Read> Read for HostKeySniffer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.tail.extend_from_slice(&buf[..n]);
+        if self
+            .tail
+            .windows(HOST_KEY_FAILURE.len())
+            .any(|window| window == HOST_KEY_FAILURE)
+        {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        let keep = self.tail.len().min(HOST_KEY_FAILURE.len() - 1);
+        self.tail.drain(..self.tail.len() - keep);
+        Ok(n)
+    }
+}
+
+fn host_key_mismatch_message(host: &str) -> String {
+    format!(
+        "ssh-proxy: host key verification failed for {}: the server's key is not one pinned \
+         in ssh-proxy.json (or known to the host). This can mean the key changed or the \
+         connection is being intercepted; ask the user to verify the key before updating host_keys\n",
+        host
+    )
+}
+
+/// Check a request for an interactive session: a lone `user@host` listed
+/// in the `interactive` rules, from a client running in a terminal.
+fn check_interactive(args: &[String], has_pty: bool, config: &Config) -> Result<String, String> {
+    let [dest] = args else {
+        return Err("interactive sessions take only a user@host destination".to_string());
+    };
+    if dest.starts_with('-') {
+        return Err(format!("ssh flags are not supported (got {})", dest));
+    }
+    let (_, host) = parse_destination(dest).ok_or("destination must be user@host".to_string())?;
+    if config.require_pinned_host_keys && !config.host_keys.contains_key(&host) {
+        return Err(format!(
+            "denied: {} has no pinned host key (ask the user to add it to host_keys in ssh-proxy.json)",
+            host
+        ));
+    }
+    if !config.interactive.contains(dest) {
+        return Err(format!(
+            "denied: interactive session to {} (ask the user to add it to interactive in ssh-proxy.json)",
+            dest
+        ));
+    }
+    if !has_pty {
+        return Err(
+            "interactive sessions need a terminal; pass a command to run instead".to_string(),
+        );
+    }
+    Ok(host)
+}
+
 fn read_handshake_line(stream: &mut impl Read) -> Option<String> {
     let mut line = Vec::new();
     let mut byte = [0u8; 1];
@@ -288,9 +1094,20 @@ fn write_frame(writer: &Mutex<impl Write>, frame_type: u8, data: &[u8]) -> std::
     w.flush()
 }
 
+fn deny(stream: &mut std::os::unix::net::UnixStream, reason: String) {
+    let resp = HandshakeResponse {
+        status: "denied".to_string(),
+        reason: Some(reason),
+    };
+    let _ = serde_json::to_writer(&mut *stream, &resp);
+    let _ = stream.write_all(b"\n");
+}
+
 fn handle_connection(
     mut stream: std::os::unix::net::UnixStream,
     config: &Config,
+    policy: &HostKeyPolicy,
+    transcripts: &Path,
     log: &Arc<Mutex<File>>,
 ) {
     let line = match read_handshake_line(&mut stream) {
@@ -312,10 +1129,18 @@ fn handle_connection(
         }
     };
 
+    if req.args.len() == 1 && !req.args[0].starts_with('-') {
+        handle_interactive(stream, &req, config, policy, transcripts, log);
+        return;
+    }
+
     let cmd_line = req.args.join(" ");
+    let access = requested_git_access(&req.args)
+        .map(|access| format!(" ({})", access.as_str()))
+        .unwrap_or_default();
 
     if let Err(reason) = check_allowed(&req.args, config) {
-        log_line(log, &format!("DENIED  {}", cmd_line));
+        log_line(log, &format!("DENIED  {}{}", cmd_line, access));
         let resp = HandshakeResponse {
             status: "denied".to_string(),
             reason: Some(reason),
@@ -325,7 +1150,38 @@ fn handle_connection(
         return;
     }
 
-    log_line(log, &format!("ALLOWED {}", cmd_line));
+    let transfer = parse_request(&req.args)
+        .ok()
+        .and_then(|parsed| matching_transfer_rule(&parsed, &config.transfer).cloned());
+    let access = match &transfer {
+        Some(rule) => format!(" ({})", rule.direction.as_str()),
+        None => access,
+    };
+
+    let (dest, command) = parse_request(&req.args)
+        .map(|parsed| (parsed.dest, parsed.command))
+        .unwrap_or_default();
+    let transcript =
+        if parse_request(&req.args).is_ok_and(|parsed| wants_transcript(&parsed, config)) {
+            match ssh_transcript::Transcript::create(transcripts, &dest, &command, None) {
+                Ok(transcript) => Some(Arc::new(transcript)),
+                Err(e) => {
+                    log_line(
+                        log,
+                        &format!("ERROR   {} (failed to start transcript: {})", cmd_line, e),
+                    );
+                    deny(&mut stream, format!("failed to start transcript: {}", e));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+    let session = transcript
+        .as_ref()
+        .map(|transcript| format!(" (session {})", transcript.id))
+        .unwrap_or_default();
+    log_line(log, &format!("ALLOWED {}{}{}", cmd_line, access, session));
 
     let resp = HandshakeResponse {
         status: "ok".to_string(),
@@ -334,13 +1190,19 @@ fn handle_connection(
     let _ = serde_json::to_writer(&mut stream, &resp);
     let _ = stream.write_all(b"\n");
 
-    let mut child = match Command::new("/usr/bin/ssh")
-        .args(&req.args)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
+    let host = parse_request(&req.args)
+        .map(|parsed| parsed.host)
+        .unwrap_or_default();
+    let spawned = policy.ssh_command(&host).and_then(|mut command| {
+        command
+            .args(&req.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())
+    });
+    let mut child = match spawned {
         Ok(c) => c,
         Err(e) => {
             log_line(log, &format!("ERROR   {} ({})", cmd_line, e));
@@ -350,18 +1212,41 @@ fn handle_connection(
                 format!("ssh-proxy: failed to spawn ssh: {}\n", e).as_bytes(),
             );
             let _ = write_frame(&Mutex::new(&stream), FRAME_EXIT, &1i32.to_be_bytes());
+            if let Some(transcript) = &transcript {
+                transcript.exit(1);
+            }
             return;
         }
     };
 
     let ssh_stdin = child.stdin.take().unwrap();
     let ssh_stdout = child.stdout.take().unwrap();
-    let ssh_stderr = child.stderr.take().unwrap();
+    let host_key_failed = Arc::new(AtomicBool::new(false));
+    let ssh_stderr = HostKeySniffer {
+        inner: child.stderr.take().unwrap(),
+        tail: Vec::new(),
+        failed: Arc::clone(&host_key_failed),
+    };
 
     let write_stream = stream.try_clone().expect("failed to clone socket");
     let writer = Arc::new(Mutex::new(write_stream));
 
+    // Transfers stop relaying, and the ssh child is killed, once more than
+    // `max_bytes` flow in the transfer direction
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let limit = |direction| {
+        transfer
+            .as_ref()
+            .filter(|rule| rule.direction == direction)
+            .and_then(|rule| rule.max_bytes)
+    };
+    let upload_limit = limit(TransferDirection::Upload);
+    let download_limit = limit(TransferDirection::Download);
+    let ssh_stdout = limit_reader(ssh_stdout, download_limit, &exceeded);
+
     let read_stream = stream.try_clone().expect("failed to clone socket");
+    let read_stream = limit_reader(read_stream, upload_limit, &exceeded);
+    let transcript_a = transcript.clone();
     let thread_a = thread::spawn(move || {
         let mut reader = read_stream;
         let mut stdin = ssh_stdin;
@@ -370,6 +1255,9 @@ fn handle_connection(
             match reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
+                    if let Some(transcript) = &transcript_a {
+                        transcript.record(ssh_transcript::Stream::Stdin, &buf[..n]);
+                    }
                     if stdin.write_all(&buf[..n]).is_err() {
                         break;
                     }
@@ -380,52 +1268,629 @@ fn handle_connection(
         drop(stdin);
     });
 
+    // Git protocol sessions carry pack data and transfers carry file
+    // contents, so both are passed through untouched
+    let redact_output = transfer.is_none()
+        && parse_request(&req.args)
+            .map(|parsed| parse_git_command(&parsed.command).is_none())
+            .unwrap_or(true);
+
     let writer_b = Arc::clone(&writer);
+    let record_b = transcript
+        .clone()
+        .map(|transcript| (transcript, ssh_transcript::Stream::Stdout));
     let thread_b = thread::spawn(move || {
-        let mut stdout = ssh_stdout;
-        let mut buf = [0u8; MAX_FRAME];
+        forward_output(
+            ssh_stdout,
+            &writer_b,
+            FRAME_STDOUT,
+            redact_output,
+            PARTIAL_LINE_IDLE,
+            record_b,
+        )
+    });
+
+    let writer_c = Arc::clone(&writer);
+    let record_c = transcript
+        .clone()
+        .map(|transcript| (transcript, ssh_transcript::Stream::Stderr));
+    let thread_c = thread::spawn(move || {
+        forward_output(
+            ssh_stderr,
+            &writer_c,
+            FRAME_STDERR,
+            redact_output,
+            PARTIAL_LINE_IDLE,
+            record_c,
+        )
+    });
+
+    let status = if upload_limit.is_some() || download_limit.is_some() {
         loop {
-            match stdout.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    if write_frame(&writer_b, FRAME_STDOUT, &buf[..n]).is_err() {
-                        break;
+            if let Some(status) = child.try_wait().unwrap() {
+                break status;
+            }
+            if exceeded.load(Ordering::Relaxed) {
+                let _ = child.kill();
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    } else {
+        child.wait().unwrap()
+    };
+    let exit_code = status.code().unwrap_or(255);
+
+    let redacted = thread_b.join().unwrap_or(0) + thread_c.join().unwrap_or(0);
+
+    if host_key_failed.load(Ordering::Relaxed) {
+        log_line(
+            log,
+            &format!("HOSTKEY {} (host key verification failed)", cmd_line),
+        );
+        let _ = write_frame(
+            &writer,
+            FRAME_STDERR,
+            host_key_mismatch_message(&host).as_bytes(),
+        );
+    }
+
+    if exceeded.load(Ordering::Relaxed) {
+        let max_bytes = upload_limit.or(download_limit).unwrap_or_default();
+        log_line(
+            log,
+            &format!(
+                "DENIED  {} (transfer exceeded {} bytes)",
+                cmd_line, max_bytes
+            ),
+        );
+        let _ = write_frame(
+            &writer,
+            FRAME_STDERR,
+            format!(
+                "ssh-proxy: transfer stopped after exceeding the {} byte limit in ssh-proxy.json\n",
+                max_bytes
+            )
+            .as_bytes(),
+        );
+    }
+
+    let _ = write_frame(&writer, FRAME_EXIT, &(exit_code as i32).to_be_bytes());
+    if let Some(transcript) = &transcript {
+        transcript.exit(exit_code);
+    }
+
+    drop(writer);
+    let _ = thread_a.join();
+
+    log_line(
+        log,
+        &format!("EXIT    {}{} -> {}", cmd_line, session, exit_code),
+    );
+    redact::log_redactions(log, &cmd_line, redacted);
+}
+
+/// Run an interactive session: ssh gets a terminal on the host, the client
+/// sends keystrokes and window-size changes as frames, and every byte in
+/// either direction goes to a transcript under the project's log directory.
+fn handle_interactive(
+    mut stream: std::os::unix::net::UnixStream,
+    req: &Request,
+    config: &Config,
+    policy: &HostKeyPolicy,
+    transcripts: &Path,
+    log: &Arc<Mutex<File>>,
+) {
+    let dest = req.args[0].clone();
+    let host = match check_interactive(&req.args, req.pty.is_some(), config) {
+        Ok(host) => host,
+        Err(reason) => {
+            log_line(log, &format!("DENIED  {} (interactive)", dest));
+            deny(&mut stream, reason);
+            return;
+        }
+    };
+    let size = req.pty.unwrap_or(PtySize { cols: 80, rows: 24 });
+
+    let started =
+        ssh_transcript::Transcript::create(transcripts, &dest, "", Some((size.cols, size.rows)))
+            .map_err(|e| format!("failed to start transcript: {}", e))
+            .and_then(|transcript| {
+                let pty = pty::open(size.cols, size.rows)
+                    .map_err(|e| format!("failed to open a terminal: {}", e))?;
+                let mut command = policy.ssh_command(&host)?;
+                command
+                    .arg("-tt")
+                    .arg("--")
+                    .arg(&dest)
+                    .stderr(Stdio::piped());
+                let master = pty.attach(&mut command).map_err(|e| e.to_string())?;
+                let child = command
+                    .spawn()
+                    .map_err(|e| format!("failed to spawn ssh: {}", e))?;
+                Ok((transcript, master, child))
+            });
+    let (transcript, master, mut child) = match started {
+        Ok(started) => started,
+        Err(e) => {
+            log_line(log, &format!("ERROR   {} ({})", dest, e));
+            deny(&mut stream, e);
+            return;
+        }
+    };
+    let transcript = Arc::new(transcript);
+    log_line(
+        log,
+        &format!("ALLOWED {} (interactive, session {})", dest, transcript.id),
+    );
+
+    let resp = HandshakeResponse {
+        status: "ok".to_string(),
+        reason: None,
+    };
+    let _ = serde_json::to_writer(&mut stream, &resp);
+    let _ = stream.write_all(b"\n");
+
+    let writer = Arc::new(Mutex::new(
+        stream.try_clone().expect("failed to clone socket"),
+    ));
+    let client_gone = Arc::new(AtomicBool::new(false));
+
+    let input = {
+        let mut reader = stream.try_clone().expect("failed to clone socket");
+        let mut terminal = master.try_clone().expect("failed to clone terminal");
+        let transcript = Arc::clone(&transcript);
+        let client_gone = Arc::clone(&client_gone);
+        thread::spawn(move || {
+            let mut header = [0u8; 5];
+            while reader.read_exact(&mut header).is_ok() {
+                let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+                if len > MAX_FRAME {
+                    break;
+                }
+                let mut payload = vec![0u8; len];
+                if reader.read_exact(&mut payload).is_err() {
+                    break;
+                }
+                match (header[0], payload.as_slice()) {
+                    (FRAME_STDIN, data) => {
+                        transcript.record(ssh_transcript::Stream::Stdin, data);
+                        if terminal.write_all(data).is_err() {
+                            break;
+                        }
                     }
+                    (FRAME_RESIZE, [c0, c1, r0, r1]) => {
+                        let (cols, rows) = (
+                            u16::from_be_bytes([*c0, *c1]),
+                            u16::from_be_bytes([*r0, *r1]),
+                        );
+                        transcript.resize(cols, rows);
+                        let _ = pty::resize(&terminal, cols, rows);
+                    }
+                    _ => {}
                 }
-                Err(_) => break,
+            }
+            client_gone.store(true, Ordering::Relaxed);
+        })
+    };
+
+    // Terminal output is redacted like any other output; reads end with
+    // EIO once ssh exits and the terminal closes
+    let output = {
+        let writer = Arc::clone(&writer);
+        let record = (Arc::clone(&transcript), ssh_transcript::Stream::Stdout);
+        thread::spawn(move || {
+            forward_output(
+                master,
+                &writer,
+                FRAME_STDOUT,
+                true,
+                INTERACTIVE_PARTIAL_LINE_IDLE,
+                Some(record),
+            )
+        })
+    };
+
+    let host_key_failed = Arc::new(AtomicBool::new(false));
+    let errors = {
+        let ssh_stderr = HostKeySniffer {
+            inner: child.stderr.take().unwrap(),
+            tail: Vec::new(),
+            failed: Arc::clone(&host_key_failed),
+        };
+        let writer = Arc::clone(&writer);
+        let record = (Arc::clone(&transcript), ssh_transcript::Stream::Stderr);
+        thread::spawn(move || {
+            forward_output(
+                ssh_stderr,
+                &writer,
+                FRAME_STDERR,
+                true,
+                INTERACTIVE_PARTIAL_LINE_IDLE,
+                Some(record),
+            )
+        })
+    };
+
+    let exit_code = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status.code().unwrap_or(255),
+            Ok(None) => {}
+            Err(e) => {
+                log_line(log, &format!("ERROR   {} (wait failed: {})", dest, e));
+                let _ = child.kill();
+                break 255;
             }
         }
-    });
+        if client_gone.load(Ordering::Relaxed) {
+            let _ = child.kill();
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    let redacted = output.join().unwrap_or(0) + errors.join().unwrap_or(0);
 
-    let writer_c = Arc::clone(&writer);
-    let thread_c = thread::spawn(move || {
-        let mut stderr = ssh_stderr;
-        let mut buf = [0u8; MAX_FRAME];
+    if host_key_failed.load(Ordering::Relaxed) {
+        log_line(
+            log,
+            &format!("HOSTKEY {} (host key verification failed)", dest),
+        );
+        let _ = write_frame(
+            &writer,
+            FRAME_STDERR,
+            host_key_mismatch_message(&host).as_bytes(),
+        );
+    }
+    let _ = write_frame(&writer, FRAME_EXIT, &exit_code.to_be_bytes());
+    transcript.exit(exit_code);
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    let _ = input.join();
+
+    log_line(
+        log,
+        &format!(
+            "EXIT    {} (session {}) -> {}",
+            dest, transcript.id, exit_code
+        ),
+    );
+    redact::log_redactions(log, &dest, redacted);
+}
+
+/// Reader that reports end of stream instead of passing on more than
+/// `remaining` bytes, and records that the limit was hit.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.exceeded.load(Ordering::Relaxed) {
+            return Ok(0);
+        }
+        let n = self.inner.read(buf)?;
+        if n as u64 > self.remaining {
+            self.exceeded.store(true, Ordering::Relaxed);
+            return Ok(0);
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn limit_reader<R: Read + Send + 'static>(
+    inner: R,
+    limit: Option<u64>,
+    exceeded: &Arc<AtomicBool>,
+) -> Box<dyn Read + Send> {
+    match limit {
+        Some(remaining) => Box::new(LimitedReader {
+            inner,
+            remaining,
+            exceeded: Arc::clone(exceeded),
+        }),
+        None => Box::new(inner),
+    }
+}
+
+/// Copy one output pipe of the ssh child into frames, redacting secrets
+/// line by line and flushing a partial line after `idle` without output.
+/// What the client receives is also written to `record`'s transcript.
+/// Returns the number of redactions.
+fn forward_output(
+    mut pipe: impl Read + Send,
+    writer: &Mutex<impl Write>,
+    frame_type: u8,
+    redact_output: bool,
+    idle: Duration,
+    record: Option<(Arc<ssh_transcript::Transcript>, ssh_transcript::Stream)>,
+) -> usize {
+    let send = |data: &[u8]| {
+        if let Some((transcript, stream)) = &record
+            && !data.is_empty()
+        {
+            transcript.record(*stream, data);
+        }
+        data.chunks(MAX_FRAME)
+            .all(|part| write_frame(writer, frame_type, part).is_ok())
+    };
+    let mut buf = [0u8; MAX_FRAME];
+    if !redact_output {
         loop {
-            match stderr.read(&mut buf) {
-                Ok(0) => break,
+            match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => return 0,
                 Ok(n) => {
-                    if write_frame(&writer_c, FRAME_STDERR, &buf[..n]).is_err() {
-                        break;
+                    if !send(&buf[..n]) {
+                        return 0;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut redactor = redact::StreamRedactor::new(redact::global(), PARTIAL_LINE_BYTES);
+    // Reads happen on their own thread so a held-back partial line can be
+    // flushed when the remote side goes quiet
+    thread::scope(|scope| {
+        let (chunks, received) = mpsc::channel();
+        scope.spawn(move || {
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if chunks.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        loop {
+            let data = match received.recv_timeout(idle) {
+                Ok(chunk) => redactor.push(&chunk),
+                Err(mpsc::RecvTimeoutError::Timeout) if redactor.has_pending() => redactor.flush(),
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if !send(&data) {
+                return redactor.count();
+            }
+        }
+        send(&redactor.finish());
+        redactor.count()
+    })
+}
+
+/// A `forward` entry: `user@host -> target:port`, optionally followed by
+/// `as LOCAL_PORT` to pick the port the container connects to.
+#[derive(Debug, PartialEq)]
+struct Forward {
+    dest: String,
+    target_host: String,
+    target_port: u16,
+    local_port: u16,
+}
+
+impl Forward {
+    fn describe(&self) -> String {
+        format!("{} -> {}:{}", self.dest, self.target_host, self.target_port)
+    }
+}
+
+fn parse_port(port: &str) -> Result<u16, String> {
+    match port.parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!("invalid port '{}'", port)),
+        Ok(port) => Ok(port),
+    }
+}
+
+fn is_valid_forward_host(host: &str) -> bool {
+    !host.is_empty()
+        && !host.starts_with('-')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+fn parse_forward(entry: &str) -> Result<Forward, String> {
+    let usage = "expected format: user@host -> target:port [as local_port]";
+    let (dest, rest) = entry
+        .split_once("->")
+        .ok_or(format!("missing '->'. {}", usage))?;
+    let dest = dest.trim();
+    let (target, local) = match rest.trim().split_once(" as ") {
+        Some((target, local)) => (target.trim(), Some(local.trim())),
+        None => (rest.trim(), None),
+    };
+
+    let (user, host) = dest
+        .split_once('@')
+        .ok_or(format!("destination must be user@host. {}", usage))?;
+    if !is_valid_forward_host(user) || !is_valid_forward_host(host) {
+        return Err(format!("invalid destination '{}'. {}", dest, usage));
+    }
+
+    let (target_host, target_port) = target
+        .rsplit_once(':')
+        .ok_or(format!("target must be host:port. {}", usage))?;
+    if !is_valid_forward_host(target_host) {
+        return Err(format!("invalid target host '{}'", target_host));
+    }
+    let target_port = parse_port(target_port)?;
+    let local_port = match local {
+        Some(local) => parse_port(local)?,
+        None => target_port,
+    };
+
+    Ok(Forward {
+        dest: dest.to_string(),
+        target_host: target_host.to_string(),
+        target_port,
+        local_port,
+    })
+}
+
+/// Socket a forward is exposed on, next to the proxy's own socket. The
+/// container relays it to `127.0.0.1:<local_port>`.
+fn forward_socket_path(socket_path: &Path, local_port: u16) -> std::path::PathBuf {
+    socket_path.with_file_name(format!("ssh-forward-{}.sock", local_port))
+}
+
+/// Bind a socket for every valid `forward` rule and serve it on its own
+/// thread. Returns the sockets to clean up on shutdown.
+fn start_forwards(
+    socket_path: &Path,
+    config: &Config,
+    policy: &Arc<HostKeyPolicy>,
+    log: &Arc<Mutex<File>>,
+) -> Vec<proxy_socket::SocketIdentity> {
+    let mut identities = Vec::new();
+    let mut local_ports = Vec::new();
+    for entry in &config.forward {
+        let forward = match parse_forward(entry) {
+            Ok(forward) => forward,
+            Err(e) => {
+                log_line(log, &format!("ERROR   forward {:?}: {}", entry, e));
+                continue;
+            }
+        };
+        let forward_host = forward.dest.split_once('@').map_or("", |(_, host)| host);
+        if config.require_pinned_host_keys && !config.host_keys.contains_key(forward_host) {
+            log_line(
+                log,
+                &format!(
+                    "ERROR   forward {:?}: {} has no pinned host key",
+                    entry, forward_host
+                ),
+            );
+            continue;
+        }
+        if local_ports.contains(&forward.local_port) {
+            log_line(
+                log,
+                &format!(
+                    "ERROR   forward {:?}: local port {} is already forwarded (add 'as PORT')",
+                    entry, forward.local_port
+                ),
+            );
+            continue;
+        }
+        local_ports.push(forward.local_port);
+
+        let path = forward_socket_path(socket_path, forward.local_port);
+        let bound = match proxy_socket::bind(&path) {
+            Ok(bound) => bound,
+            Err(e) => {
+                log_line(
+                    log,
+                    &format!(
+                        "ERROR   forward {:?}: bind {}: {}",
+                        entry,
+                        path.display(),
+                        e
+                    ),
+                );
+                continue;
+            }
+        };
+        log_line(
+            log,
+            &format!(
+                "forwarding {} on {} (container port {})",
+                forward.describe(),
+                path.display(),
+                forward.local_port
+            ),
+        );
+        identities.push(bound.identity);
+
+        let forward = Arc::new(forward);
+        let policy = Arc::clone(policy);
+        let log = Arc::clone(log);
+        let listener = bound.listener;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let forward = Arc::clone(&forward);
+                        let policy = Arc::clone(&policy);
+                        let log = Arc::clone(&log);
+                        thread::spawn(move || handle_forward(stream, &forward, &policy, &log));
                     }
+                    Err(e) => log_line(&log, &format!("connection error: {}", e)),
                 }
-                Err(_) => break,
             }
-        }
-    });
-
-    let status = child.wait().unwrap();
-    let exit_code = status.code().unwrap_or(255);
-
-    let _ = thread_b.join();
-    let _ = thread_c.join();
+        });
+    }
+    identities
+}
 
-    let _ = write_frame(&writer, FRAME_EXIT, &(exit_code as i32).to_be_bytes());
+/// Carry one container connection through `ssh -W` to the forward target.
+/// The arguments come only from the host config, never from the container.
+fn handle_forward(
+    stream: std::os::unix::net::UnixStream,
+    forward: &Forward,
+    policy: &HostKeyPolicy,
+    log: &Arc<Mutex<File>>,
+) {
+    let description = forward.describe();
+    log_line(log, &format!("FORWARD {}", description));
+
+    let host = forward.dest.split_once('@').map_or("", |(_, host)| host);
+    let spawned = policy.ssh_command(host).and_then(|mut command| {
+        command
+            .arg("-W")
+            .arg(format!("{}:{}", forward.target_host, forward.target_port))
+            .arg("--")
+            .arg(&forward.dest)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())
+    });
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            log_line(log, &format!("ERROR   {} ({})", description, e));
+            return;
+        }
+    };
 
-    drop(writer);
-    let _ = thread_a.join();
+    let mut ssh_stdin = child.stdin.take().unwrap();
+    let mut ssh_stdout = child.stdout.take().unwrap();
+    let host_key_failed = Arc::new(AtomicBool::new(false));
+    let mut ssh_stderr = HostKeySniffer {
+        inner: child.stderr.take().unwrap(),
+        tail: Vec::new(),
+        failed: Arc::clone(&host_key_failed),
+    };
+    let errors = thread::spawn(move || std::io::copy(&mut ssh_stderr, &mut std::io::sink()));
+    let Ok(mut reader) = stream.try_clone() else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return;
+    };
+    let inbound = thread::spawn(move || {
+        let _ = std::io::copy(&mut reader, &mut ssh_stdin);
+    });
 
-    log_line(log, &format!("EXIT    {} -> {}", cmd_line, exit_code));
+    let mut writer = stream;
+    let _ = std::io::copy(&mut ssh_stdout, &mut writer);
+    let _ = writer.shutdown(std::net::Shutdown::Both);
+
+    let status = child.wait().map(|status| status.code().unwrap_or(255));
+    let _ = inbound.join();
+    let _ = errors.join();
+    if host_key_failed.load(Ordering::Relaxed) {
+        log_line(
+            log,
+            &format!("HOSTKEY {} (host key verification failed)", description),
+        );
+    }
+    log_line(
+        log,
+        &format!("EXIT    {} -> {}", description, status.unwrap_or(255)),
+    );
 }
 
 pub fn run(socket_path: &str, log_path: &Path, config: &Config) {
@@ -440,6 +1905,20 @@ pub fn run(socket_path: &str, log_path: &Path, config: &Config) {
     });
     let log = Arc::new(Mutex::new(log_file));
 
+    // Random and private, so no other local user can plant known_hosts files
+    let known_hosts = git_proxy::TemporaryDirectory::create().unwrap_or_else(|e| {
+        eprintln!(
+            "ssh-proxy: failed to create the known_hosts directory: {}",
+            e
+        );
+        std::process::exit(1);
+    });
+    let known_hosts_dir = known_hosts.path.clone();
+    let policy = Arc::new(HostKeyPolicy::new(config, known_hosts_dir.clone()));
+
+    // Bound before the main socket, which the launcher waits for
+    let forward_sockets = start_forwards(path, config, &policy, &log);
+
     let bound = proxy_socket::bind(path).unwrap_or_else(|e| {
         eprintln!("ssh-proxy: failed to bind {}: {}", socket_path, e);
         std::process::exit(1);
@@ -451,8 +1930,28 @@ pub fn run(socket_path: &str, log_path: &Path, config: &Config) {
     log_line(
         &log,
         &format!(
-            "rules: git={:?} command={:?} host={:?}",
-            config.git, config.command, config.host
+            "rules: git={:?} command={} host={} forward={:?} transfer={} interactive={:?}",
+            config.git,
+            serde_json::to_string(&config.command).unwrap_or_default(),
+            serde_json::to_string(&config.host).unwrap_or_default(),
+            config.forward,
+            serde_json::to_string(&config.transfer).unwrap_or_default(),
+            config.interactive
+        ),
+    );
+    for error in command_rule_errors(&config.command) {
+        log_line(&log, &format!("ERROR   command rule {}", error));
+    }
+    for error in host_key_errors(&config.host_keys) {
+        log_line(&log, &format!("ERROR   host_keys {}", error));
+    }
+    log_line(
+        &log,
+        &format!(
+            "host keys: pinned={:?} require_pinned={} use_ssh_config={}",
+            config.host_keys.keys().collect::<Vec<_>>(),
+            config.require_pinned_host_keys,
+            config.use_ssh_config
         ),
     );
 
@@ -471,21 +1970,33 @@ pub fn run(socket_path: &str, log_path: &Path, config: &Config) {
                         parent_pid, current_ppid
                     ),
                 );
+                for socket in &forward_sockets {
+                    let _ = socket.remove_if_owned();
+                }
                 let _ = watchdog_socket.remove_if_owned();
+                let _ = std::fs::remove_dir_all(&known_hosts_dir);
                 process::exit(0);
             }
         }
     });
 
     let config = Arc::new(config.clone());
+    let transcripts = Arc::new(
+        log_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("ssh"),
+    );
 
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
                 let log = Arc::clone(&log);
                 let config = Arc::clone(&config);
+                let policy = Arc::clone(&policy);
+                let transcripts = Arc::clone(&transcripts);
                 thread::spawn(move || {
-                    handle_connection(stream, &config, &log);
+                    handle_connection(stream, &config, &policy, &transcripts, &log);
                 });
             }
             Err(e) => {
@@ -499,6 +2010,16 @@ pub fn run(socket_path: &str, log_path: &Path, config: &Config) {
 mod tests {
     use super::*;
 
+    fn commands(s: &[&str]) -> Vec<CommandRule> {
+        s.iter()
+            .map(|c| CommandRule::Exact(c.to_string()))
+            .collect()
+    }
+
+    fn hosts(s: &[&str]) -> Vec<HostRule> {
+        s.iter().map(|h| HostRule::Dest(h.to_string())).collect()
+    }
+
     fn strs(s: &[&str]) -> Vec<String> {
         s.iter().map(|x| x.to_string()).collect()
     }
@@ -607,23 +2128,37 @@ mod tests {
 
     #[test]
     fn test_parse_git_entry_host_only() {
-        let (host, repo) = parse_git_entry("github.com");
+        let (host, repo, access) = parse_git_entry("github.com");
         assert_eq!(host, "github.com");
         assert_eq!(repo, None);
+        assert_eq!(access, GitAccess::Write);
     }
 
     #[test]
     fn test_parse_git_entry_with_repo() {
-        let (host, repo) = parse_git_entry("github.com/org/*");
+        let (host, repo, _) = parse_git_entry("github.com/org/*");
         assert_eq!(host, "github.com");
         assert_eq!(repo, Some("org/*"));
     }
 
     #[test]
     fn test_parse_git_entry_wildcard_host() {
-        let (host, repo) = parse_git_entry("*.gitlab.com");
+        let (host, repo, _) = parse_git_entry("*.gitlab.com");
+        assert_eq!(host, "*.gitlab.com");
+        assert_eq!(repo, None);
+    }
+
+    #[test]
+    fn test_parse_git_entry_access_suffix() {
+        let (host, repo, access) = parse_git_entry("github.com/org/*:read");
+        assert_eq!(host, "github.com");
+        assert_eq!(repo, Some("org/*"));
+        assert_eq!(access, GitAccess::Read);
+
+        let (host, repo, access) = parse_git_entry("*.gitlab.com:write");
         assert_eq!(host, "*.gitlab.com");
         assert_eq!(repo, None);
+        assert_eq!(access, GitAccess::Write);
     }
 
     // ── check_git_rules ───────────────────────────────────────────
@@ -720,6 +2255,61 @@ mod tests {
         assert!(check_git_rules(&req, &rules));
     }
 
+    #[test]
+    fn test_git_read_rule_allows_fetch_and_archive_but_not_push() {
+        let rules = strs(&["github.com/myorg/*:read"]);
+        let fetch = parse_request(&git_fetch("github.com", "myorg/repo")).unwrap();
+        let archive = parse_request(&strs(&[
+            "git@github.com",
+            "git-upload-archive 'myorg/repo.git'",
+        ]))
+        .unwrap();
+        let push = parse_request(&git_push("github.com", "myorg/repo")).unwrap();
+        assert!(check_git_rules(&fetch, &rules));
+        assert!(check_git_rules(&archive, &rules));
+        assert!(!check_git_rules(&push, &rules));
+    }
+
+    #[test]
+    fn test_git_write_rule_wins_over_earlier_read_rule() {
+        let rules = strs(&["github.com:read", "github.com/myorg/*:write"]);
+        let own = parse_request(&git_push("github.com", "myorg/repo")).unwrap();
+        let other = parse_request(&git_push("github.com", "other/repo")).unwrap();
+        let other_fetch = parse_request(&git_fetch("github.com", "other/repo")).unwrap();
+        assert!(check_git_rules(&own, &rules));
+        assert!(!check_git_rules(&other, &rules));
+        assert!(check_git_rules(&other_fetch, &rules));
+    }
+
+    #[test]
+    fn test_git_push_to_read_only_repo_explains_denial() {
+        let config = Config {
+            git: strs(&["github.com/myorg/*:read"]),
+            command: vec![],
+            host: vec![],
+            ..default_config()
+        };
+        let err = check_allowed(&git_push("github.com", "myorg/repo"), &config).unwrap_err();
+        assert!(err.contains("read access"), "{err}");
+        assert!(check_allowed(&git_fetch("github.com", "myorg/repo"), &config).is_ok());
+    }
+
+    #[test]
+    fn test_requested_git_access() {
+        assert_eq!(
+            requested_git_access(&git_push("github.com", "repo")),
+            Some(GitAccess::Write)
+        );
+        assert_eq!(
+            requested_git_access(&git_fetch("github.com", "repo")),
+            Some(GitAccess::Read)
+        );
+        assert_eq!(
+            requested_git_access(&strs(&["deploy@host", "uptime"])),
+            None
+        );
+    }
+
     #[test]
     fn test_git_repo_restriction_denied() {
         let rules = strs(&["github.com/myorg/*"]);
@@ -747,28 +2337,28 @@ mod tests {
 
     #[test]
     fn test_command_exact_match() {
-        let rules = strs(&["deploy@prod.example.com uptime"]);
+        let rules = commands(&["deploy@prod.example.com uptime"]);
         let req = parse_request(&strs(&["deploy@prod.example.com", "uptime"])).unwrap();
         assert!(check_command_rules(&req, &rules));
     }
 
     #[test]
     fn test_command_denied_different_command() {
-        let rules = strs(&["deploy@prod.example.com uptime"]);
+        let rules = commands(&["deploy@prod.example.com uptime"]);
         let req = parse_request(&strs(&["deploy@prod.example.com", "bash"])).unwrap();
         assert!(!check_command_rules(&req, &rules));
     }
 
     #[test]
     fn test_command_denied_different_host() {
-        let rules = strs(&["deploy@prod.example.com uptime"]);
+        let rules = commands(&["deploy@prod.example.com uptime"]);
         let req = parse_request(&strs(&["deploy@evil.com", "uptime"])).unwrap();
         assert!(!check_command_rules(&req, &rules));
     }
 
     #[test]
     fn test_command_multi_word() {
-        let rules = strs(&["deploy@host sudo systemctl restart myapp"]);
+        let rules = commands(&["deploy@host sudo systemctl restart myapp"]);
         let req = parse_request(&strs(&[
             "deploy@host",
             "sudo",
@@ -780,11 +2370,156 @@ mod tests {
         assert!(check_command_rules(&req, &rules));
     }
 
+    fn journalctl_rule() -> Vec<CommandRule> {
+        serde_json::from_str(
+            r#"[{
+                "dest": "deploy@prod.example.com",
+                "program": "journalctl",
+                "args": [
+                    {"literal": "-u"},
+                    {"one_of": ["myapp", "worker"]},
+                    {"literal": "-n"},
+                    {"range": {"min": 1, "max": 1000}},
+                    {"regex": "[0-9]{4}-[0-9]{2}-[0-9]{2}"}
+                ]
+            }]"#,
+        )
+        .unwrap()
+    }
+
+    fn command_req(command: &str) -> ParsedRequest {
+        parse_request(&strs(&["deploy@prod.example.com", command])).unwrap()
+    }
+
+    #[test]
+    fn test_command_pattern_allows_matching_arguments() {
+        let rules = journalctl_rule();
+        assert!(check_command_rules(
+            &command_req("journalctl -u myapp -n 50 2026-01-31"),
+            &rules
+        ));
+        assert!(check_command_rules(
+            &command_req("journalctl -u 'worker' -n 1000 \"2026-01-31\""),
+            &rules
+        ));
+    }
+
+    #[test]
+    fn test_command_pattern_denies_each_constraint() {
+        let rules = journalctl_rule();
+        for command in [
+            "journalctl -u other -n 50 2026-01-31",
+            "journalctl -u myapp -n 5000 2026-01-31",
+            "journalctl -u myapp -n lots 2026-01-31",
+            "journalctl -u myapp -n 50 yesterday",
+            "journalctl -u myapp -n 50 2026-01-31x",
+            "journalctl -u myapp -n 50",
+            "journalctl -u myapp -n 50 2026-01-31 extra",
+            "systemctl -u myapp -n 50 2026-01-31",
+        ] {
+            assert!(
+                !check_command_rules(&command_req(command), &rules),
+                "{command}"
+            );
+        }
+        let other_dest = parse_request(&strs(&[
+            "root@prod.example.com",
+            "journalctl -u myapp -n 50 2026-01-31",
+        ]))
+        .unwrap();
+        assert!(!check_command_rules(&other_dest, &rules));
+    }
+
+    #[test]
+    fn test_command_pattern_glob_argument() {
+        let rules: Vec<CommandRule> = serde_json::from_str(
+            r#"[{"dest": "deploy@prod.example.com", "program": "kubectl",
+                 "args": [{"literal": "logs"}, {"glob": "pod-*"}]}]"#,
+        )
+        .unwrap();
+        assert!(check_command_rules(
+            &command_req("kubectl logs pod-xyz"),
+            &rules
+        ));
+        assert!(!check_command_rules(
+            &command_req("kubectl logs other"),
+            &rules
+        ));
+    }
+
+    #[test]
+    fn test_command_pattern_rejects_shell_metacharacters() {
+        let rules: Vec<CommandRule> = serde_json::from_str(
+            r#"[{"dest": "deploy@prod.example.com", "program": "kubectl",
+                 "args": [{"literal": "logs"}, {"glob": "*"}]}]"#,
+        )
+        .unwrap();
+        for command in [
+            "kubectl logs x;reboot",
+            "kubectl logs x|sh",
+            "kubectl logs $(reboot)",
+            "kubectl logs `reboot`",
+            "kubectl logs \"$(reboot)\"",
+            "kubectl logs x&&reboot",
+            "kubectl logs >/etc/passwd",
+            "kubectl logs 'unterminated",
+        ] {
+            assert!(
+                !check_command_rules(&command_req(command), &rules),
+                "{command}"
+            );
+        }
+        // Single quotes make metacharacters literal for the remote shell
+        assert!(check_command_rules(
+            &command_req("kubectl logs 'a;b'"),
+            &rules
+        ));
+    }
+
+    #[test]
+    fn test_command_pattern_allow_metacharacters() {
+        let rules: Vec<CommandRule> = serde_json::from_str(
+            r#"[{"dest": "deploy@prod.example.com", "program": "ls",
+                 "args": [{"literal": "*.log"}], "allow_metacharacters": true}]"#,
+        )
+        .unwrap();
+        assert!(check_command_rules(&command_req("ls *.log"), &rules));
+        assert!(!check_command_rules(
+            &command_req("ls *.log;reboot"),
+            &rules
+        ));
+    }
+
+    #[test]
+    fn test_shell_words() {
+        assert_eq!(
+            shell_words(r#"a  'b c' "d \"e\"" f\ g"#, false).unwrap(),
+            strs(&["a", "b c", "d \"e\"", "f g"])
+        );
+        assert_eq!(shell_words("''", false).unwrap(), strs(&[""]));
+        assert!(shell_words("a;b", false).is_err());
+        assert_eq!(shell_words("a;b", true).unwrap(), strs(&["a;b"]));
+    }
+
+    #[test]
+    fn test_command_rule_errors_reports_invalid_regex() {
+        let rules: Vec<CommandRule> = serde_json::from_str(
+            r#"["deploy@host uptime",
+                {"dest": "deploy@host", "program": "cat", "args": [{"regex": "("}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(command_rule_errors(&rules).len(), 1);
+        assert!(!check_command_rules(
+            &parse_request(&strs(&["deploy@host", "cat ("])).unwrap(),
+            &rules
+        ));
+    }
+
     // ── check_host_rules ──────────────────────────────────────────
 
     #[test]
     fn test_host_any_command() {
-        let rules = strs(&["admin@staging.internal"]);
+        let rules = hosts(&["admin@staging.internal"]);
         let req = parse_request(&strs(&["admin@staging.internal", "uptime"])).unwrap();
         assert!(check_host_rules(&req, &rules));
         let req2 =
@@ -794,18 +2529,49 @@ mod tests {
 
     #[test]
     fn test_host_denied_wrong_dest() {
-        let rules = strs(&["admin@staging.internal"]);
+        let rules = hosts(&["admin@staging.internal"]);
         let req = parse_request(&strs(&["admin@evil.com", "uptime"])).unwrap();
         assert!(!check_host_rules(&req, &rules));
     }
 
     #[test]
     fn test_host_denied_wrong_user() {
-        let rules = strs(&["admin@staging.internal"]);
+        let rules = hosts(&["admin@staging.internal"]);
         let req = parse_request(&strs(&["root@staging.internal", "uptime"])).unwrap();
         assert!(!check_host_rules(&req, &rules));
     }
 
+    #[test]
+    fn test_host_rule_object_form() {
+        let config: Config = serde_json::from_str(
+            r#"{"host": ["admin@box", {"dest": "admin@audited", "transcript": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.host[0], HostRule::Dest("admin@box".to_string()));
+        let req = parse_request(&strs(&["admin@audited", "uptime"])).unwrap();
+        assert!(check_host_rules(&req, &config.host));
+        assert!(check_allowed(&strs(&["admin@audited", "uptime"]), &config).is_ok());
+        let loaded: Config =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(loaded.host, config.host);
+    }
+
+    #[test]
+    fn test_wants_transcript_only_for_opted_in_rules() {
+        let config: Config = serde_json::from_str(
+            r#"{"host": ["admin@box", {"dest": "admin@audited", "transcript": true}],
+                "command": ["deploy@web uptime",
+                    {"dest": "deploy@web", "program": "systemctl",
+                     "args": [{"literal": "restart"}, {"glob": "app-*"}], "transcript": true}]}"#,
+        )
+        .unwrap();
+        let wants = |args: &[&str]| wants_transcript(&parse_request(&strs(args)).unwrap(), &config);
+        assert!(wants(&["admin@audited", "rm -rf /tmp/x"]));
+        assert!(!wants(&["admin@box", "uptime"]));
+        assert!(wants(&["deploy@web", "systemctl", "restart", "app-api"]));
+        assert!(!wants(&["deploy@web", "uptime"]));
+    }
+
     // ── check_allowed (integration) ───────────────────────────────
 
     #[test]
@@ -820,6 +2586,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         assert!(check_allowed(&git_push("github.com", "repo"), &config).is_ok());
     }
@@ -830,6 +2597,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["git@github.com", "bash"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -839,8 +2607,9 @@ mod tests {
     fn test_command_rule_allows_exact() {
         let config = Config {
             git: vec![],
-            command: strs(&["deploy@host uptime"]),
+            command: commands(&["deploy@host uptime"]),
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["deploy@host", "uptime"]);
         assert!(check_allowed(&args, &config).is_ok());
@@ -851,7 +2620,8 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["admin@box"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         let args = strs(&["admin@box", "anything"]);
         assert!(check_allowed(&args, &config).is_ok());
@@ -862,7 +2632,8 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["admin@box"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         let args = strs(&["-v", "admin@box", "cmd"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -873,20 +2644,485 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["admin@box"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         let args = strs(&["admin@box"]);
         assert!(check_allowed(&args, &config).is_err());
     }
 
+    // ── forward rules ─────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_forward() {
+        assert_eq!(
+            parse_forward("admin@bastion -> db.internal:5432").unwrap(),
+            Forward {
+                dest: "admin@bastion".to_string(),
+                target_host: "db.internal".to_string(),
+                target_port: 5432,
+                local_port: 5432,
+            }
+        );
+        let renamed = parse_forward("admin@bastion->db.internal:5432 as 15432").unwrap();
+        assert_eq!(renamed.target_port, 5432);
+        assert_eq!(renamed.local_port, 15432);
+        assert_eq!(renamed.describe(), "admin@bastion -> db.internal:5432");
+    }
+
+    #[test]
+    fn test_parse_forward_rejects_malformed_and_option_like_entries() {
+        for entry in [
+            "admin@bastion",
+            "bastion -> db:5432",
+            "admin@bastion -> db",
+            "admin@bastion -> db:0",
+            "admin@bastion -> db:70000",
+            "admin@bastion -> db:5432 as x",
+            "admin@-oProxyCommand=x -> db:5432",
+            "-oProxyCommand=x@bastion -> db:5432",
+            "admin@bastion -> -db:5432",
+            "admin@bastion -> db;reboot:5432",
+        ] {
+            assert!(parse_forward(entry).is_err(), "{entry}");
+        }
+    }
+
+    #[test]
+    fn test_forward_only_config_is_not_empty() {
+        let mut config = default_config();
+        assert!(is_empty(&config));
+        config.forward = strs(&["admin@bastion -> db.internal:5432"]);
+        assert!(!is_empty(&config));
+        let loaded: Config =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(loaded.forward, config.forward);
+    }
+
+    #[test]
+    fn test_start_forwards_binds_one_socket_per_local_port() {
+        let root = std::env::temp_dir().join(format!(
+            "claude-sandbox-ssh-forward-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let socket = root.join("ssh-proxy.sock");
+        std::fs::create_dir_all(&root).unwrap();
+        let log = Arc::new(Mutex::new(File::create(root.join("log")).unwrap()));
+
+        let config = Config {
+            forward: strs(&[
+                "admin@bastion -> db.internal:5432",
+                "admin@other -> db.other:5432",
+                "admin@other -> db.other:5432 as 15432",
+                "not a rule",
+            ]),
+            ..default_config()
+        };
+        let policy = Arc::new(HostKeyPolicy::new(&config, root.clone()));
+        let identities = start_forwards(&socket, &config, &policy, &log);
+
+        assert_eq!(identities.len(), 2);
+        assert!(root.join("ssh-forward-5432.sock").exists());
+        assert!(root.join("ssh-forward-15432.sock").exists());
+        let logged = std::fs::read_to_string(root.join("log")).unwrap();
+        assert!(logged.contains("already forwarded"), "{logged}");
+        assert!(logged.contains("missing '->'"), "{logged}");
+        for identity in identities {
+            identity.remove_if_owned().unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    // ── transfer rules ────────────────────────────────────────────
+
+    fn transfer(command: &str) -> Option<Transfer> {
+        parse_transfer_command(command)
+    }
+
+    fn upload(paths: &[&str]) -> Option<Transfer> {
+        Some(Transfer {
+            direction: TransferDirection::Upload,
+            paths: strs(paths),
+        })
+    }
+
+    fn download(paths: &[&str]) -> Option<Transfer> {
+        Some(Transfer {
+            direction: TransferDirection::Download,
+            paths: strs(paths),
+        })
+    }
+
+    #[test]
+    fn test_parse_scp_shapes() {
+        assert_eq!(transfer("scp -t /srv/a.tar"), upload(&["/srv/a.tar"]));
+        assert_eq!(
+            transfer("scp -v -r -p -d -t -- /srv/dir"),
+            upload(&["/srv/dir"])
+        );
+        assert_eq!(transfer("scp -f logs/app.log"), download(&["logs/app.log"]));
+        assert_eq!(transfer("scp -pf 'my file'"), download(&["my file"]));
+    }
+
+    #[test]
+    fn test_parse_scp_rejects_other_shapes() {
+        for command in [
+            "scp /srv/a",
+            "scp -t",
+            "scp -t -f /srv/a",
+            "scp -t /srv/a /srv/b",
+            "scp -S evil -t /srv/a",
+            "scp -t /srv/*.log",
+            "scp -t /srv/a; reboot",
+            "scp -t $(reboot)",
+            "sftp-server",
+        ] {
+            assert_eq!(transfer(command), None, "{command}");
+        }
+    }
+
+    #[test]
+    fn test_parse_rsync_shapes() {
+        assert_eq!(
+            transfer("rsync --server -vogDtpre.iLsfxCIvu . /srv/dist/"),
+            upload(&["/srv/dist/"])
+        );
+        assert_eq!(
+            transfer("rsync --server --sender -vlogDtpre.iLsfxCIvu . /var/log/app.log"),
+            download(&["/var/log/app.log"])
+        );
+        assert_eq!(
+            transfer("rsync --server -ogDtpr --delete --partial --timeout=30 . dist"),
+            upload(&["dist"])
+        );
+        assert_eq!(
+            transfer("rsync --server --sender -r . a b"),
+            download(&["a", "b"])
+        );
+    }
+
+    #[test]
+    fn test_parse_rsync_rejects_other_shapes() {
+        for command in [
+            "rsync -av /srv/a /srv/b",
+            "rsync --server -vlogDtpr .",
+            "rsync --server -vlogDtpr /srv/a",
+            "rsync --server -vslogDtpr . /srv/a",
+            "rsync --server --log-file=/tmp/x -r . /srv/a",
+            "rsync --server --temp-dir=/tmp -r . /srv/a",
+            "rsync --server --remove-source-files --sender -r . /srv/a",
+            "rsync --server --timeout=1;reboot -r . /srv/a",
+            "rsync --server -r . -/srv/a",
+        ] {
+            assert_eq!(transfer(command), None, "{command}");
+        }
+    }
+
+    #[test]
+    fn test_parse_rsync_rejects_symlink_flags() {
+        // An upload could plant `/srv/dist/x -> /` and write through it later
+        for command in [
+            "rsync --server -vlogDtpre.iLsfxCIvu . /srv/dist/",
+            "rsync --server -rK . /srv/dist/",
+            "rsync --server -rL . /srv/dist/",
+            "rsync --server -rk . /srv/dist/",
+            "rsync --server --sender -rL . /srv/dist/",
+            "rsync --server --sender -rk . /srv/dist/",
+            "rsync --server --links -r . /srv/dist/",
+        ] {
+            assert_eq!(transfer(command), None, "{command}");
+        }
+        // Downloads may copy symlinks as symlinks; the capability string
+        // after `e` is not an option
+        assert_eq!(
+            transfer("rsync --server --sender -vlogDtpre.iLsfxCIvu . /var/log/app.log"),
+            download(&["/var/log/app.log"])
+        );
+    }
+
+    #[test]
+    fn test_is_under_prefix() {
+        assert!(is_under_prefix("/srv/dist", "/srv/dist"));
+        assert!(is_under_prefix("/srv/dist/", "/srv/dist/"));
+        assert!(is_under_prefix("/srv/dist/a/b.tar", "/srv/dist"));
+        assert!(is_under_prefix("/anything", "/"));
+        assert!(!is_under_prefix("/srv/distx", "/srv/dist"));
+        assert!(!is_under_prefix("/srv/dist/../etc/passwd", "/srv/dist"));
+        assert!(!is_under_prefix("/srv", "/srv/dist"));
+        assert!(!is_under_prefix("relative", "/"));
+    }
+
+    fn transfer_config() -> Config {
+        let mut config = default_config();
+        config.transfer = vec![
+            TransferRule {
+                dest: "deploy@staging".to_string(),
+                direction: TransferDirection::Upload,
+                path: "/srv/artifacts".to_string(),
+                max_bytes: Some(1024),
+            },
+            TransferRule {
+                dest: "deploy@staging".to_string(),
+                direction: TransferDirection::Download,
+                path: "/var/log/app".to_string(),
+                max_bytes: None,
+            },
+        ];
+        config
+    }
+
+    #[test]
+    fn test_transfer_rules_constrain_direction_dest_and_path() {
+        let config = transfer_config();
+        let allowed =
+            |dest: &str, command: &str| check_allowed(&strs(&[dest, command]), &config).is_ok();
+        assert!(allowed("deploy@staging", "scp -t /srv/artifacts/build.tar"));
+        assert!(allowed(
+            "deploy@staging",
+            "rsync --server --sender -vlogDtpr . /var/log/app/today.log"
+        ));
+        assert!(!allowed(
+            "deploy@staging",
+            "scp -f /srv/artifacts/build.tar"
+        ));
+        assert!(!allowed("deploy@staging", "scp -t /var/log/app/x"));
+        assert!(!allowed("deploy@staging", "scp -t /srv/artifacts/../x"));
+        assert!(!allowed("root@staging", "scp -t /srv/artifacts/build.tar"));
+        assert!(!allowed("deploy@staging", "cat /srv/artifacts/build.tar"));
+
+        let req = parse_request(&strs(&["deploy@staging", "scp -t /srv/artifacts/a"])).unwrap();
+        assert_eq!(
+            matching_transfer_rule(&req, &config.transfer).and_then(|rule| rule.max_bytes),
+            Some(1024)
+        );
+    }
+
+    #[test]
+    fn test_limited_reader_stops_past_the_limit() {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let mut reader = limit_reader(&b"0123456789"[..], Some(10), &exceeded);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"0123456789");
+        assert!(!exceeded.load(Ordering::Relaxed));
+
+        let mut reader = limit_reader(&b"0123456789"[..], Some(9), &exceeded);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert!(out.is_empty());
+        assert!(exceeded.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_transfer_config_round_trip() {
+        let json = r#"{"transfer": [{"dest": "deploy@staging", "direction": "upload",
+                        "path": "/srv/artifacts", "max_bytes": 1048576}]}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert!(!is_empty(&config));
+        assert_eq!(config.transfer[0].direction, TransferDirection::Upload);
+        assert_eq!(config.transfer[0].max_bytes, Some(1048576));
+        let loaded: Config =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(loaded.transfer, config.transfer);
+    }
+
+    // ── host key policy ───────────────────────────────────────────
+
+    const ED25519_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    fn policy_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "claude-sandbox-ssh-policy-{}-{}-{}",
+            name,
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn command_args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_unpinned_host_ignores_ssh_config_and_checks_strictly() {
+        let dir = policy_dir("unpinned");
+        let policy = HostKeyPolicy::new(&default_config(), dir.clone());
+        let args = command_args(&policy.ssh_command("example.com").unwrap());
+        assert_eq!(
+            args,
+            strs(&["-F", "none", "-o", "StrictHostKeyChecking=yes"])
+        );
+
+        let mut config = default_config();
+        config.use_ssh_config = true;
+        let policy = HostKeyPolicy::new(&config, dir.clone());
+        let args = command_args(&policy.ssh_command("example.com").unwrap());
+        assert_eq!(args, strs(&["-o", "StrictHostKeyChecking=yes"]));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pinned_host_uses_generated_known_hosts() {
+        let dir = policy_dir("pinned");
+        let mut config = default_config();
+        config.host_keys.insert(
+            "github.com".to_string(),
+            vec![format!("{} comment", ED25519_KEY)],
+        );
+        let policy = HostKeyPolicy::new(&config, dir.clone());
+        let args = command_args(&policy.ssh_command("github.com").unwrap());
+
+        let known_hosts = dir.join("known_hosts-github.com");
+        assert!(args.contains(&format!("UserKnownHostsFile={}", known_hosts.display())));
+        assert!(args.contains(&"GlobalKnownHostsFile=/dev/null".to_string()));
+        assert!(args.contains(&"HostKeyAlias=github.com".to_string()));
+        assert!(args.contains(&"StrictHostKeyChecking=yes".to_string()));
+        assert_eq!(
+            std::fs::read_to_string(&known_hosts).unwrap(),
+            format!("github.com {}\n", ED25519_KEY)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_host_key_errors() {
+        let mut pins = BTreeMap::new();
+        pins.insert(
+            "github.com".to_string(),
+            vec![
+                ED25519_KEY.to_string(),
+                "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU".to_string(),
+            ],
+        );
+        assert!(host_key_errors(&pins).is_empty());
+        pins.insert("bad host".to_string(), vec!["not a key".to_string()]);
+        pins.insert("empty.example.com".to_string(), vec![]);
+        assert_eq!(host_key_errors(&pins).len(), 3);
+    }
+
+    #[test]
+    fn test_parse_keygen_fingerprint() {
+        assert_eq!(
+            parse_keygen_fingerprint("256 SHA256:abcDEF+/ no comment (ED25519)\n"),
+            Some("SHA256:abcDEF+/")
+        );
+        assert_eq!(
+            parse_keygen_fingerprint("(stdin) is not a public key file.\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_require_pinned_host_keys_denies_unpinned_hosts() {
+        let mut config = Config {
+            host: hosts(&["admin@box", "admin@pinned"]),
+            require_pinned_host_keys: true,
+            ..default_config()
+        };
+        config
+            .host_keys
+            .insert("pinned".to_string(), vec![ED25519_KEY.to_string()]);
+        let err = check_allowed(&strs(&["admin@box", "uptime"]), &config).unwrap_err();
+        assert!(err.contains("no pinned host key"), "{err}");
+        assert!(check_allowed(&strs(&["admin@pinned", "uptime"]), &config).is_ok());
+    }
+
+    #[test]
+    fn test_host_key_sniffer_spans_reads() {
+        let failed = Arc::new(AtomicBool::new(false));
+        let message = b"@@@ WARNING @@@\nHost key verification failed.\r\n".to_vec();
+        let mut sniffer = HostKeySniffer {
+            inner: std::io::Cursor::new(message.clone()),
+            tail: Vec::new(),
+            failed: Arc::clone(&failed),
+        };
+        let mut out = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            let n = sniffer.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, message);
+        assert!(failed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_host_key_config_fields_default_off() {
+        let config: Config = serde_json::from_str(r#"{"git": ["github.com"]}"#).unwrap();
+        assert!(config.host_keys.is_empty());
+        assert!(!config.require_pinned_host_keys);
+        assert!(!config.use_ssh_config);
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("host_keys"), "{json}");
+    }
+
+    // ── interactive sessions ──────────────────────────────────────
+
+    #[test]
+    fn test_interactive_rule_allows_terminal_sessions() {
+        let config = Config {
+            interactive: strs(&["admin@staging"]),
+            ..default_config()
+        };
+        assert!(!is_empty(&config));
+        assert_eq!(
+            check_interactive(&strs(&["admin@staging"]), true, &config),
+            Ok("staging".to_string())
+        );
+        // Interactive rules do not allow commands
+        assert!(check_allowed(&strs(&["admin@staging", "uptime"]), &config).is_err());
+    }
+
+    #[test]
+    fn test_interactive_denials() {
+        let config = Config {
+            interactive: strs(&["admin@staging"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
+        };
+        let denied = |args: &[&str], has_pty: bool| {
+            check_interactive(&strs(args), has_pty, &config).unwrap_err()
+        };
+        assert!(denied(&["admin@box"], true).contains("add it to interactive"));
+        assert!(denied(&["root@staging"], true).contains("add it to interactive"));
+        assert!(denied(&["admin@staging"], false).contains("need a terminal"));
+        assert!(denied(&["-tt"], true).contains("flags are not supported"));
+        assert!(denied(&["staging"], true).contains("user@host"));
+        assert!(denied(&["admin@staging", "sh"], true).contains("only a user@host"));
+
+        let pinned = Config {
+            require_pinned_host_keys: true,
+            ..config.clone()
+        };
+        let err = check_interactive(&strs(&["admin@staging"]), true, &pinned).unwrap_err();
+        assert!(err.contains("no pinned host key"), "{err}");
+    }
+
     // ── config serialization ──────────────────────────────────────
 
     #[test]
     fn test_config_round_trip() {
         let config = Config {
             git: strs(&["github.com", "gitlab.com/org/*"]),
-            command: strs(&["deploy@host uptime"]),
-            host: strs(&["admin@box"]),
+            command: commands(&["deploy@host uptime"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         let json = serde_json::to_string_pretty(&config).unwrap();
         let loaded: Config = serde_json::from_str(&json).unwrap();
@@ -1057,6 +3293,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["deploy@github.com", "git-receive-pack '/repo.git'"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1069,6 +3306,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["git@github.com", "bash"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1080,6 +3318,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["git@github.com", "git-receive-pack 'repo; rm -rf /'"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1091,6 +3330,7 @@ mod tests {
             git: strs(&["github.com"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["git@github.com", "git-upload-pack 'repo.git'; arbitrary"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1103,6 +3343,7 @@ mod tests {
             git: strs(&["github.com/myorg/*"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["git@github.com", "git-receive-pack '../other-org/repo.git'"]);
         let req = parse_request(&args).unwrap();
@@ -1117,8 +3358,9 @@ mod tests {
         // Trying to extend a command with extra args
         let config = Config {
             git: vec![],
-            command: strs(&["deploy@host uptime"]),
+            command: commands(&["deploy@host uptime"]),
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["deploy@host", "uptime; rm -rf /"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1129,8 +3371,9 @@ mod tests {
         // "uptime" should not match "uptimex"
         let config = Config {
             git: vec![],
-            command: strs(&["deploy@host uptime"]),
+            command: commands(&["deploy@host uptime"]),
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["deploy@host", "uptimex"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1141,8 +3384,9 @@ mod tests {
         // "uptime" command should not match "uptime && bash"
         let config = Config {
             git: vec![],
-            command: strs(&["deploy@host uptime"]),
+            command: commands(&["deploy@host uptime"]),
             host: vec![],
+            ..default_config()
         };
         let args = strs(&["deploy@host", "uptime && bash"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1153,8 +3397,9 @@ mod tests {
         // Splitting the command across args to try boundary confusion
         let config = Config {
             git: vec![],
-            command: strs(&["deploy@host sudo systemctl restart app"]),
+            command: commands(&["deploy@host sudo systemctl restart app"]),
             host: vec![],
+            ..default_config()
         };
         // "sudo" "systemctl" "restart" "app" joins to same string
         let args = strs(&["deploy@host", "sudo", "systemctl", "restart", "app"]);
@@ -1179,7 +3424,8 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["deploy@host"]),
+            host: hosts(&["deploy@host"]),
+            ..default_config()
         };
         let args = strs(&["root@host", "cmd"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1190,7 +3436,8 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["admin@box"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         let args = strs(&["admin@box"]);
         assert!(check_allowed(&args, &config).is_err());
@@ -1201,7 +3448,8 @@ mod tests {
         let config = Config {
             git: vec![],
             command: vec![],
-            host: strs(&["admin@box"]),
+            host: hosts(&["admin@box"]),
+            ..default_config()
         };
         assert!(
             check_allowed(
@@ -1249,6 +3497,7 @@ mod tests {
             git: strs(&["host"]),
             command: vec![],
             host: vec![],
+            ..default_config()
         };
         assert!(check_allowed(&strs(&["git@host\nevil", "cmd"]), &config).is_err());
     }
@@ -1258,4 +3507,85 @@ mod tests {
         let req = parse_request(&strs(&["git@host\0evil", "cmd"])).unwrap();
         assert_eq!(req.host, "host\0evil");
     }
+
+    // ── output redaction ──────────────────────────────────────────
+
+    fn frames(bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut out = Vec::new();
+        let mut rest = bytes;
+        while rest.len() >= 5 {
+            let len = u32::from_be_bytes(rest[1..5].try_into().unwrap()) as usize;
+            out.push((rest[0], rest[5..5 + len].to_vec()));
+            rest = &rest[5 + len..];
+        }
+        out
+    }
+
+    #[test]
+    fn test_forward_output_redacts_remote_output() {
+        let token = format!("ghp_{}", "k".repeat(36));
+        let input = format!("export GITHUB_TOKEN={token}\nok");
+        let writer = Mutex::new(Vec::new());
+        let count = forward_output(
+            input.as_bytes(),
+            &writer,
+            FRAME_STDOUT,
+            true,
+            PARTIAL_LINE_IDLE,
+            None,
+        );
+        assert_eq!(count, 1);
+        let payload: Vec<u8> = frames(&writer.into_inner().unwrap())
+            .into_iter()
+            .inspect(|(kind, _)| assert_eq!(*kind, FRAME_STDOUT))
+            .flat_map(|(_, data)| data)
+            .collect();
+        assert_eq!(payload, b"export GITHUB_TOKEN=[REDACTED]\nok");
+    }
+
+    #[test]
+    fn test_forward_output_flushes_prompts_without_newline() {
+        let (pipe, mut remote) = std::io::pipe().unwrap();
+        let writer = Arc::new(Mutex::new(Vec::new()));
+        let forwarding = {
+            let writer = Arc::clone(&writer);
+            thread::spawn(move || {
+                forward_output(pipe, &*writer, FRAME_STDOUT, true, PARTIAL_LINE_IDLE, None)
+            })
+        };
+        remote.write_all(b"Password: ").unwrap();
+        let started = std::time::Instant::now();
+        while writer.lock().unwrap().is_empty() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "prompt was held back"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            frames(&writer.lock().unwrap()),
+            vec![(FRAME_STDOUT, b"Password: ".to_vec())]
+        );
+        drop(remote);
+        assert_eq!(forwarding.join().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_forward_output_passes_git_traffic_through() {
+        let input = format!("PACK ghp_{}", "k".repeat(36));
+        let writer = Mutex::new(Vec::new());
+        assert_eq!(
+            forward_output(
+                input.as_bytes(),
+                &writer,
+                FRAME_STDOUT,
+                false,
+                PARTIAL_LINE_IDLE,
+                None
+            ),
+            0
+        );
+        let written = frames(&writer.into_inner().unwrap());
+        assert_eq!(written, vec![(FRAME_STDOUT, input.into_bytes())]);
+    }
 }
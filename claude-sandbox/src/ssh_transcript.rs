@@ -0,0 +1,649 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs::{DirBuilder, File, Permissions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::managed_push;
+use crate::proxy_log;
+
+/// Recorded bytes per session before the rest of the session is dropped.
+const MAX_SESSION_BYTES: u64 = 16 * 1024 * 1024;
+/// Total size of the transcript directory; the oldest sessions are
+/// removed when a new one would go over.
+const MAX_DIR_BYTES: u64 = 256 * 1024 * 1024;
+
+/// One line of a session transcript. `t` is seconds since the session
+/// started.
+#[derive(Serialize)]
+struct Event<'a> {
+    t: f64,
+    #[serde(flatten)]
+    kind: EventKind<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum EventKind<'a> {
+    Start {
+        started_at: u64,
+        dest: &'a str,
+        #[serde(skip_serializing_if = "str::is_empty")]
+        command: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cols: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rows: Option<u16>,
+    },
+    Stdin(Data),
+    Stdout(Data),
+    Stderr(Data),
+    Resize {
+        cols: u16,
+        rows: u16,
+    },
+    /// The session went over `MAX_SESSION_BYTES`; no more data follows.
+    Truncated {
+        max_bytes: u64,
+    },
+    Exit {
+        code: i32,
+    },
+}
+
+/// Bytes as text when they are valid UTF-8, base64 otherwise.
+#[derive(Serialize)]
+struct Data {
+    data: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    base64: bool,
+}
+
+impl Data {
+    fn new(bytes: &[u8]) -> Data {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Data {
+                data: text.to_string(),
+                base64: false,
+            },
+            Err(_) => Data {
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+                base64: true,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+/// A JSON lines record of one ssh-proxy session, written to an owner-only
+/// file under the project's `logs/ssh/` directory.
+pub struct Transcript {
+    pub id: String,
+    output: Mutex<Output>,
+    started: Instant,
+    max_bytes: u64,
+}
+
+struct Output {
+    file: File,
+    data_bytes: u64,
+    truncated: bool,
+}
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A session id that sorts by start time: `<unix seconds>-<pid>-<n>`.
+fn new_session_id() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!(
+        "{}-{}-{}",
+        secs,
+        std::process::id(),
+        SESSION_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+impl Transcript {
+    pub fn create(
+        dir: &Path,
+        dest: &str,
+        command: &str,
+        size: Option<(u16, u16)>,
+    ) -> io::Result<Transcript> {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        std::fs::set_permissions(dir, Permissions::from_mode(0o700))?;
+        prune(dir, MAX_DIR_BYTES - MAX_SESSION_BYTES)?;
+        let id = new_session_id();
+        let transcript = Transcript {
+            output: Mutex::new(Output {
+                file: proxy_log::open(&dir.join(format!("{}.jsonl", id)))?,
+                data_bytes: 0,
+                truncated: false,
+            }),
+            id,
+            started: Instant::now(),
+            max_bytes: MAX_SESSION_BYTES,
+        };
+        transcript.write(EventKind::Start {
+            started_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            dest,
+            command,
+            cols: size.map(|(cols, _)| cols),
+            rows: size.map(|(_, rows)| rows),
+        });
+        Ok(transcript)
+    }
+
+    fn write(&self, kind: EventKind) {
+        let event = Event {
+            t: self.started.elapsed().as_secs_f64(),
+            kind,
+        };
+        if let (Ok(mut line), Ok(mut output)) = (serde_json::to_vec(&event), self.output.lock()) {
+            line.push(b'\n');
+            let _ = output.file.write_all(&line);
+        }
+    }
+
+    /// Count `len` data bytes against the session cap. The first time the
+    /// cap is crossed a `truncated` event is written and later data is
+    /// dropped.
+    fn within_cap(&self, len: usize) -> bool {
+        let Ok(mut output) = self.output.lock() else {
+            return false;
+        };
+        if output.truncated {
+            return false;
+        }
+        output.data_bytes += len as u64;
+        if output.data_bytes <= self.max_bytes {
+            return true;
+        }
+        output.truncated = true;
+        drop(output);
+        self.write(EventKind::Truncated {
+            max_bytes: self.max_bytes,
+        });
+        false
+    }
+
+    pub fn record(&self, stream: Stream, bytes: &[u8]) {
+        if !self.within_cap(bytes.len()) {
+            return;
+        }
+        let data = Data::new(bytes);
+        self.write(match stream {
+            Stream::Stdin => EventKind::Stdin(data),
+            Stream::Stdout => EventKind::Stdout(data),
+            Stream::Stderr => EventKind::Stderr(data),
+        });
+    }
+
+    pub fn resize(&self, cols: u16, rows: u16) {
+        self.write(EventKind::Resize { cols, rows });
+    }
+
+    pub fn exit(&self, code: i32) {
+        self.write(EventKind::Exit { code });
+    }
+}
+
+/// Remove the oldest transcripts until the directory holds at most `keep`
+/// bytes.
+fn prune(dir: &Path, keep: u64) -> io::Result<()> {
+    let mut files = transcript_files(dir)?;
+    let mut total: u64 = files.iter().map(|(_, _, len)| len).sum();
+    files.sort_by_key(|(_, modified, _)| *modified);
+    for (path, _, len) in files {
+        if total <= keep {
+            break;
+        }
+        std::fs::remove_file(&path)?;
+        total -= len;
+    }
+    Ok(())
+}
+
+fn transcript_files(dir: &Path) -> io::Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "jsonl") {
+            let metadata = entry.metadata()?;
+            files.push((
+                path,
+                metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                metadata.len(),
+            ));
+        }
+    }
+    Ok(files)
+}
+
+/// A transcript line as read back. Every field other than `t` and `event`
+/// depends on the event.
+#[derive(Deserialize)]
+struct Recorded {
+    t: f64,
+    event: String,
+    #[serde(default)]
+    started_at: u64,
+    #[serde(default)]
+    dest: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    data: String,
+    #[serde(default)]
+    base64: bool,
+    #[serde(default)]
+    cols: u16,
+    #[serde(default)]
+    rows: u16,
+    #[serde(default)]
+    max_bytes: u64,
+    #[serde(default)]
+    code: i32,
+}
+
+impl Recorded {
+    fn bytes(&self) -> Vec<u8> {
+        if self.base64 {
+            base64::engine::general_purpose::STANDARD
+                .decode(&self.data)
+                .unwrap_or_default()
+        } else {
+            self.data.as_bytes().to_vec()
+        }
+    }
+}
+
+fn read_events(path: &Path) -> Result<Vec<Recorded>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+        // A session still running may end in a partly written line
+        if let Ok(event) = serde_json::from_str(&line) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+fn is_session_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit() || c == '-')
+}
+
+/// Recorded sessions newest first, as shown by `claude-sandbox logs ssh`.
+pub fn list(dir: &Path) -> Result<String, String> {
+    let mut files = match transcript_files(dir) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+    };
+    if files.is_empty() {
+        return Ok(
+            "No ssh sessions recorded yet. Interactive sessions and host or command \
+             rules with \"transcript\": true in ssh-proxy.json are recorded."
+                .to_string(),
+        );
+    }
+    files.sort_by_key(|(_, modified, _)| std::cmp::Reverse(*modified));
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut report = String::from("ssh sessions:\n");
+    for (path, _, len) in files {
+        let events = read_events(&path)?;
+        let Some(start) = events.iter().find(|event| event.event == "start") else {
+            continue;
+        };
+        let status = match events.iter().find(|event| event.event == "exit") {
+            Some(exit) => format!("exit {}", exit.code),
+            None => "running".to_string(),
+        };
+        report.push_str(&format!(
+            "  {}  {} ago  {} {}  {}, {} bytes{}\n",
+            path.file_stem().unwrap_or_default().to_string_lossy(),
+            managed_push::format_duration(now.saturating_sub(start.started_at)),
+            start.dest,
+            if start.command.is_empty() {
+                "(interactive)"
+            } else {
+                &start.command
+            },
+            status,
+            len,
+            if events.iter().any(|event| event.event == "truncated") {
+                ", truncated"
+            } else {
+                ""
+            }
+        ));
+    }
+    Ok(report)
+}
+
+/// Output for a terminal: escape sequences are dropped and other control
+/// characters, apart from newline and tab, shown as U+FFFD, so a recorded
+/// session cannot drive the host terminal.
+fn sanitize(bytes: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | '\t' => clean.push(c),
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final byte
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC and other strings: up to BEL or ESC \
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            chars.next_if_eq(&'\\');
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            c if c.is_control() => clean.push('\u{fffd}'),
+            c => clean.push(c),
+        }
+    }
+    clean.into_bytes()
+}
+
+/// Replay session `id`: recorded stdout and stderr go to `out` and `err`,
+/// passed through `sanitize` when `sanitize_output` is set. With `timing`
+/// the original pauses are kept. With `events` every event, including what
+/// was typed or piped in, is printed as one annotated line on `out`
+/// instead.
+pub fn replay(
+    dir: &Path,
+    id: &str,
+    timing: bool,
+    events: bool,
+    sanitize_output: bool,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> Result<(), String> {
+    if !is_session_id(id) {
+        return Err(format!("invalid session id: {}", id));
+    }
+    let path = dir.join(format!("{}.jsonl", id));
+    if !path.exists() {
+        return Err(format!(
+            "no ssh session {} (run 'claude-sandbox logs ssh' to list them)",
+            id
+        ));
+    }
+    let mut previous = 0.0;
+    for event in read_events(&path)? {
+        if timing && event.t > previous {
+            std::thread::sleep(Duration::from_secs_f64(event.t - previous));
+        }
+        previous = event.t;
+        let written = if events {
+            let detail = match event.event.as_str() {
+                "start" if event.command.is_empty() => {
+                    format!(
+                        "{} (interactive, {}x{})",
+                        event.dest, event.cols, event.rows
+                    )
+                }
+                "start" => format!("{} {}", event.dest, event.command),
+                "stdin" | "stdout" | "stderr" => {
+                    format!("{:?}", String::from_utf8_lossy(&event.bytes()))
+                }
+                "resize" => format!("{}x{}", event.cols, event.rows),
+                "truncated" => format!("after {} bytes", event.max_bytes),
+                "exit" => event.code.to_string(),
+                _ => String::new(),
+            };
+            writeln!(out, "[{:>9.3}] {:<9} {}", event.t, event.event, detail)
+        } else {
+            let bytes = || {
+                if sanitize_output {
+                    sanitize(&event.bytes())
+                } else {
+                    event.bytes()
+                }
+            };
+            match event.event.as_str() {
+                "stdout" => out.write_all(&bytes()).and_then(|_| out.flush()),
+                "stderr" => err.write_all(&bytes()).and_then(|_| err.flush()),
+                _ => Ok(()),
+            }
+        };
+        written.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "claude-sandbox-ssh-transcript-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    #[test]
+    fn transcript_is_owner_only_json_lines() {
+        let dir = temp_dir();
+        let transcript = Transcript::create(&dir, "admin@box", "", Some((80, 24))).unwrap();
+        transcript.record(Stream::Stdin, b"ls\r");
+        transcript.record(Stream::Stdout, b"\xff\xfe");
+        transcript.resize(100, 30);
+        transcript.exit(0);
+
+        let path = dir.join(format!("{}.jsonl", transcript.id));
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0]["event"], "start");
+        assert_eq!(events[0]["dest"], "admin@box");
+        assert_eq!(events[0]["cols"], 80);
+        assert_eq!(events[1]["event"], "stdin");
+        assert_eq!(events[1]["data"], "ls\r");
+        assert_eq!(events[2]["base64"], true);
+        assert_eq!(events[2]["data"], "//4=");
+        assert_eq!(events[3]["event"], "resize");
+        assert_eq!(events[4]["code"], 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn session_cap_drops_later_data() {
+        let dir = temp_dir();
+        let mut transcript = Transcript::create(&dir, "admin@box", "cat big", None).unwrap();
+        transcript.max_bytes = 8;
+        transcript.record(Stream::Stdout, b"12345");
+        transcript.record(Stream::Stdout, b"67890");
+        transcript.record(Stream::Stderr, b"more");
+        transcript.exit(0);
+
+        let mut replayed = Vec::new();
+        replay(
+            &dir,
+            &transcript.id,
+            false,
+            true,
+            false,
+            &mut replayed,
+            &mut io::sink(),
+        )
+        .unwrap();
+        let replayed = String::from_utf8(replayed).unwrap();
+        let kinds: Vec<&str> = replayed
+            .lines()
+            .map(|line| line.split("] ").nth(1).unwrap().split(' ').next().unwrap())
+            .collect();
+        assert_eq!(kinds, ["start", "stdout", "truncated", "exit"]);
+        assert!(replayed.contains("after 8 bytes"));
+        assert!(list(&dir).unwrap().contains(", truncated"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_removes_oldest_sessions_first() {
+        let dir = temp_dir();
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("1-1-0", 30), ("1-1-1", 20), ("1-1-2", 10)] {
+            let path = dir.join(format!("{}.jsonl", name));
+            std::fs::write(&path, [b'x'; 100]).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), [b'x'; 1000]).unwrap();
+
+        prune(&dir, 250).unwrap();
+        assert!(!dir.join("1-1-0.jsonl").exists());
+        assert!(dir.join("1-1-1.jsonl").exists());
+        assert!(dir.join("1-1-2.jsonl").exists());
+        assert!(dir.join("notes.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replay_writes_output_streams_and_lists_sessions() {
+        let dir = temp_dir();
+        let transcript = Transcript::create(&dir, "admin@box", "uptime", None).unwrap();
+        transcript.record(Stream::Stdin, b"secret input");
+        transcript.record(Stream::Stdout, b"up 3 days\n");
+        transcript.record(Stream::Stderr, b"\xffwarning\n");
+        transcript.exit(3);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        replay(
+            &dir,
+            &transcript.id,
+            false,
+            false,
+            false,
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(out, b"up 3 days\n");
+        assert_eq!(err, b"\xffwarning\n");
+
+        let mut annotated = Vec::new();
+        replay(
+            &dir,
+            &transcript.id,
+            false,
+            true,
+            false,
+            &mut annotated,
+            &mut io::sink(),
+        )
+        .unwrap();
+        let annotated = String::from_utf8(annotated).unwrap();
+        assert!(annotated.contains("stdin     \"secret input\""));
+        assert!(annotated.contains("start     admin@box uptime"));
+
+        let listed = list(&dir).unwrap();
+        assert!(listed.contains(&transcript.id));
+        assert!(listed.contains("admin@box uptime  exit 3"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replay_sanitizes_terminal_output() {
+        let dir = temp_dir();
+        let transcript = Transcript::create(&dir, "admin@box", "", Some((80, 24))).unwrap();
+        transcript.record(
+            Stream::Stdout,
+            b"\x1b[31mred\x1b[0m\tok\r\n\x1b]52;c;cGF3bmVk\x07\x1b]0;title\x1b\\done\n",
+        );
+        transcript.record(Stream::Stderr, "bell\x07 c1\u{9b}6n\n".as_bytes());
+        transcript.exit(0);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        replay(&dir, &transcript.id, false, false, true, &mut out, &mut err).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "red\tok\u{fffd}\ndone\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "bell\u{fffd} c1\u{fffd}6n\n"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replay_rejects_ids_outside_the_directory() {
+        let dir = temp_dir();
+        let err = replay(
+            &dir,
+            "../ssh-proxy",
+            false,
+            false,
+            false,
+            &mut io::sink(),
+            &mut io::sink(),
+        );
+        assert!(err.unwrap_err().contains("invalid session id"));
+        let err = replay(
+            &dir,
+            "1-2-3",
+            false,
+            false,
+            false,
+            &mut io::sink(),
+            &mut io::sink(),
+        );
+        assert!(err.unwrap_err().contains("no ssh session 1-2-3"));
+        assert!(
+            list(&dir)
+                .unwrap()
+                .starts_with("No ssh sessions recorded yet")
+        );
+    }
+}
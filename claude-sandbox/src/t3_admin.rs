@@ -290,32 +290,45 @@ fn approve_candidate(config: &Config, form: &HashMap<String, String>) -> Result<
     let scope = match form.get("scope").map(String::as_str) {
         Some("once") => ApprovalScope::Once,
         Some("persistent") => ApprovalScope::Persistent,
+        Some("push") => ApprovalScope::Push,
         _ => return Err("Invalid approval scope".to_string()),
     };
-    let candidate = managed_push::read_candidate(&config.state_dir, id)?;
-    let (_, current) = managed_push::resolve_relative_repository(
+    let reviewed = match (form.get("reference"), form.get("oid")) {
+        (Some(reference), Some(oid)) => Some(managed_push::PushTarget {
+            reference: reference.clone(),
+            oid: oid.clone(),
+        }),
+        _ => None,
+    };
+    let limits = managed_push::ApprovalLimits {
+        duration: form
+            .get("duration")
+            .filter(|value| !value.is_empty())
+            .map(|value| managed_push::parse_duration(value))
+            .transpose()?,
+        branches: form
+            .get("branches")
+            .map(|value| {
+                value
+                    .split([',', ' '])
+                    .filter(|pattern| !pattern.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    let current = managed_push::approve_candidate(
+        &config.state_dir,
         &config.workspace_root,
-        &candidate.repository.relative_path,
+        id,
+        scope,
+        reviewed.as_ref(),
+        &limits,
     )?;
-    if current.origin != candidate.repository.origin {
-        managed_push::record_candidate(
-            &config.state_dir,
-            &current,
-            candidate.previously_approved_origin,
-        )?;
-        managed_push::remove_candidate(&config.state_dir, id)?;
-        return Err(
-            "The repository origin changed. Review the new candidate before approving.".to_string(),
-        );
-    }
-    managed_push::approve(&config.state_dir, &current, scope)?;
-    managed_push::remove_candidate(&config.state_dir, id)?;
-    Ok(match scope {
-        ApprovalScope::Once => format!("Approved the next push from {}.", current.relative_path),
-        ApprovalScope::Persistent => {
-            format!("Approved persistent pushes from {}.", current.relative_path)
-        }
-    })
+    Ok(managed_push::approved_message(
+        scope,
+        &current.relative_path,
+    ))
 }
 
 fn dismiss_candidate(config: &Config, form: &HashMap<String, String>) -> Result<String, String> {
@@ -412,7 +425,7 @@ fn render_page(
         .unwrap_or_default();
     format!(
         r#"<!doctype html><html lang="en"><head><meta charset="utf-8"><meta name="viewport" content="width=device-width,initial-scale=1"><meta name="color-scheme" content="dark"><title>T3 Code · Admin</title><style>
-:root{{--ink:#f4f1e8;--muted:#a4a49b;--line:#353630;--acid:#d8ff4f;--danger:#ff8c7e;--bg:#11120f}}*{{box-sizing:border-box}}body{{margin:0;min-height:100vh;color:var(--ink);background:radial-gradient(circle at 85% 10%,#293315 0,transparent 30%),var(--bg);font:14px/1.55 "IBM Plex Mono","Courier New",monospace}}main{{width:min(920px,calc(100% - 32px));margin:auto;padding:48px 0 80px}}header{{border-top:1px solid var(--acid);padding-top:16px;margin-bottom:48px}}h1{{font:400 clamp(42px,8vw,76px)/.95 Georgia,serif;letter-spacing:-.05em;margin:18px 0}}h2{{font-size:13px;text-transform:uppercase;letter-spacing:.12em;color:var(--acid)}}section{{border:1px solid var(--line);padding:22px;margin:14px 0;background:#11120fd9}}p,small{{color:var(--muted)}}form{{display:inline-flex;gap:10px;align-items:end;margin:5px 8px 5px 0}}label{{display:grid;gap:7px}}input{{background:#090a08;color:var(--ink);border:1px solid var(--line);padding:12px}}button,.pair{{display:inline-block;border:0;background:var(--acid);color:#15170d;padding:12px 15px;font:700 11px/1 monospace;text-transform:uppercase;text-decoration:none;cursor:pointer}}button.secondary{{background:#34362e;color:var(--ink)}}button.danger{{background:#713a34;color:#fff}}.pair-result{{border-top:1px solid var(--line);margin-top:17px;padding-top:17px}}.pair-url{{width:100%;margin:5px 0 12px}}.repo{{padding:16px 0;border-top:1px solid var(--line)}}.repo:first-of-type{{border-top:0}}code{{color:var(--ink);overflow-wrap:anywhere}}.meta{{display:grid;grid-template-columns:100px 1fr;gap:5px 12px}}.notice{{border:1px solid #6f752f;padding:13px;margin-bottom:14px}}.changed{{color:var(--danger)}}footer{{margin-top:42px;color:#67685f;font-size:10px;text-transform:uppercase;letter-spacing:.12em}}@media(max-width:600px){{.meta{{grid-template-columns:1fr}}form{{display:flex;flex-wrap:wrap}}}}
+:root{{--ink:#f4f1e8;--muted:#a4a49b;--line:#353630;--acid:#d8ff4f;--danger:#ff8c7e;--bg:#11120f}}*{{box-sizing:border-box}}body{{margin:0;min-height:100vh;color:var(--ink);background:radial-gradient(circle at 85% 10%,#293315 0,transparent 30%),var(--bg);font:14px/1.55 "IBM Plex Mono","Courier New",monospace}}main{{width:min(920px,calc(100% - 32px));margin:auto;padding:48px 0 80px}}header{{border-top:1px solid var(--acid);padding-top:16px;margin-bottom:48px}}h1{{font:400 clamp(42px,8vw,76px)/.95 Georgia,serif;letter-spacing:-.05em;margin:18px 0}}h2{{font-size:13px;text-transform:uppercase;letter-spacing:.12em;color:var(--acid)}}section{{border:1px solid var(--line);padding:22px;margin:14px 0;background:#11120fd9}}p,small{{color:var(--muted)}}form{{display:inline-flex;gap:10px;align-items:end;margin:5px 8px 5px 0}}label{{display:grid;gap:7px}}input,select{{background:#090a08;color:var(--ink);border:1px solid var(--line);padding:12px}}button,.pair{{display:inline-block;border:0;background:var(--acid);color:#15170d;padding:12px 15px;font:700 11px/1 monospace;text-transform:uppercase;text-decoration:none;cursor:pointer}}button.secondary{{background:#34362e;color:var(--ink)}}button.danger{{background:#713a34;color:#fff}}.pair-result{{border-top:1px solid var(--line);margin-top:17px;padding-top:17px}}.pair-url{{width:100%;margin:5px 0 12px}}.repo{{padding:16px 0;border-top:1px solid var(--line)}}.repo:first-of-type{{border-top:0}}code{{color:var(--ink);overflow-wrap:anywhere}}pre{{margin:0;color:var(--ink);font:inherit;white-space:pre-wrap;overflow-wrap:anywhere}}.meta{{display:grid;grid-template-columns:100px 1fr;gap:5px 12px}}.notice{{border:1px solid #6f752f;padding:13px;margin-bottom:14px}}.changed{{color:var(--danger)}}footer{{margin-top:42px;color:#67685f;font-size:10px;text-transform:uppercase;letter-spacing:.12em}}@media(max-width:600px){{.meta{{grid-template-columns:1fr}}form{{display:flex;flex-wrap:wrap}}}}
 </style></head><body><main><header><small>Private control plane · {}</small><h1>T3 Code<br>Admin.</h1></header>{notice}{action}<footer>Host-owned administration surface</footer></main></body></html>"#,
         config.portal_port
     )
@@ -444,6 +457,21 @@ fn render_push_controls(config: &Config) -> String {
     }
     for (id, candidate) in candidates.into_iter().take(100) {
         let repository = candidate.repository;
+        let (push, push_button) = candidate
+            .push
+            .as_ref()
+            .map(|push| {
+                (
+                    render_push_preview(push),
+                    // Binds every approval to the push shown here
+                    format!(
+                        r#"<input type="hidden" name="reference" value="{}"><input type="hidden" name="oid" value="{}"><button name="scope" value="push">Approve this push</button>"#,
+                        escape_html(&push.target.reference),
+                        escape_html(&push.target.oid)
+                    ),
+                )
+            })
+            .unwrap_or_default();
         let changed = candidate
             .previously_approved_origin
             .as_ref()
@@ -455,8 +483,8 @@ fn render_push_controls(config: &Config) -> String {
             })
             .unwrap_or_default();
         html.push_str(&format!(
-            r#"<div class="repo"><div class="meta"><span>Repository</span><strong>{}</strong><span>Origin</span><code>{}</code><span>Branch</span><code>{}</code></div>{}<form method="post" action="/approve"><input type="hidden" name="csrf" value="{}"><input type="hidden" name="id" value="{}"><button name="scope" value="once">Approve once</button><button name="scope" value="persistent">Always allow</button></form><form method="post" action="/dismiss"><input type="hidden" name="csrf" value="{}"><input type="hidden" name="id" value="{}"><button class="secondary">Dismiss</button></form></div>"#,
-            escape_html(&repository.relative_path), escape_html(&repository.origin), escape_html(repository.branch.as_deref().unwrap_or("detached HEAD")), changed, config.csrf_token, id, config.csrf_token, id
+            r#"<div class="repo"><div class="meta"><span>Repository</span><strong>{}</strong><span>Origin</span><code>{}</code><span>Branch</span><code>{}</code>{}</div>{}<form method="post" action="/approve"><input type="hidden" name="csrf" value="{}"><input type="hidden" name="id" value="{}"><label>Expires<select name="duration"><option value="">Never</option><option value="1h">In 1 hour</option><option value="8h">In 8 hours</option><option value="24h">In 24 hours</option></select></label><label>Branches<input name="branches" placeholder="any, or agent/*"></label>{}<button name="scope" value="once">Approve once</button><button name="scope" value="persistent">Always allow</button></form><form method="post" action="/dismiss"><input type="hidden" name="csrf" value="{}"><input type="hidden" name="id" value="{}"><button class="secondary">Dismiss</button></form></div>"#,
+            escape_html(&repository.relative_path), escape_html(&repository.origin), escape_html(repository.branch.as_deref().unwrap_or("detached HEAD")), push, changed, config.csrf_token, id, push_button, config.csrf_token, id
         ));
     }
     html.push_str("</section><section><h2>Approved repositories</h2>");
@@ -464,20 +492,9 @@ fn render_push_controls(config: &Config) -> String {
         html.push_str("<p>No repositories are approved.</p>");
     }
     for (id, approval) in approvals {
-        let scope = match approval.scope {
-            ApprovalScope::Once => "next push only",
-            ApprovalScope::Persistent => "persistent",
-        };
-        let current_origin = managed_push::resolve_relative_repository(
-            &config.workspace_root,
-            &approval.repository.relative_path,
-        )
-        .ok()
-        .map(|(_, repository)| repository.origin);
-        let status = match current_origin {
-            Some(current) if current == approval.repository.origin => scope.to_string(),
-            Some(current) => format!("suspended — origin is now {}", escape_html(&current)),
-            None => "suspended — repository unavailable".to_string(),
+        let status = match managed_push::suspension(&config.workspace_root, &approval) {
+            None => escape_html(&managed_push::scope_label(&approval)),
+            Some(reason) => format!("suspended — {}", escape_html(&reason)),
         };
         html.push_str(&format!(
             r#"<div class="repo"><div class="meta"><span>Repository</span><strong>{}</strong><span>Origin</span><code>{}</code><span>Status</span><span>{}</span></div><form method="post" action="/revoke"><input type="hidden" name="csrf" value="{}"><input type="hidden" name="id" value="{}"><button class="danger">Revoke</button></form></div>"#,
@@ -485,9 +502,53 @@ fn render_push_controls(config: &Config) -> String {
         ));
     }
     html.push_str("</section>");
+    html.push_str(&render_history(
+        &managed_push::list_history(&config.state_dir).unwrap_or_default(),
+    ));
     html
 }
 
+/// The most recent approval events across repositories.
+fn render_history(events: &[managed_push::HistoryEvent]) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut html = String::from("<section><h2>Approval history</h2>");
+    if events.is_empty() {
+        html.push_str("<p>No approvals have been made yet.</p>");
+    }
+    for event in events.iter().take(50) {
+        html.push_str(&format!(
+            r#"<div class="meta"><span>{} ago</span><span><strong>{}</strong> {} <small>{}</small></span></div>"#,
+            managed_push::format_duration(now.saturating_sub(event.at)),
+            event.event.as_str(),
+            escape_html(&event.relative_path),
+            escape_html(&event.detail)
+        ));
+    }
+    html.push_str("</section>");
+    html
+}
+
+/// The held push's target, commits and diffstat, as rows of the `.meta` grid.
+fn render_push_preview(push: &managed_push::PushPreview) -> String {
+    let lines = |lines: &[String]| {
+        lines
+            .iter()
+            .map(|line| escape_html(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    format!(
+        "<span>Target</span><code>{}</code><span>Commit</span><code>{}</code><span>Commits</span><pre>{}</pre><span>Diffstat</span><pre>{}</pre>",
+        escape_html(&push.target.reference),
+        escape_html(&push.target.oid),
+        lines(&push.commits),
+        lines(&push.diffstat)
+    )
+}
+
 fn render_error(title: &str, message: &str) -> String {
     format!(
         "<!doctype html><meta charset=utf-8><title>{}</title><h1>{}</h1><p>{}</p>",
@@ -728,6 +789,22 @@ mod tests {
         assert!(html.contains("Creating or copying it does not pair this browser."));
     }
 
+    #[test]
+    fn renders_escaped_push_preview() {
+        let html = render_push_preview(&managed_push::PushPreview {
+            target: managed_push::PushTarget {
+                reference: "refs/heads/main".to_string(),
+                oid: "a".repeat(40),
+            },
+            commits: vec!["abc1234 <script>".to_string()],
+            diffstat: vec!["1 file(s) changed, +1 -0".to_string()],
+        });
+
+        assert!(html.contains("<code>refs/heads/main</code>"));
+        assert!(html.contains("abc1234 &lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
     #[test]
     fn hides_pairing_result_before_a_link_is_created() {
         let html = render_pairing_controls("csrf-token", None);
@@ -13,44 +13,138 @@ const SOCKET_PATH = fs.existsSync(RUNTIME_DIR)
 const invocation = path.basename(process.argv[1]);
 const args = process.argv.slice(2);
 
-function validate() {
-  if (invocation === "xclip") {
-    const expected = ["-selection", "clipboard", "-t", "image/png", "-o"];
-    if (
-      args.length === expected.length &&
-      args.every((a, i) => a === expected[i])
-    ) {
-      return;
+// Other names tools use for plain text
+const TEXT_TYPES = ["text", "UTF8_STRING", "STRING", "TEXT", "text/plain;charset=utf-8"];
+
+function fail(message) {
+  process.stderr.write(message + "\n");
+  process.exit(1);
+}
+
+function normalizeType(type) {
+  return TEXT_TYPES.includes(type) ? "text/plain" : type;
+}
+
+// xclip -selection clipboard [-t TYPE] -o, with -t TARGETS listing types, or
+// xclip -selection clipboard [-t TYPE] [-i] to copy stdin
+function parseXclip() {
+  let selection = "primary";
+  let type = null;
+  let output = false;
+  for (let i = 0; i < args.length; i++) {
+    const arg = args[i];
+    if (["-selection", "-sel", "-se"].includes(arg) && i + 1 < args.length) {
+      selection = args[++i];
+    } else if (["-t", "-target"].includes(arg) && i + 1 < args.length) {
+      type = args[++i];
+    } else if (["-o", "-out"].includes(arg)) {
+      output = true;
+    } else if (["-i", "-in"].includes(arg)) {
+      output = false;
+    } else {
+      fail("xclip (proxy): unsupported argument: " + arg);
     }
-    process.stderr.write(
-      "xclip (proxy): only 'xclip -selection clipboard -t image/png -o' is supported\n"
-    );
-    process.exit(1);
   }
+  if (!"clipboard".startsWith(selection) || selection.length === 0) {
+    fail("xclip (proxy): only the clipboard selection is supported (-selection clipboard)");
+  }
+  if (!output) {
+    const mime = normalizeType(type || "text/plain");
+    return { request: { command: "write", mime }, newline: false, input: readStdin() };
+  }
+  if (type === "TARGETS") {
+    return { request: { command: "list_types" }, newline: false };
+  }
+  return { request: { command: "read", mime: normalizeType(type || "text/plain") }, newline: false };
+}
 
-  if (invocation === "wl-paste") {
-    const expected = ["--type", "image/png"];
-    if (
-      args.length === expected.length &&
-      args.every((a, i) => a === expected[i])
-    ) {
-      return;
+// wl-paste [-t|--type TYPE] [-n|--no-newline], or -l|--list-types
+function parseWlPaste() {
+  let type = null;
+  let list = false;
+  let newline = true;
+  for (let i = 0; i < args.length; i++) {
+    const arg = args[i];
+    if (["-t", "--type"].includes(arg) && i + 1 < args.length) {
+      type = args[++i];
+    } else if (arg.startsWith("--type=")) {
+      type = arg.slice("--type=".length);
+    } else if (["-n", "--no-newline"].includes(arg)) {
+      newline = false;
+    } else if (["-l", "--list-types"].includes(arg)) {
+      list = true;
+    } else {
+      fail("wl-paste (proxy): unsupported argument: " + arg);
     }
-    process.stderr.write(
-      "wl-paste (proxy): only 'wl-paste --type image/png' is supported\n"
-    );
-    process.exit(1);
   }
+  if (list) {
+    return { request: { command: "list_types" }, newline: false };
+  }
+  const mime = normalizeType(type || "text/plain");
+  // Like wl-paste, end text with a newline unless told not to
+  return { request: { command: "read", mime }, newline: newline && mime === "text/plain" };
+}
 
-  process.stderr.write(
-    "clipboard-proxy-client: unsupported invocation: " + invocation + "\n"
-  );
-  process.exit(1);
+// wl-copy [-t|--type TYPE] [-n|--trim-newline] [TEXT...], copying stdin
+// when no text is given
+function parseWlCopy() {
+  let type = null;
+  let trim = false;
+  const words = [];
+  for (let i = 0; i < args.length; i++) {
+    const arg = args[i];
+    if (words.length === 0 && ["-t", "--type"].includes(arg) && i + 1 < args.length) {
+      type = args[++i];
+    } else if (words.length === 0 && arg.startsWith("--type=")) {
+      type = arg.slice("--type=".length);
+    } else if (words.length === 0 && ["-n", "--trim-newline"].includes(arg)) {
+      trim = true;
+    } else if (words.length === 0 && arg === "--") {
+      words.push(...args.slice(i + 1));
+      break;
+    } else if (words.length === 0 && arg.startsWith("-")) {
+      fail("wl-copy (proxy): unsupported argument: " + arg);
+    } else {
+      words.push(arg);
+    }
+  }
+  let input = words.length > 0 ? Buffer.from(words.join(" ")) : readStdin();
+  if (trim && input.length > 0 && input[input.length - 1] === 0x0a) {
+    input = input.subarray(0, input.length - 1);
+  }
+  return {
+    request: { command: "write", mime: normalizeType(type || "text/plain") },
+    newline: false,
+    input,
+  };
 }
 
-validate();
+function readStdin() {
+  try {
+    return fs.readFileSync(0);
+  } catch (e) {
+    fail("clipboard-proxy-client: failed to read stdin: " + e.message);
+  }
+}
 
-const request = JSON.stringify({ command: "read_image" }) + "\n";
+function parseArgs() {
+  if (invocation === "xclip") {
+    return parseXclip();
+  }
+  if (invocation === "wl-paste") {
+    return parseWlPaste();
+  }
+  if (invocation === "wl-copy") {
+    return parseWlCopy();
+  }
+  fail("clipboard-proxy-client: unsupported invocation: " + invocation);
+}
+
+const { request: parsed, newline, input } = parseArgs();
+if (input !== undefined) {
+  parsed.data_b64 = input.toString("base64");
+}
+const request = JSON.stringify(parsed) + "\n";
 
 const socket = net.createConnection(SOCKET_PATH, () => {
   socket.write(request);
@@ -69,8 +163,13 @@ socket.on("end", () => {
       const buf = Buffer.from(response.stdout_b64, "base64");
       process.stdout.write(buf);
     }
+    if (newline && response.exit_code === 0) {
+      process.stdout.write("\n");
+    }
     if (response.stderr) {
-      process.stderr.write(response.stderr);
+      process.stderr.write(
+        response.stderr.endsWith("\n") ? response.stderr : response.stderr + "\n"
+      );
     }
     process.exit(response.exit_code);
   } catch (e) {
@@ -14,6 +14,24 @@ if [ -S /run/claude-sandbox/ssh-proxy.sock ] || [ -S /workspace/.claude-sandbox/
     git config --global core.sshCommand "/usr/local/bin/ssh"
 fi
 
+# Publish policy-controlled SSH port forwards on localhost (if any are configured)
+if compgen -G "/run/claude-sandbox/ssh-forward-*.sock" > /dev/null || compgen -G "/workspace/.claude-sandbox/ssh-forward-*.sock" > /dev/null; then
+    /usr/local/bin/ssh-forward-relay &
+fi
+
+# Sign commits and tags with the host key through the signing proxy (if configured)
+if [ -n "$GIT_SIGNING_FORMAT" ] && { [ -S /run/claude-sandbox/signing-proxy.sock ] || [ -S /workspace/.claude-sandbox/signing-proxy.sock ]; }; then
+    git config --global gpg.format "$GIT_SIGNING_FORMAT"
+    if [ "$GIT_SIGNING_FORMAT" = "ssh" ]; then
+        git config --global gpg.ssh.program /usr/local/bin/git-sign-client
+    else
+        git config --global gpg.program /usr/local/bin/git-sign-client
+    fi
+    git config --global user.signingkey "$GIT_SIGNING_KEY"
+    git config --global commit.gpgSign true
+    git config --global tag.gpgSign true
+fi
+
 # Merge image MCP config into project-level config
 if [ -f /etc/claude/mcp.json ]; then
     MCP_TARGET=/workspace/.mcp.json
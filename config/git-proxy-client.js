@@ -57,7 +57,7 @@ socket.on("end", () => {
         if (result.status !== 0) {
           exitCode = 1;
           trackingError =
-            "git-proxy-client: push succeeded, but the local tracking ref could not be updated\n" +
+            `git-proxy-client: ${args[0]} succeeded, but the local tracking ref could not be updated\n` +
             (result.stderr || result.error?.message || "");
         }
       }
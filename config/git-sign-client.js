@@ -0,0 +1,92 @@
+#!/usr/bin/env node
+"use strict";
+
+// Stands in for gpg.program / gpg.ssh.program. Git hands the commit or tag
+// payload to this shim; the host checks it and signs with a key that never
+// enters the container.
+
+const net = require("net");
+const fs = require("fs");
+
+const RUNTIME_DIR = "/run/claude-sandbox";
+const SOCKET_PATH = fs.existsSync(RUNTIME_DIR)
+  ? `${RUNTIME_DIR}/signing-proxy.sock`
+  : "/workspace/.claude-sandbox/signing-proxy.sock";
+
+const args = process.argv.slice(2);
+
+function fail(message) {
+  process.stderr.write(`git-sign-client: ${message}\n`);
+  process.exit(1);
+}
+
+function sign(format, namespace, payload, done) {
+  const request = JSON.stringify({
+    format,
+    namespace,
+    payload_b64: payload.toString("base64"),
+    cwd: process.cwd(),
+  }) + "\n";
+  const socket = net.createConnection(SOCKET_PATH, () => {
+    socket.write(request);
+  });
+  let data = "";
+  socket.on("data", (chunk) => {
+    data += chunk.toString();
+  });
+  socket.on("end", () => {
+    let response;
+    try {
+      response = JSON.parse(data.trim());
+    } catch (e) {
+      fail("failed to parse response: " + e.message);
+    }
+    if (response.stderr) {
+      process.stderr.write(response.stderr);
+    }
+    if (response.exit_code !== 0) {
+      process.exit(response.exit_code || 1);
+    }
+    done(response.signature);
+  });
+  socket.on("error", (err) => {
+    fail("connection error: " + err.message);
+  });
+}
+
+if (args[0] === "-Y") {
+  // ssh-keygen -Y sign -n <namespace> -f <key> [-U] <file>
+  if (args[1] !== "sign") {
+    fail(`only signing is bridged to the host (got -Y ${args[1] || ""})`);
+  }
+  let namespace = null;
+  let file = null;
+  for (let i = 2; i < args.length; i++) {
+    if (args[i] === "-n" || args[i] === "-f") {
+      if (args[i] === "-n") namespace = args[i + 1];
+      i++;
+    } else if (args[i] === "-U") {
+      continue;
+    } else {
+      file = args[i];
+    }
+  }
+  if (!file) {
+    fail("no file to sign");
+  }
+  sign("ssh", namespace, fs.readFileSync(file), (signature) => {
+    fs.writeFileSync(`${file}.sig`, signature);
+    process.exit(0);
+  });
+} else if (args.some((arg) => /^-[a-z]*b[a-z]*s/.test(arg) || arg === "--detach-sign")) {
+  // gpg --status-fd=2 -bsau <key>, payload on stdin
+  const chunks = [];
+  process.stdin.on("data", (chunk) => chunks.push(chunk));
+  process.stdin.on("end", () => {
+    sign("openpgp", null, Buffer.concat(chunks), (signature) => {
+      process.stdout.write(signature, () => process.exit(0));
+    });
+  });
+} else {
+  fail("only signing is bridged to the host; verify signatures on the host");
+}
@@ -1,7 +1,10 @@
 #!/bin/sh
-# git shim: bridge exactly `git push` / `git push --tags` to the host-side
-# push proxy when it is enabled (claude-sandbox --allow-push). Everything
-# else execs the real git in-place, so behavior is byte-for-byte identical.
+# git shim: bridge exactly `git push` / `git push --tags` /
+# `git push --force-with-lease` and
+# `git fetch [origin]` to the host-side git proxy when it is enabled
+# (claude-sandbox --allow-push). `git pull` is a bridged fetch followed by a
+# local merge or rebase onto the upstream branch. Everything else execs the
+# real git in-place, so behavior is byte-for-byte identical.
 
 if [ -d /run/claude-sandbox ]; then
     SOCKET=/run/claude-sandbox/git-proxy.sock
@@ -12,7 +15,7 @@ fi
 if [ "$1" = "push" ]; then
     if [ -S "$SOCKET" ]; then
         case "$*" in
-            push|"push --tags")
+            push|"push --tags"|"push --force-with-lease")
                 exec /usr/local/bin/git-proxy-client "$@"
                 ;;
         esac
@@ -21,7 +24,7 @@ if [ "$1" = "push" ]; then
     status=$?
     if [ "$status" -ne 0 ]; then
         if [ -S "$SOCKET" ]; then
-            echo "hint: only plain 'git push' and 'git push --tags' are bridged to the host" >&2
+            echo "hint: only plain 'git push', 'git push --tags' and 'git push --force-with-lease' are bridged to the host" >&2
         else
             echo "hint: pushes from the sandbox are disabled; relaunch with 'claude-sandbox --allow-push' to enable them" >&2
         fi
@@ -29,4 +32,40 @@ if [ "$1" = "push" ]; then
     exit "$status"
 fi
 
+if [ "$1" = "fetch" ] && [ -S "$SOCKET" ]; then
+    case "$*" in
+        fetch|"fetch origin")
+            exec /usr/local/bin/git-proxy-client "$@"
+            ;;
+    esac
+    /usr/bin/git "$@"
+    status=$?
+    if [ "$status" -ne 0 ]; then
+        echo "hint: only plain 'git fetch' and 'git fetch origin' are bridged to the host" >&2
+    fi
+    exit "$status"
+fi
+
+if [ "$1" = "pull" ] && [ -S "$SOCKET" ]; then
+    case "$*" in
+        pull|"pull --rebase"|"pull --ff-only")
+            /usr/local/bin/git-proxy-client fetch origin || exit $?
+            if [ "$2" = "--rebase" ] ||
+                { [ -z "$2" ] && [ "$(/usr/bin/git config --bool pull.rebase)" = "true" ]; }; then
+                exec /usr/bin/git rebase "@{upstream}"
+            fi
+            if [ "$2" = "--ff-only" ] || [ "$(/usr/bin/git config pull.ff)" = "only" ]; then
+                exec /usr/bin/git merge --ff-only "@{upstream}"
+            fi
+            exec /usr/bin/git merge "@{upstream}"
+            ;;
+    esac
+    /usr/bin/git "$@"
+    status=$?
+    if [ "$status" -ne 0 ]; then
+        echo "hint: only 'git pull', 'git pull --rebase' and 'git pull --ff-only' are bridged to the host" >&2
+    fi
+    exit "$status"
+fi
+
 exec /usr/bin/git "$@"
@@ -0,0 +1,5 @@
+#!/bin/sh
+# scp shim: run the real scp over the ssh proxy. `-O` selects the scp
+# protocol, whose remote `scp -t`/`scp -f` command the proxy checks against
+# transfer rules; the SFTP protocol would hide paths from it.
+exec /usr/bin/scp -O -S /usr/local/bin/ssh-transfer "$@"
@@ -0,0 +1,42 @@
+#!/usr/bin/env node
+"use strict";
+
+// Publish each host-side SSH forward socket (ssh-forward-<port>.sock) on
+// 127.0.0.1:<port> so TCP clients inside the container can use the tunnel.
+
+const net = require("net");
+const fs = require("fs");
+const path = require("path");
+
+const RUNTIME_DIR = fs.existsSync("/run/claude-sandbox")
+  ? "/run/claude-sandbox"
+  : "/workspace/.claude-sandbox";
+const SOCKET_PATTERN = /^ssh-forward-(\d+)\.sock$/;
+
+let entries;
+try {
+  entries = fs.readdirSync(RUNTIME_DIR);
+} catch (e) {
+  process.exit(0);
+}
+
+for (const name of entries) {
+  const match = SOCKET_PATTERN.exec(name);
+  if (!match) continue;
+
+  const port = Number(match[1]);
+  const socketPath = path.join(RUNTIME_DIR, name);
+  const server = net.createServer((client) => {
+    const upstream = net.connect(socketPath);
+    client.pipe(upstream);
+    upstream.pipe(client);
+    client.on("error", () => upstream.destroy());
+    upstream.on("error", () => client.destroy());
+  });
+  server.on("error", (err) => {
+    process.stderr.write(
+      `ssh-forward-relay: cannot listen on 127.0.0.1:${port}: ${err.message}\n`
+    );
+  });
+  server.listen(port, "127.0.0.1");
+}
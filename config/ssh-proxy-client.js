@@ -9,8 +9,56 @@ const SOCKET_PATH = fs.existsSync(RUNTIME_DIR)
   ? `${RUNTIME_DIR}/ssh-proxy.sock`
   : "/workspace/.claude-sandbox/ssh-proxy.sock";
 
+const FRAME_STDIN = 3;
+const FRAME_RESIZE = 4;
+const MAX_FRAME = 65536;
+
 const args = process.argv.slice(2);
-const request = JSON.stringify({ args }) + "\n";
+// A bare `ssh user@host` in a terminal asks for an interactive session
+const interactive =
+  args.length === 1 &&
+  !args[0].startsWith("-") &&
+  process.stdin.isTTY &&
+  process.stdout.isTTY;
+const request =
+  JSON.stringify(interactive ? { args, pty: terminalSize() } : { args }) + "\n";
+
+function terminalSize() {
+  return { cols: process.stdout.columns || 80, rows: process.stdout.rows || 24 };
+}
+
+function sendFrame(type, payload) {
+  let offset = 0;
+  do {
+    const part = payload.slice(offset, offset + MAX_FRAME);
+    const header = Buffer.alloc(5);
+    header[0] = type;
+    header.writeUInt32BE(part.length, 1);
+    socket.write(Buffer.concat([header, part]));
+    offset += MAX_FRAME;
+  } while (offset < payload.length);
+}
+
+function startInteractive() {
+  process.stdin.setRawMode(true);
+  process.stdin.on("data", (chunk) => sendFrame(FRAME_STDIN, chunk));
+  process.stdin.on("end", () => socket.end());
+  process.stdout.on("resize", () => {
+    const { cols, rows } = terminalSize();
+    const payload = Buffer.alloc(4);
+    payload.writeUInt16BE(cols, 0);
+    payload.writeUInt16BE(rows, 2);
+    sendFrame(FRAME_RESIZE, payload);
+  });
+  process.stdin.resume();
+}
+
+function exit(code) {
+  if (interactive && process.stdin.isRaw) {
+    process.stdin.setRawMode(false);
+  }
+  process.exit(code);
+}
 
 const socket = new net.Socket({ allowHalfOpen: true });
 
@@ -56,8 +104,12 @@ socket.on("data", (chunk) => {
 
     handshakeDone = true;
 
-    process.stdin.pipe(socket, { end: true });
-    process.stdin.resume();
+    if (interactive) {
+      startInteractive();
+    } else {
+      process.stdin.pipe(socket, { end: true });
+      process.stdin.resume();
+    }
 
     if (remaining.length > 0) {
       processFrames(remaining);
@@ -86,13 +138,13 @@ function processFrames(data) {
       process.stderr.write(payload);
     } else if (type === 0) {
       const exitCode = payload.readInt32BE(0);
-      process.exit(exitCode);
+      exit(exitCode);
     }
   }
 }
 
 socket.on("end", () => {
-  process.exit(1);
+  exit(1);
 });
 
 socket.on("error", (err) => {
@@ -0,0 +1,53 @@
+#!/bin/sh
+# ssh stand-in for `scp -S` and `rsync -e`: drop the fixed ssh options they
+# pass, fold `-l user` into the destination and hand `user@host command` to
+# the ssh proxy shim as a single command string. The host-side proxy only
+# ever runs its own ssh, so dropped options could not have taken effect.
+
+user=""
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -x|-a|-T|-q|-4|-6|-C)
+            shift
+            ;;
+        -o)
+            shift 2
+            ;;
+        -o*)
+            shift
+            ;;
+        -l)
+            user="$2"
+            shift 2
+            ;;
+        --)
+            shift
+            break
+            ;;
+        -*)
+            echo "ssh-proxy: unsupported ssh option $1 for scp/rsync" >&2
+            exit 255
+            ;;
+        *)
+            break
+            ;;
+    esac
+done
+
+if [ $# -lt 2 ]; then
+    echo "ssh-proxy: expected a destination and a remote command" >&2
+    exit 255
+fi
+
+dest="$1"
+shift
+case "$dest" in
+    *@*) ;;
+    *)
+        if [ -n "$user" ]; then
+            dest="$user@$dest"
+        fi
+        ;;
+esac
+
+exec /usr/local/bin/ssh "$dest" "$*"